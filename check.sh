@@ -37,6 +37,11 @@ for_all_features() {
 	echo "features: macro, std"
 	echo "--------------------"
 	$* --no-default-features --features macro,std
+
+	echo
+	echo "features: io-uring"
+	echo "------------------"
+	$* --features io-uring
 }
 
 echo
@@ -44,6 +49,11 @@ echo "Clippy"
 echo "======"
 for_all_features cargo clippy --workspace --all-targets
 
+echo
+echo "Minimum supported Rust version"
+echo "=============================="
+for_all_features cargo +1.70 check --workspace
+
 echo
 echo "Doctests"
 echo "========"
@@ -118,6 +118,47 @@ impl<F: CompletionFuture> CompletionFuture for std::panic::AssertUnwindSafe<F> {
     }
 }
 
+/// A [`CompletionFuture`] that tracks whether it has completed.
+///
+/// This allows users to check whether a future may still be polled, for example when repeatedly
+/// selecting over several futures in a loop.
+pub trait FusedCompletionFuture: CompletionFuture {
+    /// Returns `true` if the future has reached the complete state, either by returning a value
+    /// from [`poll`](CompletionFuture::poll) or by finishing cancellation, and so should not be
+    /// polled again.
+    fn is_terminated(&self) -> bool;
+}
+
+impl<F: FusedCompletionFuture + Unpin + ?Sized> FusedCompletionFuture for &'_ mut F {
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<F: FusedCompletionFuture + Unpin + ?Sized> FusedCompletionFuture for alloc::boxed::Box<F> {
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+impl<P> FusedCompletionFuture for Pin<P>
+where
+    P: Unpin + DerefMut,
+    P::Target: FusedCompletionFuture,
+{
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: FusedCompletionFuture> FusedCompletionFuture for std::panic::AssertUnwindSafe<F> {
+    fn is_terminated(&self) -> bool {
+        self.0.is_terminated()
+    }
+}
+
 macro_rules! derive_completion_future {
     ($([$($generics:tt)*] $t:ty,)*) => {
         $(
@@ -272,3 +313,44 @@ impl<S: CompletionStream> CompletionStream for std::panic::AssertUnwindSafe<S> {
         self.0.size_hint()
     }
 }
+
+/// A [`CompletionStream`] that tracks whether it has been exhausted.
+///
+/// This allows users to check whether a stream may still be polled, for example when repeatedly
+/// selecting over several streams in a loop.
+pub trait FusedCompletionStream: CompletionStream {
+    /// Returns `true` if the stream has reached the exhausted state, either by returning
+    /// [`None`] from [`poll_next`](CompletionStream::poll_next) or by finishing cancellation, and
+    /// so should not be polled again.
+    fn is_terminated(&self) -> bool;
+}
+
+impl<S: FusedCompletionStream + Unpin + ?Sized> FusedCompletionStream for &'_ mut S {
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S: FusedCompletionStream + Unpin + ?Sized> FusedCompletionStream for alloc::boxed::Box<S> {
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+impl<P> FusedCompletionStream for Pin<P>
+where
+    P: Unpin + DerefMut,
+    P::Target: FusedCompletionStream,
+{
+    fn is_terminated(&self) -> bool {
+        (**self).is_terminated()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: FusedCompletionStream> FusedCompletionStream for std::panic::AssertUnwindSafe<S> {
+    fn is_terminated(&self) -> bool {
+        self.0.is_terminated()
+    }
+}
@@ -34,3 +34,6 @@ pub use write::*;
 
 mod seek;
 pub use seek::*;
+
+mod owned;
+pub use owned::*;
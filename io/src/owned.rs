@@ -0,0 +1,282 @@
+use std::io::Result;
+
+use completion_core::CompletionFuture;
+
+/// A buffer that can be passed by value to an I/O operation.
+///
+/// Because the operation owns the buffer instead of borrowing it, the operation does not need to
+/// borrow anything from the caller. This allows the buffer to be kept alive for as long as the
+/// operation runs, for example by a spawned task or by a driver that registers buffers ahead of
+/// time.
+///
+/// # Safety
+///
+/// The pointer returned by [`stable_ptr`](Self::stable_ptr) must point to
+/// [`bytes_total`](Self::bytes_total) bytes of memory, of which the first
+/// [`bytes_init`](Self::bytes_init) are initialized. The pointer must remain valid when the buffer
+/// is moved, until the buffer is dropped or accessed mutably.
+///
+/// This only guarantees that the memory stays at the same address. Moving some buffers, such as
+/// `Box<[u8]>`, asserts unique access to their memory, so references to the memory created before
+/// the move must not be used after it. Users that lend the memory out while the buffer is moved
+/// around, for example inside a future, should keep the buffer itself in place, such as by boxing
+/// it.
+pub unsafe trait IoBuf: Unpin + 'static {
+    /// Get a pointer to the start of the buffer.
+    fn stable_ptr(&self) -> *const u8;
+
+    /// The number of initialized bytes at the start of the buffer. These are the bytes that are
+    /// written by an [`OwnedAsyncWrite`]r.
+    fn bytes_init(&self) -> usize;
+
+    /// The total number of bytes in the buffer, including those that are not initialized.
+    fn bytes_total(&self) -> usize;
+}
+
+/// A mutable buffer that can be passed by value to an I/O operation.
+///
+/// # Safety
+///
+/// The pointer returned by [`stable_mut_ptr`](Self::stable_mut_ptr) must be the same as the one
+/// returned by [`stable_ptr`](IoBuf::stable_ptr), and writing to any of its
+/// [`bytes_total`](IoBuf::bytes_total) bytes must be valid.
+pub unsafe trait IoBufMut: IoBuf {
+    /// Get a mutable pointer to the start of the buffer.
+    fn stable_mut_ptr(&mut self) -> *mut u8;
+
+    /// Mark the first `n` bytes of the buffer as initialized. If more bytes than that are already
+    /// initialized, this does nothing.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` bytes of the buffer must have been initialized, and `n` must not exceed
+    /// [`bytes_total`](IoBuf::bytes_total).
+    unsafe fn set_init(&mut self, n: usize);
+}
+
+unsafe impl IoBuf for Vec<u8> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+unsafe impl IoBufMut for Vec<u8> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+    unsafe fn set_init(&mut self, n: usize) {
+        if self.len() < n {
+            self.set_len(n);
+        }
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+    unsafe fn set_init(&mut self, _n: usize) {}
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for &'static str {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "bytes")]
+unsafe impl IoBuf for bytes::Bytes {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "bytes")]
+unsafe impl IoBuf for bytes::BytesMut {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+#[cfg(feature = "bytes")]
+unsafe impl IoBufMut for bytes::BytesMut {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        // Dereferencing to a slice would give a pointer to only the first `len` bytes. Emptying the
+        // buffer first makes the spare capacity cover all of it.
+        let len = self.len();
+        unsafe { self.set_len(0) };
+        let ptr = self.spare_capacity_mut().as_mut_ptr().cast::<u8>();
+        unsafe { self.set_len(len) };
+        ptr
+    }
+    unsafe fn set_init(&mut self, n: usize) {
+        if self.len() < n {
+            self.set_len(n);
+        }
+    }
+}
+
+/// Read bytes from a source asynchronously into owned buffers.
+///
+/// This is like [`AsyncRead`](crate::AsyncRead), but the buffer is passed by value and returned
+/// alongside the result.
+///
+/// You should not implement this trait manually, instead implement [`OwnedAsyncReadWith`].
+pub trait OwnedAsyncRead<B: IoBufMut>: for<'a> OwnedAsyncReadWith<'a, B> {}
+impl<B: IoBufMut, T: for<'a> OwnedAsyncReadWith<'a, B> + ?Sized> OwnedAsyncRead<B> for T {}
+
+/// Read bytes from a source asynchronously into owned buffers with a specific lifetime.
+pub trait OwnedAsyncReadWith<'a, B: IoBufMut> {
+    /// The future that reads from the source, and outputs the number of bytes read along with the
+    /// buffer.
+    type ReadOwnedFuture: CompletionFuture<Output = (Result<usize>, B)>;
+
+    /// Pull some bytes from this source into the start of the buffer, overwriting anything that
+    /// was there before.
+    ///
+    /// Up to [`bytes_total`](IoBuf::bytes_total) bytes are read, and the read bytes are marked
+    /// as initialized. If this reads 0 bytes of data, either the buffer was 0 bytes in length or
+    /// the stream has reached EOF.
+    fn read_owned(&'a mut self, buf: B) -> Self::ReadOwnedFuture;
+}
+
+impl<'a, B: IoBufMut, R: OwnedAsyncReadWith<'a, B> + ?Sized> OwnedAsyncReadWith<'a, B> for &mut R {
+    type ReadOwnedFuture = R::ReadOwnedFuture;
+
+    fn read_owned(&'a mut self, buf: B) -> Self::ReadOwnedFuture {
+        (**self).read_owned(buf)
+    }
+}
+
+impl<'a, B: IoBufMut, R: OwnedAsyncReadWith<'a, B> + ?Sized> OwnedAsyncReadWith<'a, B> for Box<R> {
+    type ReadOwnedFuture = R::ReadOwnedFuture;
+
+    fn read_owned(&'a mut self, buf: B) -> Self::ReadOwnedFuture {
+        (**self).read_owned(buf)
+    }
+}
+
+/// Write bytes to a source asynchronously from owned buffers.
+///
+/// This is like [`AsyncWrite`](crate::AsyncWrite), but the buffer is passed by value and returned
+/// alongside the result. There is no way to flush an `OwnedAsyncWrite`r; types that buffer their
+/// output should implement [`AsyncWrite`](crate::AsyncWrite) as well.
+///
+/// You should not implement this trait manually, instead implement [`OwnedAsyncWriteWith`].
+pub trait OwnedAsyncWrite<B: IoBuf>: for<'a> OwnedAsyncWriteWith<'a, B> {}
+impl<B: IoBuf, T: for<'a> OwnedAsyncWriteWith<'a, B> + ?Sized> OwnedAsyncWrite<B> for T {}
+
+/// Write bytes to a source asynchronously from owned buffers with a specific lifetime.
+pub trait OwnedAsyncWriteWith<'a, B: IoBuf> {
+    /// The future that writes to the source, and outputs the number of bytes written along with
+    /// the buffer.
+    type WriteOwnedFuture: CompletionFuture<Output = (Result<usize>, B)>;
+
+    /// Write the initialized bytes of the buffer to the writer, returning how many bytes were
+    /// written.
+    fn write_owned(&'a mut self, buf: B) -> Self::WriteOwnedFuture;
+}
+
+impl<'a, B: IoBuf, W: OwnedAsyncWriteWith<'a, B> + ?Sized> OwnedAsyncWriteWith<'a, B> for &mut W {
+    type WriteOwnedFuture = W::WriteOwnedFuture;
+
+    fn write_owned(&'a mut self, buf: B) -> Self::WriteOwnedFuture {
+        (**self).write_owned(buf)
+    }
+}
+
+impl<'a, B: IoBuf, W: OwnedAsyncWriteWith<'a, B> + ?Sized> OwnedAsyncWriteWith<'a, B> for Box<W> {
+    type WriteOwnedFuture = W::WriteOwnedFuture;
+
+    fn write_owned(&'a mut self, buf: B) -> Self::WriteOwnedFuture {
+        (**self).write_owned(buf)
+    }
+}
+
+#[cfg(all(test, feature = "bytes"))]
+mod tests {
+    use super::*;
+
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn bytes() {
+        let buf = Bytes::from_static(b"Hello");
+        assert_eq!(buf.stable_ptr(), buf.as_ptr());
+        assert_eq!(buf.bytes_init(), 5);
+        assert_eq!(buf.bytes_total(), 5);
+    }
+
+    #[test]
+    fn bytes_mut() {
+        let mut buf = BytesMut::with_capacity(8);
+        buf.extend_from_slice(b"Hi");
+        assert_eq!(buf.stable_ptr(), buf.as_ptr());
+        assert_eq!(buf.bytes_init(), 2);
+        let total = buf.bytes_total();
+        assert!(total >= 8);
+
+        // The whole capacity can be written through the pointer, not just the initialized part.
+        let ptr = buf.stable_mut_ptr();
+        assert_eq!(ptr.cast_const(), buf.as_ptr());
+        assert_eq!(buf.len(), 2);
+        unsafe {
+            ptr.write_bytes(b'x', total);
+            buf.set_init(total);
+        }
+        assert_eq!(buf.len(), total);
+        assert!(buf.iter().all(|&b| b == b'x'));
+
+        // Marking fewer bytes as initialized than already are does nothing.
+        unsafe { buf.set_init(1) };
+        assert_eq!(buf.len(), total);
+    }
+}
@@ -236,6 +236,23 @@ fn test_read_cursor() {
     });
 }
 
+#[test]
+fn test_assume_init_partially_filled() {
+    let mut bytes = [MaybeUninit::uninit(); 4];
+    let mut buf = ReadBuf::uninit(&mut bytes);
+
+    for chunk in [[1, 2], [3, 4]] {
+        unsafe {
+            buf.unfilled_mut()[..2].copy_from_slice(&chunk.map(MaybeUninit::new));
+            buf.assume_init(2);
+        }
+        buf.add_filled(2);
+    }
+
+    assert_eq!(buf.initialized(), &[1, 2, 3, 4]);
+    assert_eq!(buf.into_filled(), &[1, 2, 3, 4]);
+}
+
 #[cfg(test)]
 #[allow(dead_code, clippy::extra_unused_lifetimes)]
 fn test_impls_traits<'a>() {
@@ -438,7 +455,7 @@ macro_rules! common_read_buf_methods {
             let buf = $get_mut(self);
             let new = buf.filled + n;
             if new > buf.initialized {
-                buf.initialized = n;
+                buf.initialized = new;
             }
         }
 
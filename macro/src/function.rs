@@ -69,18 +69,21 @@ pub(crate) fn transform(mut f: AnyFn, boxed: Option<Boxed>, crate_path: &CratePa
     };
 
     let crate_path_async_span = crate_path.with_span(async_span);
-    let ret_ty_bounds = quote_spanned! {async_span=>
-        #crate_path_async_span::CompletionFuture<Output = #ret_ty> + #ret_lifetime
-    };
     let ret_ty = if let Some(boxed) = &boxed {
         let send = if boxed.send {
             Some(quote_spanned!(boxed.span=> + ::core::marker::Send))
         } else {
             None
         };
-        quote_spanned!(boxed.span=> ::core::pin::Pin<::std::boxed::Box<dyn #ret_ty_bounds #send>>)
+        quote_spanned! {boxed.span=>
+            ::core::pin::Pin<::std::boxed::Box<
+                dyn #crate_path_async_span::CompletionFuture<Output = #ret_ty> + #ret_lifetime #send
+            >>
+        }
     } else {
-        quote_spanned!(async_span=> impl #ret_ty_bounds)
+        quote_spanned! {async_span=>
+            impl #crate_path_async_span::FusedCompletionFuture<Output = #ret_ty> + #ret_lifetime
+        }
     };
     f.sig.output = ReturnType::Type(rarrow, Box::new(Type::Verbatim(ret_ty)));
 
@@ -194,7 +197,7 @@ mod tests {
         let in_scope = in_scope();
         let output = quote! {
             #[attr]
-            fn foo<'__completion_future>() -> impl c::CompletionFuture<Output = ()> + '__completion_future {
+            fn foo<'__completion_future>() -> impl c::FusedCompletionFuture<Output = ()> + '__completion_future {
                 c::__completion_async(async move {
                     #[allow(unused_imports)]
                     use c::__CompletionFutureIntoAwaitable;
@@ -224,7 +227,7 @@ mod tests {
             pub(super) fn do_stuff<'__completion_future, '__life2, '__life1, '__life0, T: Clone>(
                 &'__life0 mut self,
                 x: &'__life1 &'__life2 T
-            ) -> impl ::crate::path::CompletionFuture<Output = Vec<u8> > + '__completion_future
+            ) -> impl ::crate::path::FusedCompletionFuture<Output = Vec<u8> > + '__completion_future
             where
                 '__life2: '__completion_future,
                 '__life1: '__completion_future,
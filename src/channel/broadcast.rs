@@ -0,0 +1,470 @@
+//! A multi-producer, multi-consumer channel where every receiver sees every value.
+//!
+//! The channel holds a fixed number of values. When it is full, sending a value overwrites the
+//! oldest one, and receivers that had not yet received it are told how many values they missed
+//! with a [`Lagged`] error.
+//!
+//! # Examples
+//!
+//! ```
+//! use completion::{channel::broadcast, future, CompletionStreamExt};
+//!
+//! let (sender, mut receiver) = broadcast::channel(16);
+//! let other = sender.subscribe();
+//!
+//! for i in 0..5 {
+//!     sender.send(i).unwrap();
+//! }
+//! drop(sender);
+//!
+//! assert_eq!(future::block_on(receiver.recv()), Ok(0));
+//!
+//! // Receivers are streams, which end once all the senders have been dropped.
+//! let evens: Vec<_> = future::block_on(
+//!     other.filter_map(Result::ok).filter(|n| n % 2 == 0).collect(),
+//! );
+//! assert_eq!(evens, [0, 2, 4]);
+//! ```
+
+use core::fmt::{self, Debug, Display, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use completion_core::{CompletionFuture, CompletionStream};
+use futures_core::Stream;
+
+use crate::lock;
+
+/// Create a new broadcast channel that holds the last `capacity` values sent.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+#[must_use]
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be non-zero");
+
+    let shared = Arc::new(Mutex::new(Shared {
+        buffer: VecDeque::with_capacity(capacity),
+        capacity,
+        head: 0,
+        senders: 1,
+        receivers: 1,
+        waiters: Vec::new(),
+        next_id: 1,
+    }));
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver {
+            shared,
+            next: 0,
+            id: 0,
+        },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    buffer: VecDeque<T>,
+    capacity: usize,
+    /// The position of the first value in the buffer.
+    head: u64,
+    senders: usize,
+    receivers: usize,
+    /// The IDs and wakers of the receivers waiting for a value.
+    waiters: Vec<(usize, Waker)>,
+    next_id: usize,
+}
+
+impl<T> Shared<T> {
+    /// The position the next value sent will have.
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    /// Register a new receiver, returning its ID.
+    fn subscribe(&mut self) -> usize {
+        self.receivers += 1;
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+/// The sending half of a [`broadcast`](self) channel.
+///
+/// Senders can be cloned to send from multiple tasks.
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value to all the receivers.
+    ///
+    /// This never waits; if the channel is full the oldest value is overwritten. Returns the
+    /// number of receivers the value was sent to.
+    ///
+    /// # Errors
+    ///
+    /// Fails and gives back the value if there are no receivers.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let mut shared = lock(&self.shared);
+        if shared.receivers == 0 {
+            return Err(SendError(value));
+        }
+
+        if shared.buffer.len() == shared.capacity {
+            shared.buffer.pop_front();
+            shared.head += 1;
+        }
+        shared.buffer.push_back(value);
+        let waiters = core::mem::take(&mut shared.waiters);
+        let receivers = shared.receivers;
+        drop(shared);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+        Ok(receivers)
+    }
+
+    /// Create a new receiver that will receive all the values sent after this call.
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut shared = lock(&self.shared);
+        let next = shared.tail();
+        let id = shared.subscribe();
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next,
+            id,
+        }
+    }
+
+    /// Get the number of receivers currently subscribed to the channel.
+    #[must_use]
+    pub fn receiver_count(&self) -> usize {
+        lock(&self.shared).receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.senders -= 1;
+        let waiters = if shared.senders == 0 {
+            core::mem::take(&mut shared.waiters)
+        } else {
+            Vec::new()
+        };
+        drop(shared);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Debug for Sender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+/// The receiving half of a [`broadcast`](self) channel.
+///
+/// This is also a stream of the received values, which yields [`Lagged`] errors when the
+/// receiver falls behind and ends once all the senders have been dropped and all the values have
+/// been received.
+///
+/// Cloning a receiver creates a new receiver at the same position.
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    /// The position of the next value to receive.
+    next: u64,
+    id: usize,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Receive the next value, waiting until one is sent.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Attempt to receive the next value without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there are no values to receive, if the channel is closed, or if the receiver has
+    /// fallen behind. In the last case the receiver skips to the oldest value still held.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = lock(&self.shared);
+        Self::try_recv_locked(&mut self.next, &shared)
+    }
+
+    fn try_recv_locked(next: &mut u64, shared: &Shared<T>) -> Result<T, TryRecvError> {
+        if *next < shared.head {
+            let missed = shared.head - *next;
+            *next = shared.head;
+            return Err(TryRecvError::Lagged(missed));
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        match shared.buffer.get((*next - shared.head) as usize) {
+            Some(value) => {
+                *next += 1;
+                Ok(value.clone())
+            }
+            None if shared.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut shared = lock(&self.shared);
+        Poll::Ready(match Self::try_recv_locked(&mut self.next, &shared) {
+            Ok(value) => Ok(value),
+            Err(TryRecvError::Lagged(missed)) => Err(RecvError::Lagged(missed)),
+            Err(TryRecvError::Closed) => Err(RecvError::Closed),
+            Err(TryRecvError::Empty) => {
+                let id = self.id;
+                match shared.waiters.iter_mut().find(|(waiter, _)| *waiter == id) {
+                    Some((_, waker)) => waker.clone_from(cx.waker()),
+                    None => shared.waiters.push((id, cx.waker().clone())),
+                }
+                return Poll::Pending;
+            }
+        })
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let id = lock(&self.shared).subscribe();
+        Self {
+            shared: Arc::clone(&self.shared),
+            next: self.next,
+            id,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.receivers -= 1;
+        let id = self.id;
+        shared.waiters.retain(|&(waiter, _)| waiter != id);
+    }
+}
+
+impl<T> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone> Stream for Receiver<T> {
+    type Item = Result<T, Lagged>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(|res| match res {
+            Ok(value) => Some(Ok(value)),
+            Err(RecvError::Lagged(missed)) => Some(Err(Lagged(missed))),
+            Err(RecvError::Closed) => None,
+        })
+    }
+}
+
+impl<T: Clone> CompletionStream for Receiver<T> {
+    type Item = Result<T, Lagged>;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Stream::poll_next(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// Future for [`Receiver::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<T: Clone> CompletionFuture for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// The error returned when sending to a [`broadcast`](self) channel with no receivers,
+/// containing the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Take the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("SendError(..)")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a channel with no receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// The error yielded by a [`Receiver`] stream when it falls behind, containing the number of
+/// values it missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lagged(pub u64);
+
+impl Display for Lagged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "receiver lagged behind by {} values", self.0)
+    }
+}
+
+impl Error for Lagged {}
+
+/// The error returned by [`Receiver::recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecvError {
+    /// All the senders have been dropped and all the values have been received.
+    Closed,
+    /// The receiver fell behind and missed this many values. The next call will receive the
+    /// oldest value still held by the channel.
+    Lagged(u64),
+}
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => f.write_str("receiving on a closed channel"),
+            Self::Lagged(missed) => write!(f, "receiver lagged behind by {missed} values"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+/// The error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TryRecvError {
+    /// There are no values to receive.
+    Empty,
+    /// All the senders have been dropped and all the values have been received.
+    Closed,
+    /// The receiver fell behind and missed this many values. The next call will receive the
+    /// oldest value still held by the channel.
+    Lagged(u64),
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("receiving on an empty channel"),
+            Self::Closed => f.write_str("receiving on a closed channel"),
+            Self::Lagged(missed) => write!(f, "receiver lagged behind by {missed} values"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::future::block_on;
+    use crate::test_utils::poll_once;
+
+    #[test]
+    fn broadcast() {
+        let (sender, mut first) = channel(4);
+        assert_eq!(poll_once(first.recv()), None);
+
+        assert_eq!(sender.send(1), Ok(1));
+        let mut second = sender.subscribe();
+        assert_eq!(sender.send(2), Ok(2));
+        let mut third = second.clone();
+
+        assert_eq!(block_on(first.recv()), Ok(1));
+        assert_eq!(block_on(first.recv()), Ok(2));
+        assert_eq!(block_on(second.recv()), Ok(2));
+        assert_eq!(third.try_recv(), Ok(2));
+        assert_eq!(third.try_recv(), Err(TryRecvError::Empty));
+
+        drop((first, second, third));
+        assert_eq!(sender.receiver_count(), 0);
+        assert_eq!(sender.send(3), Err(SendError(3)));
+    }
+
+    #[test]
+    fn lagged() {
+        let (sender, mut receiver) = channel(2);
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+        drop(sender);
+
+        assert_eq!(block_on(receiver.recv()), Err(RecvError::Lagged(3)));
+        assert_eq!(block_on(receiver.recv()), Ok(3));
+        assert_eq!(block_on(receiver.recv()), Ok(4));
+        assert_eq!(block_on(receiver.recv()), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn stream() {
+        let (sender, receiver) = channel(2);
+        for i in 0..3 {
+            sender.send(i).unwrap();
+        }
+        drop(sender);
+
+        let items: Vec<_> = block_on(crate::CompletionStreamExt::collect(receiver));
+        assert_eq!(items, [Err(Lagged(1)), Ok(1), Ok(2)]);
+    }
+}
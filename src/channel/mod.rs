@@ -0,0 +1,6 @@
+//! Channels for sending values between tasks.
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+pub mod watch;
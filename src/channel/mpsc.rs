@@ -0,0 +1,696 @@
+//! A bounded multi-producer, single-consumer channel.
+//!
+//! # Examples
+//!
+//! ```
+//! use completion::{channel::mpsc, future, CompletionStreamExt, completion_async};
+//!
+//! let (sender, receiver) = mpsc::channel(2);
+//!
+//! let (received, ()) = future::block_on(future::zip((
+//!     receiver.collect::<Vec<_>>(),
+//!     completion_async! {
+//!         for i in 0..5 {
+//!             sender.send(i).await.unwrap();
+//!         }
+//!         drop(sender);
+//!     },
+//! )));
+//! assert_eq!(received, [0, 1, 2, 3, 4]);
+//! ```
+
+use core::fmt::{self, Debug, Display, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use completion_core::{CompletionFuture, CompletionStream};
+use futures_core::Stream;
+
+use crate::lock;
+
+/// Create a new bounded channel that can hold `capacity` values.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+#[must_use]
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be non-zero");
+
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        closed: false,
+        receiver_waker: None,
+        send_waiters: VecDeque::new(),
+        next_id: 0,
+    }));
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    /// The number of senders alive.
+    senders: usize,
+    /// Whether the receiver has closed the channel.
+    closed: bool,
+    receiver_waker: Option<Waker>,
+    /// The senders waiting for space in the queue, in the order they will send.
+    send_waiters: VecDeque<(usize, Waker)>,
+    next_id: usize,
+}
+
+impl<T> Shared<T> {
+    /// Push a value to the queue, returning the wakers that need to be woken.
+    fn push(&mut self, value: T) -> (Option<Waker>, Option<Waker>) {
+        self.queue.push_back(value);
+        (self.receiver_waker.take(), self.next_sender())
+    }
+
+    /// Get the waker of the next waiting sender, if there is space for it to send.
+    fn next_sender(&self) -> Option<Waker> {
+        if self.queue.len() < self.capacity {
+            self.send_waiters.front().map(|(_, waker)| waker.clone())
+        } else {
+            None
+        }
+    }
+}
+
+fn wake_all(wakers: impl IntoIterator<Item = Option<Waker>>) {
+    for waker in wakers.into_iter().flatten() {
+        waker.wake();
+    }
+}
+
+/// The sending half of an [`mpsc`](self) channel.
+///
+/// Senders can be cloned to send from multiple tasks.
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value, waiting until there is space for it in the channel.
+    ///
+    /// Senders waiting for space send their values in the order they started waiting.
+    ///
+    /// # Cancellation
+    ///
+    /// If the returned future is cancelled before the value is sent, the value stays inside it, and
+    /// can be taken back with [`Send::into_cancelled`]. This is only possible while you still own
+    /// the future: passing it by value to a combinator that can cancel it, such as
+    /// [`timeout`](crate::CompletionFutureExt::timeout), [`race`](crate::future::race) or
+    /// [`with_cancellation`](crate::CompletionFutureExt::with_cancellation), drops the value along
+    /// with the future when it is cancelled. To keep the value, pass `&mut send` to the combinator
+    /// instead.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use completion::{channel::mpsc, CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let (sender, mut receiver) = mpsc::channel(1);
+    /// sender.send(1).await.unwrap();
+    ///
+    /// // The channel is full, so both of these sends time out. The first value is dropped along
+    /// // with its future, but the second can be taken back.
+    /// assert!(sender.send(2).timeout(Duration::from_millis(10)).await.is_err());
+    ///
+    /// let mut send = sender.send(3);
+    /// assert!((&mut send).timeout(Duration::from_millis(10)).await.is_err());
+    /// assert_eq!(send.into_cancelled().unwrap().into_inner(), 3);
+    ///
+    /// assert_eq!(receiver.try_recv(), Ok(1));
+    /// assert!(receiver.try_recv().is_err());
+    /// # });
+    /// ```
+    pub fn send(&self, value: T) -> Send<'_, T> {
+        Send {
+            sender: self,
+            value: Some(value),
+            id: None,
+        }
+    }
+
+    /// Attempt to send a value without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Fails and gives back the value if the channel is full or closed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut shared = lock(&self.shared);
+        if shared.closed {
+            Err(TrySendError::Closed(value))
+        } else if shared.queue.len() < shared.capacity && shared.send_waiters.is_empty() {
+            let wakers = shared.push(value);
+            drop(shared);
+            wake_all([wakers.0, wakers.1]);
+            Ok(())
+        } else {
+            Err(TrySendError::Full(value))
+        }
+    }
+
+    /// Get whether the receiver has closed the channel, so that sending would fail.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared).closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.senders -= 1;
+        let waker = if shared.senders == 0 {
+            shared.receiver_waker.take()
+        } else {
+            None
+        };
+        drop(shared);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Debug for Sender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+/// Future for [`Sender::send`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct Send<'a, T> {
+    sender: &'a Sender<T>,
+    /// The value, until it has been sent.
+    value: Option<T>,
+    /// The ID of this sender, if it is waiting for space in the queue.
+    id: Option<usize>,
+}
+
+impl<T> Unpin for Send<'_, T> {}
+
+impl<T> Send<'_, T> {
+    /// Take back the value if it was not sent, for example because the future was cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use completion::{channel::mpsc, CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let (sender, _receiver) = mpsc::channel(1);
+    /// sender.send(1).await.unwrap();
+    ///
+    /// // The channel is full, so this will time out and be cancelled.
+    /// let mut send = sender.send(2);
+    /// assert!((&mut send).timeout(Duration::from_millis(10)).await.is_err());
+    /// assert_eq!(send.into_cancelled().unwrap().into_inner(), 2);
+    /// # });
+    /// ```
+    pub fn into_cancelled(mut self) -> Option<SendCancelled<T>> {
+        self.cancel();
+        self.value.take().map(SendCancelled)
+    }
+
+    /// Leave the queue of waiting senders.
+    fn cancel(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut shared = lock(&self.sender.shared);
+            let front = shared.send_waiters.front().map(|&(id, _)| id);
+            shared.send_waiters.retain(|&(waiter, _)| waiter != id);
+            let waker = if front == Some(id) {
+                shared.next_sender()
+            } else {
+                None
+            };
+            drop(shared);
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> CompletionFuture for Send<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut shared = lock(&this.sender.shared);
+
+        if shared.closed {
+            drop(shared);
+            this.cancel();
+            let value = this.value.take().expect("`Send` polled after completion");
+            return Poll::Ready(Err(SendError(value)));
+        }
+
+        let has_space = shared.queue.len() < shared.capacity;
+        let can_send = match this.id {
+            Some(id) => has_space && shared.send_waiters.front().map(|&(id, _)| id) == Some(id),
+            None => has_space && shared.send_waiters.is_empty(),
+        };
+
+        if can_send {
+            if this.id.take().is_some() {
+                shared.send_waiters.pop_front();
+            }
+            let value = this.value.take().expect("`Send` polled after completion");
+            let wakers = shared.push(value);
+            drop(shared);
+            wake_all([wakers.0, wakers.1]);
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Some(id) = this.id {
+            let (_, waker) = shared
+                .send_waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+        } else {
+            let id = shared.next_id;
+            shared.next_id += 1;
+            shared.send_waiters.push_back((id, cx.waker().clone()));
+            this.id = Some(id);
+        }
+
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.cancel();
+        Poll::Ready(())
+    }
+}
+
+impl<T> Drop for Send<'_, T> {
+    fn drop(&mut self) {
+        // If this sender was at the front of the queue, wake the next one so that it can take the
+        // free slot instead.
+        self.cancel();
+    }
+}
+
+impl<T> Debug for Send<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Send")
+            .field("sent", &self.value.is_none())
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// The receiving half of an [`mpsc`](self) channel.
+///
+/// This is also a stream of the received values, which ends once all the senders have been
+/// dropped and all the values have been received.
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the next value, waiting until one is sent.
+    ///
+    /// Resolves to [`None`] once all the senders have been dropped and all the values have been
+    /// received.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Attempt to receive a value without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there are no values in the channel.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut shared = lock(&self.shared);
+        match shared.queue.pop_front() {
+            Some(value) => {
+                let waker = shared.next_sender();
+                drop(shared);
+                wake_all([waker]);
+                Ok(value)
+            }
+            None if shared.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Close the channel, so that no more values can be sent.
+    ///
+    /// Values that have already been sent can still be received.
+    pub fn close(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.closed = true;
+        let waiters: Vec<_> = shared.send_waiters.iter().map(|(_, w)| w.clone()).collect();
+        drop(shared);
+
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                let mut shared = lock(&self.shared);
+                // A value may have been sent since `try_recv` released the lock.
+                if !shared.queue.is_empty() || shared.senders == 0 {
+                    drop(shared);
+                    return self.poll_recv(cx);
+                }
+                match &mut shared.receiver_waker {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    None => shared.receiver_waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<T> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> CompletionStream for Receiver<T> {
+    type Item = T;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Stream::poll_next(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// Future for [`Receiver::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<T> CompletionFuture for Recv<'_, T> {
+    type Output = Option<T>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// The error returned when sending to a closed [`mpsc`](self) channel, containing the value that
+/// could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Take the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("SendError(..)")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// A value that was not sent because its [`Send`] future was cancelled, returned by
+/// [`Send::into_cancelled`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendCancelled<T>(pub T);
+
+impl<T> SendCancelled<T> {
+    /// Take the value that was not sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Debug for SendCancelled<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("SendCancelled(..)")
+    }
+}
+
+impl<T> Display for SendCancelled<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("send was cancelled")
+    }
+}
+
+impl<T> Error for SendCancelled<T> {}
+
+/// The error returned by [`Sender::try_send`], containing the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// The channel has been closed.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Take the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full(value) | Self::Closed(value) => value,
+        }
+    }
+}
+
+impl<T> Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Full(_) => "Full(..)",
+            Self::Closed(_) => "Closed(..)",
+        })
+    }
+}
+
+impl<T> Display for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Full(_) => "sending on a full channel",
+            Self::Closed(_) => "sending on a closed channel",
+        })
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+/// The error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TryRecvError {
+    /// There are no values in the channel.
+    Empty,
+    /// There are no values in the channel and all the senders have been dropped.
+    Disconnected,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "receiving on an empty channel",
+            Self::Disconnected => "receiving on an empty and disconnected channel",
+        })
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use std::time::Duration;
+
+    use crate::future::block_on;
+    use crate::test_utils::{poll_cancel_once, poll_once};
+    use crate::time::MockClock;
+
+    #[test]
+    fn cancelled_by_timeout() {
+        let clock = MockClock::new();
+        let (sender, mut receiver) = channel(1);
+        sender.try_send(1).unwrap();
+
+        // An owning combinator drops the value when it cancels the send.
+        let mut timeout = clock.timeout(Duration::from_secs(1), sender.send(2));
+        assert!(poll_once(&mut timeout).is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!(poll_once(&mut timeout).unwrap().is_err());
+        drop(timeout);
+
+        // Lending the send to the combinator keeps the value.
+        let mut send = sender.send(3);
+        let mut timeout = clock.timeout(Duration::from_secs(1), &mut send);
+        assert!(poll_once(&mut timeout).is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!(poll_once(&mut timeout).unwrap().is_err());
+        drop(timeout);
+        assert_eq!(send.into_cancelled().unwrap().into_inner(), 3);
+
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn bounded() {
+        let (sender, mut receiver) = channel(2);
+        assert_eq!(poll_once(receiver.recv()), None);
+
+        sender.try_send(1).unwrap();
+        assert_eq!(poll_once(sender.send(2)), Some(Ok(())));
+        assert!(matches!(sender.try_send(3), Err(TrySendError::Full(3))));
+
+        let mut first = sender.send(3);
+        let mut second = sender.send(4);
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        // Waiting senders send in order, and newcomers wait behind them.
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert!(matches!(sender.try_send(5), Err(TrySendError::Full(5))));
+        assert!(poll_once(&mut second).is_none());
+        assert_eq!(poll_once(&mut first), Some(Ok(())));
+        assert_eq!(receiver.try_recv(), Ok(2));
+        assert_eq!(poll_once(&mut second), Some(Ok(())));
+        drop((first, second));
+
+        drop(sender);
+        assert_eq!(block_on(receiver.recv()), Some(3));
+        assert_eq!(block_on(receiver.recv()), Some(4));
+        assert_eq!(block_on(receiver.recv()), None);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn cancel_send() {
+        let (sender, mut receiver) = channel(1);
+        sender.try_send(Box::new(1)).unwrap();
+
+        let mut first = sender.send(Box::new(2));
+        let mut second = sender.send(Box::new(3));
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        // Cancelling the first sender gives back its value and lets the second one send.
+        assert!(poll_cancel_once(&mut first));
+        assert_eq!(first.into_cancelled().unwrap().into_inner(), Box::new(2));
+        assert_eq!(receiver.try_recv(), Ok(Box::new(1)));
+        assert_eq!(poll_once(&mut second), Some(Ok(())));
+        assert!(second.into_cancelled().is_none());
+        assert_eq!(receiver.try_recv(), Ok(Box::new(3)));
+    }
+
+    #[test]
+    fn closed() {
+        let (sender, mut receiver) = channel(1);
+        sender.try_send(1).unwrap();
+        let mut send = sender.send(2);
+        assert!(poll_once(&mut send).is_none());
+
+        receiver.close();
+        assert!(sender.is_closed());
+        assert!(matches!(poll_once(&mut send), Some(Err(SendError(2)))));
+        assert!(matches!(sender.try_send(3), Err(TrySendError::Closed(3))));
+        assert_eq!(receiver.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn threads() {
+        let (sender, receiver) = channel(4);
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for j in 0..100 {
+                        block_on(sender.send(i * 100 + j)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut values = block_on(crate::CompletionStreamExt::collect::<Vec<_>>(receiver));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        values.sort_unstable();
+        assert_eq!(values, (0..400).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,278 @@
+//! A channel for sending a single value between tasks.
+//!
+//! # Examples
+//!
+//! ```
+//! use completion::{channel::oneshot, future, completion_async};
+//!
+//! let (sender, receiver) = oneshot::channel();
+//!
+//! let (received, ()) = future::block_on(future::zip((
+//!     receiver,
+//!     completion_async! {
+//!         sender.send(5).unwrap();
+//!     },
+//! )));
+//! assert_eq!(received, Ok(5));
+//! ```
+
+use core::fmt::{self, Display, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// Create a new oneshot channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        value: None,
+        sender_dropped: false,
+        receiver_dropped: false,
+        waker: None,
+    }));
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    value: Option<T>,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+    /// The waker of the receiver.
+    waker: Option<Waker>,
+}
+
+/// The sending half of a [`oneshot`](self) channel.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send the value to the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Fails and gives back the value if the receiver has been dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut shared = lock(&self.shared);
+        if shared.receiver_dropped {
+            return Err(value);
+        }
+        shared.value = Some(value);
+        // The receiver is woken when `self` is dropped.
+        Ok(())
+    }
+
+    /// Get whether the receiver has been dropped, so that sending would fail.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared).receiver_dropped
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.sender_dropped = true;
+        let waker = shared.waker.take();
+        drop(shared);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The receiving half of a [`oneshot`](self) channel.
+///
+/// This is a future that resolves to the sent value, or an error if the sender was dropped
+/// without sending a value. Use [`recv`](Self::recv) to wait for the value without giving up the
+/// receiver.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the value, waiting until it is sent.
+    ///
+    /// Resolves to an error if the sender was dropped without sending a value.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Attempt to receive the value without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value has not been sent yet, or if the sender was dropped without sending a
+    /// value.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut shared = lock(&self.shared);
+        match shared.value.take() {
+            Some(value) => Ok(value),
+            None if shared.sender_dropped => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let mut shared = lock(&self.shared);
+        if let Some(value) = shared.value.take() {
+            Poll::Ready(Ok(value))
+        } else if shared.sender_dropped {
+            Poll::Ready(Err(RecvError))
+        } else {
+            match &mut shared.waker {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => shared.waker = Some(cx.waker().clone()),
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> CompletionFuture for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        lock(&self.shared).receiver_dropped = true;
+    }
+}
+
+/// Future for [`Receiver::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<T> CompletionFuture for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// The error returned when the sender of a [`oneshot`](self) channel was dropped without
+/// sending a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RecvError;
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("sender dropped without sending a value")
+    }
+}
+
+impl Error for RecvError {}
+
+/// The error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TryRecvError {
+    /// The value has not been sent yet.
+    Empty,
+    /// The sender was dropped without sending a value.
+    Closed,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "value has not been sent yet",
+            Self::Closed => "sender dropped without sending a value",
+        })
+    }
+}
+
+impl Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::future::block_on;
+    use crate::test_utils::poll_once;
+
+    #[test]
+    fn send() {
+        let (sender, mut receiver) = channel();
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(poll_once(&mut receiver), None);
+
+        let handle = thread::spawn(move || sender.send(Box::new(5)).unwrap());
+        assert_eq!(block_on(receiver), Ok(Box::new(5)));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dropped() {
+        let (sender, mut receiver) = channel::<()>();
+        assert_eq!(poll_once(&mut receiver), None);
+        drop(sender);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(block_on(receiver), Err(RecvError));
+
+        let (sender, receiver) = channel();
+        assert!(!sender.is_closed());
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(5), Err(5));
+    }
+
+    #[test]
+    fn recv() {
+        let (sender, mut receiver) = channel();
+        assert_eq!(poll_once(receiver.recv()), None);
+        sender.send(5).unwrap();
+        assert_eq!(block_on(receiver.recv()), Ok(5));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+
+        let (sender, mut receiver) = channel::<()>();
+        drop(sender);
+        assert_eq!(block_on(receiver.recv()), Err(RecvError));
+    }
+}
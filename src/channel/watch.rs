@@ -0,0 +1,462 @@
+//! A channel that holds a single value, which receivers can watch for changes.
+//!
+//! Only the latest value is kept, so receivers that fall behind skip straight to it. This is
+//! useful for sharing state such as configuration that may be updated while the program runs.
+//!
+//! # Examples
+//!
+//! ```
+//! use completion::{channel::watch, future, CompletionStreamExt, completion_async};
+//!
+//! let (sender, mut receiver) = watch::channel("initial");
+//! assert_eq!(*receiver.borrow(), "initial");
+//!
+//! let changes = sender.subscribe();
+//!
+//! future::block_on(completion_async! {
+//!     sender.send("updated").unwrap();
+//!     receiver.changed().await.unwrap();
+//!     assert_eq!(*receiver.borrow_and_update(), "updated");
+//!
+//!     drop(sender);
+//!     assert!(receiver.changed().await.is_err());
+//! });
+//!
+//! // Receivers are streams of the latest value each time it changes.
+//! let values: Vec<_> = future::block_on(changes.collect());
+//! assert_eq!(values, ["updated"]);
+//! ```
+
+use core::fmt::{self, Debug, Display, Formatter};
+use core::future::Future;
+use core::mem;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::error::Error;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard};
+
+use completion_core::{CompletionFuture, CompletionStream};
+use futures_core::Stream;
+
+use crate::lock;
+
+/// Create a new watch channel holding an initial value.
+///
+/// The returned receiver considers the initial value already seen.
+#[must_use]
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(initial),
+        state: Mutex::new(State {
+            version: 0,
+            closed: false,
+            receivers: 1,
+            waiters: Vec::new(),
+            next_id: 1,
+        }),
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver {
+            shared,
+            seen: 0,
+            id: 0,
+        },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    value: RwLock<T>,
+    state: Mutex<State>,
+}
+
+impl<T> Shared<T> {
+    fn read(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.value.read().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    /// Register a new receiver, returning its ID.
+    fn subscribe(&self) -> usize {
+        let mut state = lock(&self.state);
+        state.receivers += 1;
+        let id = state.next_id;
+        state.next_id += 1;
+        id
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// Incremented every time a value is sent.
+    version: u64,
+    /// Whether the sender has been dropped.
+    closed: bool,
+    receivers: usize,
+    /// The IDs and wakers of the receivers waiting for a change.
+    waiters: Vec<(usize, Waker)>,
+    next_id: usize,
+}
+
+/// A reference to the value held by a [`watch`](self) channel.
+///
+/// This holds a read lock on the value, so sending will wait until it is dropped. It should not be
+/// held for long.
+#[derive(Debug)]
+pub struct Ref<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+/// The sending half of a [`watch`](self) channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Replace the value and notify the receivers.
+    ///
+    /// # Errors
+    ///
+    /// Fails and gives back the value if there are no receivers.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if lock(&self.shared.state).receivers == 0 {
+            return Err(SendError(value));
+        }
+        self.send_replace(value);
+        Ok(())
+    }
+
+    /// Replace the value and notify the receivers, even if there are none, returning the old
+    /// value.
+    pub fn send_replace(&self, value: T) -> T {
+        let mut guard = self
+            .shared
+            .value
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let old = mem::replace(&mut *guard, value);
+
+        // Keep the write lock until the version has changed too, so that receivers never see the
+        // new value with the old version.
+        let mut state = lock(&self.shared.state);
+        state.version += 1;
+        let waiters = mem::take(&mut state.waiters);
+        drop(state);
+        drop(guard);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+        old
+    }
+
+    /// Get a reference to the current value.
+    #[must_use]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.shared.read()
+    }
+
+    /// Create a new receiver that considers the current value already seen.
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<T> {
+        let id = self.shared.subscribe();
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            seen: lock(&self.shared.state).version,
+            id,
+        }
+    }
+
+    /// Get the number of receivers watching the channel.
+    #[must_use]
+    pub fn receiver_count(&self) -> usize {
+        lock(&self.shared.state).receivers
+    }
+
+    /// Get whether all the receivers have been dropped, so that sending would fail.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared.state);
+        state.closed = true;
+        let waiters = mem::take(&mut state.waiters);
+        drop(state);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Debug for Sender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+/// The receiving half of a [`watch`](self) channel.
+///
+/// This is also a stream that yields the latest value each time it changes, ending once the
+/// sender has been dropped.
+///
+/// Cloning a receiver creates a new receiver that has seen the same values.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// The version of the value last seen.
+    seen: u64,
+    id: usize,
+}
+
+impl<T> Receiver<T> {
+    /// Get a reference to the current value, without marking it as seen.
+    #[must_use]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.shared.read()
+    }
+
+    /// Get a reference to the current value and mark it as seen.
+    #[must_use]
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        // Take the read lock first; the version only changes while the write lock is held, so it
+        // can't change before we read it.
+        let value = self.shared.read();
+        self.seen = lock(&self.shared.state).version;
+        value
+    }
+
+    /// Get whether the value has changed since it was last seen.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the sender has been dropped.
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = lock(&self.shared.state);
+        if state.closed {
+            return Err(RecvError);
+        }
+        Ok(state.version != self.seen)
+    }
+
+    /// Wait for the value to change from the one last seen, and mark the new value as seen.
+    ///
+    /// This resolves immediately if the value has already changed.
+    pub fn changed(&mut self) -> Changed<'_, T> {
+        Changed { receiver: self }
+    }
+
+    fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), RecvError>> {
+        let mut state = lock(&self.shared.state);
+        if state.version != self.seen {
+            self.seen = state.version;
+            Poll::Ready(Ok(()))
+        } else if state.closed {
+            Poll::Ready(Err(RecvError))
+        } else {
+            let id = self.id;
+            match state.waiters.iter_mut().find(|(waiter, _)| *waiter == id) {
+                Some((_, waker)) => waker.clone_from(cx.waker()),
+                None => state.waiters.push((id, cx.waker().clone())),
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let id = self.shared.subscribe();
+        Self {
+            shared: Arc::clone(&self.shared),
+            seen: self.seen,
+            id,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared.state);
+        state.receivers -= 1;
+        let id = self.id;
+        state.waiters.retain(|&(waiter, _)| waiter != id);
+    }
+}
+
+impl<T> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("seen", &self.seen)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.poll_changed(cx)
+            .map(|res| res.ok().map(|()| this.borrow_and_update().clone()))
+    }
+}
+
+impl<T: Clone> CompletionStream for Receiver<T> {
+    type Item = T;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Stream::poll_next(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// Future for [`Receiver::changed`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Changed<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_changed(cx)
+    }
+}
+
+impl<T> CompletionFuture for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// The error returned when sending to a [`watch`](self) channel with no receivers, containing
+/// the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Take the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad("SendError(..)")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a channel with no receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// The error returned when waiting for a change on a [`watch`](self) channel whose sender has
+/// been dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RecvError;
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("the sender was dropped")
+    }
+}
+
+impl Error for RecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::future::block_on;
+    use crate::test_utils::poll_once;
+
+    #[test]
+    fn version_matches_value() {
+        let (sender, mut receiver) = channel(0_u64);
+
+        // Every send increments the version by one, so it is always equal to the value.
+        let sending = thread::spawn(move || {
+            for i in 1..=1000 {
+                sender.send(i).unwrap();
+            }
+        });
+        loop {
+            let value = *receiver.borrow_and_update();
+            assert_eq!(receiver.seen, value);
+            if value == 1000 {
+                break;
+            }
+        }
+        sending.join().unwrap();
+    }
+
+    #[test]
+    fn changed() {
+        let (sender, mut receiver) = channel(0);
+        assert_eq!(receiver.has_changed(), Ok(false));
+        assert_eq!(poll_once(receiver.changed()), None);
+
+        // Only the latest value is seen.
+        sender.send(1).unwrap();
+        assert_eq!(sender.send_replace(2), 1);
+        let mut other = receiver.clone();
+        assert_eq!(receiver.has_changed(), Ok(true));
+        assert_eq!(block_on(receiver.changed()), Ok(()));
+        assert_eq!(*receiver.borrow(), 2);
+        assert_eq!(poll_once(receiver.changed()), None);
+        assert_eq!(*other.borrow_and_update(), 2);
+        assert_eq!(other.has_changed(), Ok(false));
+
+        drop(sender);
+        assert_eq!(block_on(receiver.changed()), Err(RecvError));
+        assert_eq!(receiver.has_changed(), Err(RecvError));
+    }
+
+    #[test]
+    fn no_receivers() {
+        let (sender, receiver) = channel(0);
+        assert!(!sender.is_closed());
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(1), Err(SendError(1)));
+        assert_eq!(*sender.borrow(), 0);
+
+        let mut receiver = sender.subscribe();
+        assert_eq!(sender.receiver_count(), 1);
+        sender.send(2).unwrap();
+        assert_eq!(block_on(receiver.changed()), Ok(()));
+    }
+}
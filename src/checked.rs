@@ -0,0 +1,312 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::thread;
+
+use completion_core::{CompletionFuture, CompletionStream};
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A wrapper around a [`CompletionFuture`] or [`CompletionStream`] that checks it is used
+    /// correctly, for debugging and testing.
+    ///
+    /// This tracks the state of the future or stream, and panics with a descriptive message when
+    /// the rules documented on [`CompletionFuture`] and [`CompletionStream`] are broken: when it is
+    /// polled after completing, when [`poll`](CompletionFuture::poll) is called after
+    /// [`poll_cancel`](CompletionFuture::poll_cancel), or when it is dropped while it is still
+    /// running or cancelling.
+    ///
+    /// Wrapping the futures passed to an adapter in this type can be used to check that the
+    /// adapter drives them correctly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{future, CheckedCompletion, CompletionFutureExt, completion_async};
+    ///
+    /// let future = CheckedCompletion::new(completion_async!(5)).map(|x| x + 1);
+    /// assert_eq!(future::block_on(future), 6);
+    /// ```
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[derive(Debug)]
+    #[must_use = "futures and streams do nothing unless you use them"]
+    pub struct CheckedCompletion<T> {
+        #[pin]
+        inner: T,
+        state: State,
+    }
+
+    impl<T> PinnedDrop for CheckedCompletion<T> {
+        fn drop(this: Pin<&mut Self>) {
+            if thread::panicking() {
+                return;
+            }
+            match this.state {
+                State::Running => panic!("`CheckedCompletion` dropped while running"),
+                State::Cancelling => panic!("`CheckedCompletion` dropped while cancelling"),
+                State::Idle | State::Polling | State::Complete => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The future has not been polled, or the stream is in between items.
+    Idle,
+    /// The inner value is being polled, or panicked while it was being polled.
+    Polling,
+    /// The inner value has been polled and has not completed.
+    Running,
+    /// The inner value has been polled with `poll_cancel` and has not finished cancelling.
+    Cancelling,
+    /// The future has completed or the stream has been exhausted.
+    Complete,
+}
+
+impl<T> CheckedCompletion<T> {
+    /// Wrap a future or stream to check its usage.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            state: State::Idle,
+        }
+    }
+
+    /// Get whether the future has completed or the stream has been exhausted, either normally or
+    /// by being cancelled.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    /// Check that it is valid to call `poll` or `poll_next`, and start polling.
+    fn start_poll(state: &mut State, method: &str) {
+        match *state {
+            State::Idle | State::Running => *state = State::Polling,
+            State::Polling => {
+                panic!("`CheckedCompletion`: `{}` called after panicking", method)
+            }
+            State::Cancelling => {
+                panic!(
+                    "`CheckedCompletion`: `{}` called after `poll_cancel`",
+                    method
+                )
+            }
+            State::Complete => panic!("`CheckedCompletion`: `{}` called after completion", method),
+        }
+    }
+
+    /// Check that it is valid to call `poll_cancel`, and start polling.
+    fn start_poll_cancel(state: &mut State) {
+        match *state {
+            State::Idle | State::Running | State::Cancelling => *state = State::Polling,
+            State::Polling => panic!("`CheckedCompletion`: `poll_cancel` called after panicking"),
+            State::Complete => panic!("`CheckedCompletion`: `poll_cancel` called after completion"),
+        }
+    }
+}
+
+impl<T: CompletionFuture> CompletionFuture for CheckedCompletion<T> {
+    type Output = T::Output;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        Self::start_poll(this.state, "poll");
+        let poll = this.inner.poll(cx);
+        *this.state = if poll.is_ready() {
+            State::Complete
+        } else {
+            State::Running
+        };
+        poll
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        Self::start_poll_cancel(this.state);
+        let poll = this.inner.poll_cancel(cx);
+        *this.state = if poll.is_ready() {
+            State::Complete
+        } else {
+            State::Cancelling
+        };
+        poll
+    }
+}
+
+impl<T: CompletionStream> CompletionStream for CheckedCompletion<T> {
+    type Item = T::Item;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        Self::start_poll(this.state, "poll_next");
+        let poll = this.inner.poll_next(cx);
+        *this.state = match &poll {
+            Poll::Ready(Some(_)) => State::Idle,
+            Poll::Ready(None) => State::Complete,
+            Poll::Pending => State::Running,
+        };
+        poll
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        Self::start_poll_cancel(this.state);
+        let poll = this.inner.poll_cancel(cx);
+        *this.state = if poll.is_ready() {
+            State::Complete
+        } else {
+            State::Cancelling
+        };
+        poll
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::{pending, ready};
+
+    use crate::future::{self, block_on, CompletionFutureExt};
+    use crate::stream::CompletionStreamExt;
+    use crate::test_utils::{poll_cancel_once, poll_once, Yield};
+
+    /// A stream that yields before each item and before finishing cancelling.
+    struct Counter {
+        next: u32,
+        end: u32,
+        yielded: bool,
+    }
+    impl Counter {
+        fn checked(end: u32) -> CheckedCompletion<Self> {
+            CheckedCompletion::new(Self {
+                next: 0,
+                end,
+                yielded: false,
+            })
+        }
+        fn step(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+            self.yielded = !self.yielded;
+            if self.yielded {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        }
+    }
+    impl CompletionStream for Counter {
+        type Item = u32;
+        unsafe fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Self::Item>> {
+            futures_core::ready!(self.step(cx));
+            if self.next == self.end {
+                return Poll::Ready(None);
+            }
+            self.next += 1;
+            Poll::Ready(Some(self.next - 1))
+        }
+        unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.step(cx)
+        }
+    }
+
+    /// Poll the future a number of times, then cancel it.
+    fn poll_then_cancel<F: CompletionFuture>(polls: usize, future: F) -> Option<F::Output> {
+        futures_lite::pin!(future);
+        for _ in 0..polls {
+            if let Some(output) = poll_once(future.as_mut()) {
+                return Some(output);
+            }
+        }
+        while !poll_cancel_once(future.as_mut()) {}
+        None
+    }
+
+    #[test]
+    fn future_adapters() {
+        let checked = |x| CheckedCompletion::new(Yield::new(2, ready(x)));
+
+        assert_eq!(block_on(checked(1).then(checked)), 1);
+        assert_eq!(block_on(future::zip((checked(1), checked(2)))), (1, 2));
+        assert_eq!(block_on(future::race((checked(1), checked(2)))), 1);
+
+        for polls in 0..4 {
+            poll_then_cancel(polls, checked(1).then(checked));
+            poll_then_cancel(polls, future::zip((checked(1), checked(2))));
+        }
+    }
+
+    #[test]
+    fn stream_adapters() {
+        let collect = |stream| block_on(CompletionStreamExt::collect::<Vec<_>>(stream));
+
+        assert_eq!(
+            collect(Counter::checked(3).map(|x| x * 2).boxed()),
+            [0, 2, 4]
+        );
+        assert_eq!(
+            collect(Counter::checked(4).skip(1).step_by(2).boxed()),
+            [1, 3]
+        );
+        assert_eq!(collect(Counter::checked(9).take(2).boxed()), [0, 1]);
+        assert_eq!(
+            collect(Counter::checked(2).chain(Counter::checked(1)).boxed()),
+            [0, 1, 0]
+        );
+        assert_eq!(
+            collect(Counter::checked(3).flat_map(Counter::checked).boxed()),
+            [0, 0, 1]
+        );
+        assert_eq!(
+            collect(
+                Counter::checked(3)
+                    .then(|x| CheckedCompletion::new(Yield::once(ready(x))))
+                    .boxed()
+            ),
+            [0, 1, 2]
+        );
+
+        for polls in 0..8 {
+            poll_then_cancel(polls, Counter::checked(3).fuse().count());
+            poll_then_cancel(
+                polls,
+                Counter::checked(3).flat_map(Counter::checked).count(),
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`CheckedCompletion`: `poll` called after completion")]
+    fn poll_after_completion() {
+        let mut future = CheckedCompletion::new(ready(()));
+        poll_once(&mut future);
+        poll_once(&mut future);
+    }
+
+    #[test]
+    #[should_panic(expected = "`CheckedCompletion`: `poll` called after `poll_cancel`")]
+    fn poll_after_cancel() {
+        let mut future = Box::pin(CheckedCompletion::new(Counter::checked(1).count()));
+        poll_cancel_once(future.as_mut());
+        poll_once(future.as_mut());
+    }
+
+    #[test]
+    #[should_panic(expected = "`CheckedCompletion` dropped while running")]
+    fn drop_while_running() {
+        let mut future = Box::pin(CheckedCompletion::new(pending::<()>()));
+        poll_once(future.as_mut());
+    }
+
+    #[test]
+    fn drop_between_items() {
+        let mut stream = Counter::checked(2);
+        assert_eq!(poll_once(stream.next()), None);
+        assert_eq!(poll_once(stream.next()), Some(Some(0)));
+    }
+}
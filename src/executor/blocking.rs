@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use completion_core::CompletionFuture;
+
+use crate::future::wake_pair;
+use crate::lock;
+
+/// The maximum number of threads in the blocking pool.
+const MAX_THREADS: usize = 512;
+
+/// How long an idle thread in the blocking pool waits for a job before exiting.
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+/// Run a blocking function on a shared pool of threads, returning a future that resolves to its
+/// output.
+///
+/// Threads are spawned as needed and exit after being idle for a while. If the function panics,
+/// the panic is resumed when the future is polled.
+///
+/// Dropping or cancelling the future before the function has started stops it from running;
+/// once it has started, the function runs to completion in the background and its output is
+/// dropped.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{executor::spawn_blocking, future};
+///
+/// let sum = future::block_on(spawn_blocking(|| (1..=100).sum::<u32>()));
+/// assert_eq!(sum, 5050);
+/// ```
+pub fn spawn_blocking<F, T>(f: F) -> SpawnBlocking<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    // Safety: the function borrows nothing.
+    let mut task = unsafe { spawn_blocking_unchecked(f) };
+    task.borrowed = false;
+    task
+}
+
+/// Run a blocking function that may borrow data on the blocking pool.
+///
+/// Dropping or cancelling the returned future while the function is running blocks until it has
+/// finished, so that the data it borrows is never used after it is freed.
+///
+/// # Safety
+///
+/// The data borrowed by the function must remain valid until the returned future completes, is
+/// cancelled or is dropped. In particular, the future must not be leaked.
+pub(crate) unsafe fn spawn_blocking_unchecked<'a, F, T>(f: F) -> SpawnBlocking<T>
+where
+    F: FnOnce() -> T + Send + 'a,
+    T: Send + 'static,
+{
+    let f: Box<dyn FnOnce() -> T + Send + 'a> = Box::new(f);
+    // Safety: the caller guarantees that the borrowed data outlives the future, and the function
+    // is either run or dropped before the future completes, is cancelled or is dropped.
+    let f: Func<T> = mem::transmute(f);
+    let shared = Arc::new(Mutex::new(State::Queued(f, None)));
+
+    let job_shared = Arc::clone(&shared);
+    Pool::get().push(Box::new(move || {
+        let mut state = lock(&job_shared);
+        let f = match mem::replace(&mut *state, State::Done) {
+            State::Queued(f, waker) => {
+                *state = State::Running(waker);
+                f
+            }
+            // The task was cancelled before it could start, and the function was dropped then.
+            _ => return,
+        };
+        drop(state);
+
+        let output = panic::catch_unwind(AssertUnwindSafe(f));
+
+        let mut state = lock(&job_shared);
+        if let State::Running(Some(waker)) = mem::replace(&mut *state, State::Complete(output)) {
+            drop(state);
+            waker.wake();
+        }
+    }));
+
+    SpawnBlocking {
+        shared,
+        borrowed: true,
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A blocking function, with the lifetime of the data it borrows erased.
+type Func<T> = Box<dyn FnOnce() -> T + Send + 'static>;
+
+/// The pool of threads that blocking functions run on.
+#[derive(Debug)]
+struct Pool {
+    state: Mutex<PoolState>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+impl Debug for PoolState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolState")
+            .field("queued", &self.queue.len())
+            .field("threads", &self.threads)
+            .field("idle", &self.idle)
+            .finish()
+    }
+}
+
+impl Pool {
+    fn get() -> &'static Self {
+        static POOL: OnceLock<Pool> = OnceLock::new();
+
+        POOL.get_or_init(|| Self {
+            state: Mutex::new(PoolState::default()),
+            condvar: Condvar::new(),
+        })
+    }
+
+    fn push(&'static self, job: Job) {
+        let mut state = lock(&self.state);
+        state.queue.push_back(job);
+
+        if state.queue.len() <= state.idle {
+            drop(state);
+            self.condvar.notify_one();
+        } else if state.threads < MAX_THREADS {
+            state.threads += 1;
+            drop(state);
+            thread::Builder::new()
+                .name("completion-blocking".to_owned())
+                .spawn(move || self.run())
+                .expect("failed to spawn blocking thread");
+        }
+    }
+
+    fn run(&self) {
+        let mut state = lock(&self.state);
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = lock(&self.state);
+                continue;
+            }
+
+            state.idle += 1;
+            let (new_state, timeout) = self
+                .condvar
+                .wait_timeout(state, KEEP_ALIVE)
+                .unwrap_or_else(PoisonError::into_inner);
+            state = new_state;
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                break;
+            }
+        }
+    }
+}
+
+enum State<T> {
+    /// The function waiting for a thread, and the waker of the task waiting for it.
+    Queued(Func<T>, Option<Waker>),
+    /// The function is running, and the waker of the task waiting for it.
+    Running(Option<Waker>),
+    /// The function has finished with this output.
+    Complete(thread::Result<T>),
+    /// The output has been taken, or the function was cancelled before it started.
+    Done,
+}
+
+/// Future for [`spawn_blocking`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct SpawnBlocking<T> {
+    shared: Arc<Mutex<State<T>>>,
+    /// Whether the function borrows data, so dropping must wait for it to finish.
+    borrowed: bool,
+}
+
+impl<T> SpawnBlocking<T> {
+    /// Stop the function from starting if it hasn't yet, and poll for it to finish otherwise.
+    ///
+    /// Resolves to the output of the function if it ran to completion without panicking.
+    pub(crate) fn poll_stop(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = lock(&self.shared);
+        match &mut *state {
+            State::Running(waker) if self.borrowed => {
+                match waker {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    None => *waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+            State::Complete(_) => match mem::replace(&mut *state, State::Done) {
+                State::Complete(output) => Poll::Ready(output.ok()),
+                _ => unreachable!(),
+            },
+            _ => {
+                if let State::Queued(..) = *state {
+                    let queued = mem::replace(&mut *state, State::Done);
+                    drop(state);
+                    // The data borrowed by the function may be freed as soon as this returns, so
+                    // drop the function now instead of leaving it to the thread that dequeues it.
+                    drop(queued);
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl<T> Future for SpawnBlocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.shared);
+        match &mut *state {
+            State::Queued(_, Some(waker)) | State::Running(Some(waker)) => {
+                waker.clone_from(cx.waker());
+            }
+            State::Queued(_, waker) | State::Running(waker) => *waker = Some(cx.waker().clone()),
+            State::Complete(_) => {
+                let State::Complete(output) = mem::replace(&mut *state, State::Done) else {
+                    unreachable!()
+                };
+                drop(state);
+                return Poll::Ready(output.unwrap_or_else(|payload| panic::resume_unwind(payload)));
+            }
+            State::Done => panic!("`SpawnBlocking` polled after completion"),
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> CompletionFuture for SpawnBlocking<T> {
+    type Output = T;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.get_mut().poll_stop(cx).map(drop)
+    }
+}
+
+impl<T> Drop for SpawnBlocking<T> {
+    fn drop(&mut self) {
+        let (parker, waker) = wake_pair();
+        let mut cx = Context::from_waker(&waker);
+        while self.poll_stop(&mut cx).is_pending() {
+            parker.park();
+        }
+    }
+}
+
+impl<T> Debug for SpawnBlocking<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = match &*lock(&self.shared) {
+            State::Queued(..) => "queued",
+            State::Running(_) => "running",
+            State::Complete(_) => "complete",
+            State::Done => "done",
+        };
+        f.debug_struct("SpawnBlocking")
+            .field("state", &state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+
+    use crate::future::block_on;
+    use crate::test_utils::poll_cancel_once;
+
+    #[test]
+    fn borrowed() {
+        let mut data = vec![1, 2, 3];
+        let task = unsafe { spawn_blocking_unchecked(|| data.push(4)) };
+        block_on(task);
+        assert_eq!(data, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn cancel_running() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let finish = AtomicBool::new(false);
+        let finished = AtomicBool::new(false);
+
+        // Cancelling a running function that borrows data waits for it to finish.
+        let started = started_tx.clone();
+        let (finish_ref, finished_ref) = (&finish, &finished);
+        let mut task = unsafe {
+            spawn_blocking_unchecked(move || {
+                started.send(()).unwrap();
+                while !finish_ref.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+                finished_ref.store(true, Ordering::SeqCst);
+            })
+        };
+        started_rx.recv().unwrap();
+        assert!(!poll_cancel_once(&mut task));
+        finish.store(true, Ordering::SeqCst);
+        while !poll_cancel_once(&mut task) {
+            thread::yield_now();
+        }
+        assert!(finished.load(Ordering::SeqCst));
+
+        // Cancelling a running function that borrows nothing detaches it.
+        let (finish_tx, finish_rx) = mpsc::channel();
+        let mut task = spawn_blocking(move || {
+            started_tx.send(()).unwrap();
+            finish_rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+        assert!(poll_cancel_once(&mut task));
+        drop(task);
+        finish_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn cancel_drops_function() {
+        struct SetOnDrop<'a>(&'a AtomicBool);
+        impl Drop for SetOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        // Whether or not the function has started, it must be gone once cancelling finishes.
+        for _ in 0..100 {
+            let dropped = AtomicBool::new(false);
+            let guard = SetOnDrop(&dropped);
+            let mut task = unsafe { spawn_blocking_unchecked(move || drop(guard)) };
+            while !poll_cancel_once(&mut task) {
+                thread::yield_now();
+            }
+            assert!(dropped.load(Ordering::SeqCst));
+        }
+    }
+
+    #[test]
+    #[should_panic = "oh no"]
+    fn panics() {
+        block_on(spawn_blocking(|| panic!("oh no")));
+    }
+}
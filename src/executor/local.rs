@@ -0,0 +1,346 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+
+use completion_core::CompletionFuture;
+
+use super::task::{self, JoinHandle, Runnable};
+use crate::future::{wake_pair, Parker};
+use crate::lock;
+
+/// A single-threaded executor.
+///
+/// All tasks spawned on the executor are run on the thread that created it, so they don't have to
+/// be `Send`. Tasks only make progress while [`run`](Self::run) or [`run_until`](Self::run_until)
+/// is being called.
+///
+/// Every spawned task is driven to completion: when the executor is dropped, all the tasks that
+/// are still running are cancelled through [`poll_cancel`](CompletionFuture::poll_cancel), and the
+/// drop blocks until they have all finished cancelling.
+///
+/// # Examples
+///
+/// ```
+/// use completion::completion_async;
+/// use completion::executor::LocalExecutor;
+///
+/// let executor = LocalExecutor::new();
+///
+/// let task = executor.spawn(completion_async!(1 + 2));
+/// let output = executor.run_until(completion_async! {
+///     task.await.unwrap() * 2
+/// });
+/// assert_eq!(output, 6);
+/// ```
+pub struct LocalExecutor {
+    shared: Arc<Shared>,
+    /// All the tasks that have not yet completed, by their key.
+    tasks: RefCell<HashMap<usize, Arc<dyn Runnable>>>,
+    parker: Parker,
+}
+
+/// The part of the executor that wakers can access from other threads.
+struct Shared {
+    queue: Mutex<VecDeque<Arc<dyn Runnable>>>,
+    /// Unparks the executor's thread.
+    unparker: Waker,
+}
+
+impl Shared {
+    fn push(&self, task: Arc<dyn Runnable>) {
+        lock(&self.queue).push_back(task);
+        self.unparker.wake_by_ref();
+    }
+}
+
+impl LocalExecutor {
+    /// Create a new executor that runs on the current thread.
+    #[must_use]
+    pub fn new() -> Self {
+        let (parker, unparker) = wake_pair();
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(VecDeque::new()),
+                unparker,
+            }),
+            tasks: RefCell::new(HashMap::new()),
+            parker,
+        }
+    }
+
+    /// Spawn a future onto the executor.
+    ///
+    /// The returned [`JoinHandle`] can be used to await the future's output, and cancels the
+    /// future when dropped. The future will not be polled until the executor is run.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: CompletionFuture + 'static,
+        F::Output: 'static,
+    {
+        let shared = Arc::downgrade(&self.shared);
+        let schedule = move |task| {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                shared.push(task);
+            }
+        };
+
+        // Safety: The executor is not `Send`, so the task will be run on this thread. Dropping
+        // the executor completes all the tasks.
+        let (task, handle) = unsafe { task::spawn_unchecked(future, schedule) };
+
+        self.tasks
+            .borrow_mut()
+            .insert(task::key(&task), Arc::clone(&task));
+        task.schedule();
+
+        handle
+    }
+
+    /// Run the executor until all the spawned tasks have completed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::completion_async_move;
+    /// use completion::executor::LocalExecutor;
+    ///
+    /// let executor = LocalExecutor::new();
+    /// for i in 0..5 {
+    ///     executor
+    ///         .spawn(completion_async_move!(println!("Task {}", i)))
+    ///         .detach();
+    /// }
+    /// executor.run();
+    /// ```
+    pub fn run(&self) {
+        loop {
+            self.run_queued();
+            if self.tasks.borrow().is_empty() {
+                return;
+            }
+            if lock(&self.shared.queue).is_empty() {
+                self.parker.park();
+            }
+        }
+    }
+
+    /// Run the executor until the given future completes, returning its output.
+    ///
+    /// The given future does not need to be `'static`. Spawned tasks that have not completed by
+    /// the time it does will continue to run next time the executor is run.
+    pub fn run_until<F: CompletionFuture>(&self, mut future: F) -> F::Output {
+        let mut future = unsafe { Pin::new_unchecked(&mut future) };
+
+        let main = Arc::new(MainWaker {
+            woken: AtomicBool::new(true),
+            unparker: self.shared.unparker.clone(),
+        });
+        let waker = Waker::from(Arc::clone(&main));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if main.woken.swap(false, Ordering::SeqCst) {
+                if let Poll::Ready(output) = unsafe { future.as_mut().poll(&mut cx) } {
+                    return output;
+                }
+            }
+            self.run_queued();
+            if !main.woken.load(Ordering::SeqCst) && lock(&self.shared.queue).is_empty() {
+                self.parker.park();
+            }
+        }
+    }
+
+    /// Run every task that is currently queued once.
+    fn run_queued(&self) {
+        let queued: Vec<_> = lock(&self.shared.queue).drain(..).collect();
+        for task in queued {
+            let key = task::key(&task);
+            if task.run() {
+                self.tasks.borrow_mut().remove(&key);
+            }
+        }
+    }
+}
+
+impl Default for LocalExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        let tasks: Vec<_> = self.tasks.borrow().values().cloned().collect();
+        for task in tasks {
+            task.cancel();
+        }
+        self.run();
+    }
+}
+
+impl Debug for LocalExecutor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalExecutor")
+            .field("tasks", &self.tasks.borrow().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The waker of the future passed to [`LocalExecutor::run_until`].
+struct MainWaker {
+    woken: AtomicBool,
+    unparker: Waker,
+}
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.unparker.wake_by_ref();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::test_utils::{Forever, PollThenCancel, Polls, Yield};
+    use crate::FutureExt;
+
+    #[test]
+    fn run_all() {
+        let executor = LocalExecutor::new();
+        let counter = Rc::new(Cell::new(0));
+        for i in 0..10 {
+            let counter = Rc::clone(&counter);
+            executor
+                .spawn(Yield::new(
+                    i,
+                    async move { counter.set(counter.get() + 1) }.into_completion(),
+                ))
+                .detach();
+        }
+        executor.run();
+        assert_eq!(counter.get(), 10);
+    }
+
+    #[test]
+    fn run_until() {
+        let executor = LocalExecutor::new();
+        let task = executor.spawn(Yield::new(5, std::future::ready(Box::new(3))));
+        let unfinished = executor.spawn(Yield::new(1000, std::future::ready(())));
+
+        let value = 4;
+        let output = executor.run_until(Yield::new(
+            2,
+            async { *task.await.unwrap() + value }.into_completion(),
+        ));
+        assert_eq!(output, 7);
+        assert!(!unfinished.is_finished());
+    }
+
+    #[test]
+    fn spawn_from_task() {
+        let executor = Rc::new(LocalExecutor::new());
+        let inner = Rc::clone(&executor);
+        let output = executor.run_until(
+            async move {
+                let handle = inner.spawn(Yield::once(std::future::ready(5)));
+                handle.await.unwrap()
+            }
+            .into_completion(),
+        );
+        assert_eq!(output, 5);
+    }
+
+    #[test]
+    fn panic() {
+        let executor = LocalExecutor::new();
+        let handle = executor.spawn(Yield::once(async { panic!("oh no") }.into_completion()));
+        let error = executor.run_until(handle).unwrap_err();
+        assert!(error.is_panic());
+        assert_eq!(
+            *error.try_into_panic().unwrap().downcast::<&str>().unwrap(),
+            "oh no"
+        );
+    }
+
+    #[test]
+    fn drop_cancels() {
+        let polls = Polls::new();
+
+        let executor = LocalExecutor::new();
+        let handle = executor.spawn(Forever::<()>::new(&polls, 3));
+        executor.run_until(Yield::new(3, std::future::ready(())));
+        assert_eq!(polls.cancelled(), 0);
+
+        drop(executor);
+        assert_eq!(polls.cancelled(), 1);
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn drop_handle_cancels() {
+        let polls = Polls::new();
+
+        let executor = LocalExecutor::new();
+        let handle = executor.spawn(Forever::<()>::new(&polls, 2));
+        executor.run_until(Yield::once(std::future::ready(())));
+
+        drop(handle);
+        assert_eq!(polls.cancelled(), 0);
+        executor.run();
+        assert_eq!(polls.cancelled(), 1);
+    }
+
+    #[test]
+    fn abort() {
+        let polls = Polls::new();
+
+        let executor = LocalExecutor::new();
+        let handle = executor.spawn(Forever::<()>::new(&polls, 2));
+        handle.abort();
+        assert!(executor.run_until(handle).unwrap_err().is_cancelled());
+        assert_eq!(polls.cancelled(), 1);
+
+        let handle = executor.spawn(std::future::ready(5));
+        executor.run();
+        handle.abort();
+        assert_eq!(executor.run_until(handle).unwrap(), 5);
+    }
+
+    #[test]
+    fn cancel_handle() {
+        let polls = Polls::new();
+
+        let executor = LocalExecutor::new();
+        let handle = executor.spawn(Forever::<()>::new(&polls, 5));
+        executor.run_until(PollThenCancel::new(handle));
+        assert_eq!(polls.cancelled(), 1);
+    }
+
+    #[test]
+    fn detach() {
+        let executor = LocalExecutor::new();
+        let done = Rc::new(Cell::new(false));
+        let task_done = Rc::clone(&done);
+        executor
+            .spawn(Yield::new(
+                3,
+                async move { task_done.set(true) }.into_completion(),
+            ))
+            .detach();
+        executor.run();
+        assert!(done.get());
+    }
+}
@@ -0,0 +1,19 @@
+//! Executors for running [`CompletionFuture`](crate::CompletionFuture)s.
+//!
+//! Unlike regular executors, these never drop a spawned task before it has finished: a task that
+//! needs to be stopped early is cancelled through
+//! [`poll_cancel`](crate::CompletionFuture::poll_cancel), and polled until that cancellation has
+//! completed.
+
+mod blocking;
+pub(crate) use blocking::spawn_blocking_unchecked;
+pub use blocking::{spawn_blocking, SpawnBlocking};
+
+mod task;
+pub use task::{JoinError, JoinHandle};
+
+mod local;
+pub use local::LocalExecutor;
+
+mod thread_pool;
+pub use thread_pool::ThreadPool;
@@ -0,0 +1,386 @@
+//! The task type shared by all the executors.
+
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::future::Future;
+use std::mem;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// A spawned task, as seen by the executor that runs it.
+pub(crate) trait Runnable: Send + Sync {
+    /// Poll the task once, returning whether it completed during this call.
+    fn run(self: Arc<Self>) -> bool;
+
+    /// Put the task in its executor's queue, if it isn't there already.
+    fn schedule(self: Arc<Self>);
+
+    /// Request that the task is cancelled. It will be driven through
+    /// [`poll_cancel`](CompletionFuture::poll_cancel) from now on.
+    fn cancel(self: Arc<Self>);
+}
+
+/// Get a key that uniquely identifies a task while it is alive.
+pub(crate) fn key(task: &Arc<dyn Runnable>) -> usize {
+    Arc::as_ptr(task).cast::<()>() as usize
+}
+
+/// Create a task from a `Send` future.
+///
+/// The task is not scheduled; call [`Runnable::schedule`] once the executor is tracking it.
+pub(crate) fn spawn<F, S>(future: F, schedule: S) -> (Arc<dyn Runnable>, JoinHandle<F::Output>)
+where
+    F: CompletionFuture + Send + 'static,
+    F::Output: Send + 'static,
+    S: Fn(Arc<dyn Runnable>) + Send + Sync + 'static,
+{
+    unsafe { spawn_unchecked(future, schedule) }
+}
+
+/// Create a task from a future that is not necessarily `Send`.
+///
+/// # Safety
+///
+/// If the future or its output is not `Send`, the caller must make sure that the task is only run,
+/// and only completes, on the thread it was created on.
+///
+/// Like [`spawn`], the task is not scheduled.
+pub(crate) unsafe fn spawn_unchecked<F, S>(
+    future: F,
+    schedule: S,
+) -> (Arc<dyn Runnable>, JoinHandle<F::Output>)
+where
+    F: CompletionFuture + 'static,
+    F::Output: 'static,
+    S: Fn(Arc<dyn Runnable>) + Send + Sync + 'static,
+{
+    let join = Arc::new(Mutex::new(JoinState {
+        output: None,
+        waker: None,
+        complete: false,
+        detached: false,
+    }));
+    let task: Arc<dyn Runnable> = Arc::new(Task {
+        future: Mutex::new(Some(future)),
+        scheduled: AtomicBool::new(false),
+        cancelled: AtomicBool::new(false),
+        join: Arc::clone(&join),
+        schedule,
+    });
+    let handle = JoinHandle {
+        join,
+        task: Some(Arc::clone(&task)),
+        cancelling: false,
+    };
+    (task, handle)
+}
+
+/// Drop a value, ignoring any panic from its destructor.
+fn discard<T>(value: T) {
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(|| drop(value))) {
+        // Dropping the payload could panic again.
+        mem::forget(payload);
+    }
+}
+
+struct Task<F: CompletionFuture, S> {
+    /// The future, or `None` once it has completed. It is pinned, as the task is never moved out
+    /// of its `Arc`.
+    future: Mutex<Option<F>>,
+    /// Whether the task is currently in the executor's queue.
+    scheduled: AtomicBool,
+    /// Whether the task should be driven through `poll_cancel` instead of `poll`.
+    cancelled: AtomicBool,
+    join: Arc<Mutex<JoinState<F::Output>>>,
+    schedule: S,
+}
+
+// The future and its output are only accessed when the task is run or completes, which
+// `spawn_unchecked`'s contract requires to happen on the right thread. Everything else is
+// thread-safe.
+unsafe impl<F: CompletionFuture, S: Send + Sync> Send for Task<F, S> {}
+unsafe impl<F: CompletionFuture, S: Send + Sync> Sync for Task<F, S> {}
+
+impl<F, S> Runnable for Task<F, S>
+where
+    F: CompletionFuture + 'static,
+    F::Output: 'static,
+    S: Fn(Arc<dyn Runnable>) + Send + Sync + 'static,
+{
+    fn run(self: Arc<Self>) -> bool {
+        self.scheduled.store(false, Ordering::SeqCst);
+
+        let mut future = lock(&self.future);
+        let fut = match &mut *future {
+            Some(fut) => unsafe { Pin::new_unchecked(fut) },
+            None => return false,
+        };
+
+        let waker = Waker::from(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+
+        let cancelled = self.cancelled.load(Ordering::SeqCst);
+        let res = catch_unwind(AssertUnwindSafe(|| unsafe {
+            if cancelled {
+                fut.poll_cancel(&mut cx)
+                    .map(|()| Err(JoinError::cancelled()))
+            } else {
+                fut.poll(&mut cx).map(Ok)
+            }
+        }));
+        let output = match res {
+            Ok(Poll::Ready(output)) => output,
+            Ok(Poll::Pending) => return false,
+            Err(payload) => Err(JoinError::panicked(payload)),
+        };
+
+        // Destructors can panic too. Those panics are caught rather than unwinding out of the
+        // executor, which would leave the task forever incomplete.
+        let output = match catch_unwind(AssertUnwindSafe(|| *future = None)) {
+            Ok(()) => output,
+            Err(payload) => {
+                discard(output);
+                Err(JoinError::panicked(payload))
+            }
+        };
+        drop(future);
+
+        let mut join = lock(&self.join);
+        join.complete = true;
+        let output = if join.detached {
+            Some(output)
+        } else {
+            join.output = Some(output);
+            None
+        };
+        let waker = join.waker.take();
+        drop(join);
+
+        discard(output);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        true
+    }
+
+    fn schedule(self: Arc<Self>) {
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            (self.schedule)(self.clone());
+        }
+    }
+
+    fn cancel(self: Arc<Self>) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.schedule();
+    }
+}
+
+impl<F, S> Wake for Task<F, S>
+where
+    F: CompletionFuture + 'static,
+    F::Output: 'static,
+    S: Fn(Arc<dyn Runnable>) + Send + Sync + 'static,
+{
+    fn wake(self: Arc<Self>) {
+        Runnable::schedule(self);
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        Runnable::schedule(Arc::clone(self));
+    }
+}
+
+/// The state shared between a task and its [`JoinHandle`].
+struct JoinState<T> {
+    output: Option<Result<T, JoinError>>,
+    waker: Option<Waker>,
+    complete: bool,
+    detached: bool,
+}
+
+/// A handle to a spawned task, which can be awaited to get the task's output.
+///
+/// The handle owns its task: dropping it or calling [`abort`](Self::abort) cancels the task. The
+/// task is never simply dropped; its executor drives it through
+/// [`poll_cancel`](CompletionFuture::poll_cancel) until the cancellation has completed. Use
+/// [`detach`](Self::detach) to let the task run to completion in the background instead.
+///
+/// The handle is itself a [`CompletionFuture`], and cancelling it cancels the task and waits for
+/// the task's cancellation to complete.
+///
+/// # Examples
+///
+/// ```
+/// use completion::future;
+/// use completion::executor::ThreadPool;
+///
+/// let pool = ThreadPool::new();
+///
+/// let handle = pool.spawn(std::future::pending::<()>());
+/// handle.abort();
+/// assert!(future::block_on(handle).unwrap_err().is_cancelled());
+/// ```
+#[must_use = "dropping a `JoinHandle` cancels its task"]
+pub struct JoinHandle<T> {
+    join: Arc<Mutex<JoinState<T>>>,
+    /// The task, or `None` if the handle has been detached.
+    task: Option<Arc<dyn Runnable>>,
+    /// Whether the handle has requested that the task is cancelled.
+    cancelling: bool,
+}
+
+impl<T> JoinHandle<T> {
+    /// Get whether the task has completed, either by finishing, being cancelled or panicking.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        lock(&self.join).complete
+    }
+
+    /// Cancel the task.
+    ///
+    /// The task will be driven through [`poll_cancel`](CompletionFuture::poll_cancel) until its
+    /// cancellation completes. Awaiting the handle afterwards gives a [`JoinError`] for which
+    /// [`is_cancelled`](JoinError::is_cancelled) is true, unless the task had already finished.
+    pub fn abort(&self) {
+        if let Some(task) = &self.task {
+            Arc::clone(task).cancel();
+        }
+    }
+
+    /// Drop the handle without cancelling the task, letting it run to completion in the
+    /// background. Its output will be dropped.
+    pub fn detach(mut self) {
+        self.task = None;
+    }
+}
+
+impl<T> CompletionFuture for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if !this.cancelling {
+            this.cancelling = true;
+            this.abort();
+        }
+
+        let mut join = lock(&this.join);
+        if join.complete {
+            let output = join.output.take();
+            drop(join);
+            drop(output);
+            Poll::Ready(())
+        } else {
+            join.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut join = lock(&self.join);
+        if let Some(output) = join.output.take() {
+            return Poll::Ready(output);
+        }
+        assert!(!join.complete, "`JoinHandle` polled after completion");
+        join.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let mut join = lock(&self.join);
+        join.detached = true;
+        let output = join.output.take();
+        join.waker = None;
+        let complete = join.complete;
+        drop(join);
+        drop(output);
+
+        if !complete {
+            self.abort();
+        }
+    }
+}
+
+impl<T> Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("finished", &self.is_finished())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error returned when awaiting a [`JoinHandle`] of a task that did not finish normally.
+pub struct JoinError {
+    /// The panic payload, or `None` if the task was cancelled.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl JoinError {
+    fn cancelled() -> Self {
+        Self { panic: None }
+    }
+
+    fn panicked(payload: Box<dyn Any + Send>) -> Self {
+        Self {
+            panic: Some(payload),
+        }
+    }
+
+    /// Get whether the task was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.panic.is_none()
+    }
+
+    /// Get whether the task panicked.
+    #[must_use]
+    pub fn is_panic(&self) -> bool {
+        self.panic.is_some()
+    }
+
+    /// Get the payload the task panicked with.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the task was cancelled instead of panicking.
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, Self> {
+        self.panic.ok_or_else(Self::cancelled)
+    }
+}
+
+impl Debug for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.panic {
+            Some(_) => f.write_str("JoinError::Panic(..)"),
+            None => f.write_str("JoinError::Cancelled"),
+        }
+    }
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.panic {
+            Some(_) => "task panicked",
+            None => "task was cancelled",
+        })
+    }
+}
+
+impl Error for JoinError {}
@@ -0,0 +1,390 @@
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+
+use completion_core::CompletionFuture;
+
+use super::task::{self, JoinHandle, Runnable};
+use crate::lock;
+
+/// A multi-threaded, work-stealing executor.
+///
+/// Each worker thread has its own queue of tasks. Tasks woken from a worker thread are pushed to
+/// that worker's queue, and idle workers steal tasks from the other workers' queues. A task can be
+/// polled by a different thread each time it is woken, but is never polled by two threads at once.
+///
+/// Dropping the pool shuts it down: every task that hasn't completed is cancelled through
+/// [`poll_cancel`](CompletionFuture::poll_cancel), and the drop blocks until all of them have
+/// finished cancelling and the worker threads have exited. If the pool is dropped by one of its own
+/// tasks, the drop instead returns immediately and the worker threads exit in the background. No
+/// task is ever dropped before it has completed.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{completion_async_move, future};
+/// use completion::executor::ThreadPool;
+///
+/// let pool = ThreadPool::new();
+///
+/// let handles: Vec<_> = (0..10)
+///     .map(|i| pool.spawn(completion_async_move!(i * 2)))
+///     .collect();
+///
+/// let sum: i32 = handles
+///     .into_iter()
+///     .map(|handle| future::block_on(handle).unwrap())
+///     .sum();
+/// assert_eq!(sum, 90);
+/// ```
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+type Queue = Mutex<VecDeque<Arc<dyn Runnable>>>;
+
+struct Shared {
+    /// The queue for tasks scheduled from outside the pool's threads.
+    injector: Queue,
+    /// The queue of each worker.
+    locals: Box<[Queue]>,
+    /// All the tasks that have not yet completed, by their key.
+    tasks: Mutex<HashMap<usize, Arc<dyn Runnable>>>,
+    /// Whether the pool is shutting down.
+    shutdown: AtomicBool,
+    /// Locked by workers while they decide whether to sleep.
+    sleep: Mutex<()>,
+    wake_worker: Condvar,
+}
+
+thread_local! {
+    /// The pool and index of the worker running on this thread.
+    static WORKER: Cell<Option<(*const Shared, usize)>> = const { Cell::new(None) };
+}
+
+impl Shared {
+    fn push(self: &Arc<Self>, task: Arc<dyn Runnable>) {
+        let worker = WORKER.with(Cell::get);
+        match worker {
+            Some((pool, index)) if pool == Arc::as_ptr(self) => {
+                lock(&self.locals[index]).push_back(task);
+            }
+            _ => lock(&self.injector).push_back(task),
+        }
+
+        let _guard = lock(&self.sleep);
+        self.wake_worker.notify_one();
+    }
+
+    fn find_task(&self, index: usize) -> Option<Arc<dyn Runnable>> {
+        if let Some(task) = lock(&self.locals[index]).pop_front() {
+            return Some(task);
+        }
+        if let Some(task) = lock(&self.injector).pop_front() {
+            return Some(task);
+        }
+        let workers = self.locals.len();
+        (1..workers)
+            .map(|offset| (index + offset) % workers)
+            .find_map(|victim| lock(&self.locals[victim]).pop_back())
+    }
+
+    fn has_queued(&self) -> bool {
+        !lock(&self.injector).is_empty() || self.locals.iter().any(|local| !lock(local).is_empty())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst) && lock(&self.tasks).is_empty()
+    }
+
+    fn run_worker(self: Arc<Self>, index: usize) {
+        WORKER.with(|worker| worker.set(Some((Arc::as_ptr(&self), index))));
+
+        loop {
+            if let Some(task) = self.find_task(index) {
+                let key = task::key(&task);
+                if task.run() {
+                    let mut tasks = lock(&self.tasks);
+                    tasks.remove(&key);
+                    let all_done = tasks.is_empty();
+                    drop(tasks);
+
+                    if all_done && self.shutdown.load(Ordering::SeqCst) {
+                        let _guard = lock(&self.sleep);
+                        self.wake_worker.notify_all();
+                    }
+                }
+                continue;
+            }
+
+            let guard = lock(&self.sleep);
+            if self.has_queued() {
+                continue;
+            }
+            if self.is_finished() {
+                break;
+            }
+            drop(
+                self.wake_worker
+                    .wait(guard)
+                    .unwrap_or_else(std::sync::PoisonError::into_inner),
+            );
+        }
+
+        WORKER.with(|worker| worker.set(None));
+    }
+}
+
+impl ThreadPool {
+    /// Create a thread pool with one worker thread per available CPU.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_threads(thread::available_parallelism().map_or(1, usize::from))
+    }
+
+    /// Create a thread pool with the given number of worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero, or if a thread could not be spawned.
+    #[must_use]
+    pub fn with_threads(threads: usize) -> Self {
+        assert!(threads > 0, "a thread pool needs at least one thread");
+
+        let shared = Arc::new(Shared {
+            injector: Mutex::new(VecDeque::new()),
+            locals: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            tasks: Mutex::new(HashMap::new()),
+            shutdown: AtomicBool::new(false),
+            sleep: Mutex::new(()),
+            wake_worker: Condvar::new(),
+        });
+
+        let threads = (0..threads)
+            .map(|index| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("completion-worker-{index}"))
+                    .spawn(move || shared.run_worker(index))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+
+        Self { shared, threads }
+    }
+
+    /// Spawn a future onto the thread pool.
+    ///
+    /// The returned [`JoinHandle`] can be used to await the future's output, and cancels the
+    /// future when dropped.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: CompletionFuture + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let shared = Arc::downgrade(&self.shared);
+        let schedule = move |task| {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                shared.push(task);
+            }
+        };
+        let (task, handle) = task::spawn(future, schedule);
+
+        let mut tasks = lock(&self.shared.tasks);
+        tasks.insert(task::key(&task), Arc::clone(&task));
+        let shutdown = self.shared.shutdown.load(Ordering::SeqCst);
+        drop(tasks);
+
+        if shutdown {
+            task.cancel();
+        } else {
+            task.schedule();
+        }
+
+        handle
+    }
+
+    /// Get the number of worker threads in the pool.
+    #[must_use]
+    pub fn threads(&self) -> usize {
+        self.shared.locals.len()
+    }
+}
+
+impl Default for ThreadPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+
+        let tasks: Vec<_> = lock(&self.shared.tasks).values().cloned().collect();
+        for task in tasks {
+            task.cancel();
+        }
+
+        {
+            let _guard = lock(&self.shared.sleep);
+            self.shared.wake_worker.notify_all();
+        }
+
+        // The pool can be dropped by one of its own tasks. The other workers can't exit until that
+        // task has finished running, so instead of waiting for them, leave them to exit by
+        // themselves once the remaining tasks have been cancelled.
+        let on_worker = matches!(
+            WORKER.with(Cell::get),
+            Some((pool, _)) if pool == Arc::as_ptr(&self.shared)
+        );
+        if on_worker {
+            self.threads.clear();
+            return;
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Debug for ThreadPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadPool")
+            .field("threads", &self.threads())
+            .field("tasks", &lock(&self.shared.tasks).len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use crate::future::block_on;
+    use crate::test_utils::{Forever, Polls, Yield};
+    use crate::FutureExt;
+
+    #[test]
+    fn spawn_many() {
+        let pool = ThreadPool::with_threads(4);
+        let handles: Vec<_> = (0..100)
+            .map(|i| pool.spawn(Yield::new(i % 7, std::future::ready(Box::new(i)))))
+            .collect();
+        let sum: usize = handles
+            .into_iter()
+            .map(|handle| *block_on(handle).unwrap())
+            .sum();
+        assert_eq!(sum, 4950);
+    }
+
+    #[test]
+    fn nested_spawn() {
+        let pool = Arc::new(ThreadPool::with_threads(2));
+        let inner = Arc::clone(&pool);
+        let handle = pool.spawn(
+            async move {
+                let handles: Vec<_> = (0..10)
+                    .map(|i| inner.spawn(Yield::once(std::future::ready(i))))
+                    .collect();
+                let mut sum = 0;
+                for handle in handles {
+                    sum += handle.await.unwrap();
+                }
+                sum
+            }
+            .into_completion(),
+        );
+        assert_eq!(block_on(handle).unwrap(), 45);
+    }
+
+    #[test]
+    fn panic() {
+        let pool = ThreadPool::with_threads(1);
+        let handle = pool.spawn(Yield::once(async { panic!("oh no") }.into_completion()));
+        let error = block_on(handle).unwrap_err();
+        assert!(error.is_panic());
+
+        // The worker survives the panic.
+        assert_eq!(block_on(pool.spawn(std::future::ready(1))).unwrap(), 1);
+    }
+
+    /// A future that completes immediately, but panics when it is dropped.
+    struct PanicOnDrop;
+
+    impl CompletionFuture for PanicOnDrop {
+        type Output = i32;
+
+        unsafe fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            Poll::Ready(1)
+        }
+        unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            Poll::Ready(())
+        }
+    }
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            panic!("oh no");
+        }
+    }
+
+    #[test]
+    fn panic_on_drop() {
+        let pool = ThreadPool::with_threads(1);
+        let error = block_on(pool.spawn(PanicOnDrop)).unwrap_err();
+        assert!(error.is_panic());
+
+        assert_eq!(block_on(pool.spawn(std::future::ready(1))).unwrap(), 1);
+        drop(pool);
+    }
+
+    #[test]
+    fn shutdown_cancels() {
+        let polls = Polls::new();
+        let pool = ThreadPool::with_threads(3);
+
+        let handles: Vec<_> = (0..10)
+            .map(|i| {
+                let handle = pool.spawn(Forever::<()>::new(&polls, i));
+                while polls.polled() <= i {
+                    thread::yield_now();
+                }
+                handle
+            })
+            .collect();
+
+        drop(pool);
+        assert_eq!(polls.cancelled(), 10);
+
+        for handle in handles {
+            assert!(block_on(handle).unwrap_err().is_cancelled());
+        }
+    }
+
+    #[test]
+    fn drop_from_task() {
+        let pool = Arc::new(Mutex::new(Some(ThreadPool::with_threads(2))));
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let slot = Arc::clone(&pool);
+        let _handle = lock(&pool).as_ref().unwrap().spawn(
+            async move {
+                drop(lock(&slot).take());
+                sender.send(()).unwrap();
+            }
+            .into_completion(),
+        );
+
+        receiver
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("dropping the pool from one of its tasks deadlocked");
+    }
+}
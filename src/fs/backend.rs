@@ -0,0 +1,174 @@
+//! The backends that file operations run on.
+
+#[cfg(feature = "io-uring")]
+use core::convert::TryFrom;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::fs::File as StdFile;
+use std::io::{Read, Result, Write};
+#[cfg(feature = "io-uring")]
+use std::os::unix::io::AsRawFd;
+#[cfg(feature = "io-uring")]
+use std::sync::Arc;
+
+use completion_io::ReadBufMut;
+#[cfg(feature = "io-uring")]
+use io_uring::{opcode, types};
+
+use crate::executor::{spawn_blocking_unchecked, SpawnBlocking};
+#[cfg(feature = "io-uring")]
+use crate::uring::{Driver, Op};
+
+/// How the operations of a file are run.
+#[derive(Debug, Clone)]
+pub(super) enum Backend {
+    /// Operations are submitted to `io_uring`.
+    #[cfg(feature = "io-uring")]
+    Uring(Arc<Driver>),
+    /// Operations run on the blocking thread pool.
+    Blocking,
+}
+
+impl Backend {
+    /// Get the best backend available.
+    pub(super) fn get() -> Self {
+        #[cfg(feature = "io-uring")]
+        if let Ok(driver) = Driver::get() {
+            return Self::Uring(driver);
+        }
+        Self::Blocking
+    }
+}
+
+/// A read or write in flight, which resolves to the number of bytes transferred.
+#[derive(Debug)]
+pub(super) enum Operation {
+    #[cfg(feature = "io-uring")]
+    Uring(Op),
+    Blocking(SpawnBlocking<Result<usize>>),
+}
+
+impl Operation {
+    /// Start reading from the file into the unfilled part of the buffer.
+    ///
+    /// If `pos` is [`None`], the current position of the file is used.
+    ///
+    /// # Safety
+    ///
+    /// The file and buffer must remain valid until the operation completes or is dropped.
+    #[cfg_attr(not(feature = "io-uring"), allow(clippy::unnecessary_wraps))]
+    pub(super) unsafe fn read(
+        backend: &Backend,
+        file: &StdFile,
+        pos: Option<u64>,
+        buf: &mut ReadBufMut<'_>,
+    ) -> Result<Self> {
+        match backend {
+            #[cfg(feature = "io-uring")]
+            Backend::Uring(driver) => {
+                let unfilled = buf.unfilled_mut();
+                let len = u32::try_from(unfilled.len()).unwrap_or(u32::MAX);
+                let entry = opcode::Read::new(
+                    types::Fd(file.as_raw_fd()),
+                    unfilled.as_mut_ptr().cast(),
+                    len,
+                )
+                .offset(pos.unwrap_or(u64::MAX))
+                .build();
+                Op::submit(Arc::clone(driver), entry).map(Self::Uring)
+            }
+            Backend::Blocking => {
+                let buf = buf.initialize_unfilled();
+                Ok(Self::Blocking(spawn_blocking_unchecked(move || {
+                    read_at(file, buf, pos)
+                })))
+            }
+        }
+    }
+
+    /// Start writing the buffer to the file.
+    ///
+    /// If `pos` is [`None`], the current position of the file is used.
+    ///
+    /// # Safety
+    ///
+    /// The file and buffer must remain valid until the operation completes or is dropped.
+    #[cfg_attr(not(feature = "io-uring"), allow(clippy::unnecessary_wraps))]
+    pub(super) unsafe fn write(
+        backend: &Backend,
+        file: &StdFile,
+        pos: Option<u64>,
+        buf: &[u8],
+    ) -> Result<Self> {
+        match backend {
+            #[cfg(feature = "io-uring")]
+            Backend::Uring(driver) => {
+                let len = u32::try_from(buf.len()).unwrap_or(u32::MAX);
+                let entry = opcode::Write::new(types::Fd(file.as_raw_fd()), buf.as_ptr(), len)
+                    .offset(pos.unwrap_or(u64::MAX))
+                    .build();
+                Op::submit(Arc::clone(driver), entry).map(Self::Uring)
+            }
+            Backend::Blocking => Ok(Self::Blocking(spawn_blocking_unchecked(move || {
+                write_at(file, buf, pos)
+            }))),
+        }
+    }
+
+    /// Poll for the result of the operation.
+    pub(super) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self {
+            #[cfg(feature = "io-uring")]
+            Self::Uring(op) => op.poll(cx).map_ok(|n| n as usize),
+            Self::Blocking(task) => Future::poll(Pin::new(task), cx),
+        }
+    }
+
+    /// Cancel the operation, resolving to its result if it completed anyway.
+    pub(super) fn poll_cancel(&mut self, cx: &mut Context<'_>) -> Poll<Option<usize>> {
+        match self {
+            #[cfg(feature = "io-uring")]
+            Self::Uring(op) => op.poll_cancel(cx).map(|res| res.ok().map(|n| n as usize)),
+            Self::Blocking(task) => task.poll_stop(cx).map(|res| res.and_then(Result::ok)),
+        }
+    }
+}
+
+fn read_at(mut file: &StdFile, buf: &mut [u8], pos: Option<u64>) -> Result<usize> {
+    let Some(pos) = pos else {
+        return file.read(buf);
+    };
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::FileExt::read_at(file, buf, pos)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::FileExt::seek_read(file, buf, pos)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(pos))?;
+        file.read(buf)
+    }
+}
+
+fn write_at(mut file: &StdFile, buf: &[u8], pos: Option<u64>) -> Result<usize> {
+    let Some(pos) = pos else {
+        return file.write(buf);
+    };
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::FileExt::write_at(file, buf, pos)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::FileExt::seek_write(file, buf, pos)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(pos))?;
+        file.write(buf)
+    }
+}
@@ -0,0 +1,103 @@
+use core::fmt::{self, Debug, Formatter};
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::collections::VecDeque;
+use std::fs::{self, DirEntry};
+use std::io::Result;
+
+use completion_core::CompletionStream;
+use futures_core::{ready, Stream};
+
+use crate::executor::{spawn_blocking, SpawnBlocking};
+
+/// The number of entries read on the blocking pool at once.
+const BATCH_SIZE: usize = 32;
+
+/// A batch of entries, and the directory if it has more.
+type Batch = (VecDeque<Result<DirEntry>>, Option<fs::ReadDir>);
+
+/// Stream for [`read_dir`](super::read_dir).
+///
+/// Entries are read on the blocking pool in batches.
+#[must_use = "streams do nothing unless you use them"]
+pub struct ReadDir {
+    entries: VecDeque<Result<DirEntry>>,
+    state: State,
+}
+
+enum State {
+    Idle(fs::ReadDir),
+    Reading(SpawnBlocking<Batch>),
+    Done,
+}
+
+impl ReadDir {
+    pub(super) fn new(dir: fs::ReadDir) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            state: State::Idle(dir),
+        }
+    }
+
+    /// Poll for the batch being read and store it.
+    fn poll_batch(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let State::Reading(task) = &mut self.state {
+            let (entries, dir) = ready!(Future::poll(Pin::new(task), cx));
+            self.entries = entries;
+            self.state = dir.map_or(State::Done, State::Idle);
+        }
+        Poll::Ready(())
+    }
+}
+
+fn read_batch(mut dir: fs::ReadDir) -> Batch {
+    let entries: VecDeque<_> = dir.by_ref().take(BATCH_SIZE).collect();
+    let more = entries.len() == BATCH_SIZE;
+    (entries, if more { Some(dir) } else { None })
+}
+
+impl Stream for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(entry) = this.entries.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            match mem::replace(&mut this.state, State::Done) {
+                State::Idle(dir) => {
+                    this.state = State::Reading(spawn_blocking(move || read_batch(dir)));
+                }
+                state @ State::Reading(_) => {
+                    this.state = state;
+                    ready!(this.poll_batch(cx));
+                }
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl CompletionStream for ReadDir {
+    type Item = Result<DirEntry>;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Stream::poll_next(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Stopping the batch would lose the directory along with it, so wait for it instead.
+        self.get_mut().poll_batch(cx)
+    }
+}
+
+impl Debug for ReadDir {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadDir")
+            .field("buffered", &self.entries.len())
+            .field("reading", &matches!(self.state, State::Reading(_)))
+            .finish_non_exhaustive()
+    }
+}
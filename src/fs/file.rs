@@ -0,0 +1,570 @@
+use core::fmt::{self, Debug, Formatter};
+use core::future::{self, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+#[cfg(feature = "io-uring")]
+use std::ffi::CString;
+use std::fs::{File as StdFile, OpenOptions};
+use std::io::{self, IoSlice, Result, SeekFrom};
+#[cfg(feature = "io-uring")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(feature = "io-uring")]
+use std::os::unix::io::FromRawFd;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "io-uring")]
+use std::sync::Arc;
+
+use completion_core::CompletionFuture;
+use completion_io::{
+    AsyncReadWith, AsyncSeekWith, AsyncWriteWith, DefaultWriteVectored, ReadBufMut,
+};
+use futures_core::ready;
+#[cfg(feature = "io-uring")]
+use io_uring::{opcode, types};
+
+use super::backend::{Backend, Operation};
+use crate::executor::{spawn_blocking, SpawnBlocking};
+#[cfg(feature = "io-uring")]
+use crate::uring::Op;
+
+/// An open file on the filesystem.
+///
+/// Reads and writes use the caller's buffers directly. They are submitted to `io_uring` when the
+/// `io-uring` feature is enabled and the system supports it, and otherwise run on the
+/// [blocking pool](crate::executor::spawn_blocking).
+///
+/// Files that support seeking keep track of their own position, which is advanced by reads and
+/// writes and can be changed with [`seek`](AsyncSeekWith::seek). Other files, such as pipes,
+/// always read from and write to the current position of the underlying file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{fs::File, io::AsyncWriteExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let mut file = File::create("hello.txt").await?;
+/// file.write_all(b"Hello world!").await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct File {
+    inner: StdFile,
+    /// The position of the file, or [`None`] if it cannot be seeked.
+    pos: Option<u64>,
+    backend: Backend,
+}
+
+impl File {
+    /// Open a file in read-only mode.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened.
+    pub fn open<P: AsRef<Path>>(path: P) -> Open {
+        Open::new(path.as_ref(), false)
+    }
+
+    /// Open a file in write-only mode, creating it if it doesn't exist and truncating it if it
+    /// does.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened.
+    pub fn create<P: AsRef<Path>>(path: P) -> Open {
+        Open::new(path.as_ref(), true)
+    }
+
+    /// Convert a standard library file to a `File`.
+    ///
+    /// If the file can be seeked, the new `File` starts at its current position.
+    #[must_use]
+    pub fn from_std(file: StdFile) -> Self {
+        Self::with_backend(file, Backend::get())
+    }
+
+    fn with_backend(mut file: StdFile, backend: Backend) -> Self {
+        let pos = io::Seek::stream_position(&mut file).ok();
+        Self {
+            inner: file,
+            pos,
+            backend,
+        }
+    }
+
+    /// Convert this file to a standard library file.
+    ///
+    /// If the file can be seeked, the position of the returned file is set to this file's
+    /// position.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the position cannot be set.
+    pub fn into_std(mut self) -> Result<StdFile> {
+        if let Some(pos) = self.pos {
+            io::Seek::seek(&mut self.inner, SeekFrom::Start(pos))?;
+        }
+        Ok(self.inner)
+    }
+
+    fn seek_sync(&mut self, pos: SeekFrom) -> Result<u64> {
+        let Some(current) = self.pos else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "file does not support seeking",
+            ));
+        };
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.pos = Some(n);
+                return Ok(n);
+            }
+            SeekFrom::Current(offset) => (current, offset),
+            SeekFrom::End(offset) => (self.inner.metadata()?.len(), offset),
+        };
+        let new = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.pos = Some(new);
+        Ok(new)
+    }
+}
+
+impl From<StdFile> for File {
+    fn from(file: StdFile) -> Self {
+        Self::from_std(file)
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl IntoRawFd for File {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for File {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("File")
+            .field("inner", &self.inner)
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future for [`File::open`] and [`File::create`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct Open {
+    /// Declared first so that it is dropped, blocking until the operation has been cancelled,
+    /// before the path it uses.
+    state: OpenState,
+    path: PathBuf,
+    /// Whether to open the file for writing instead of reading.
+    write: bool,
+    /// The backend chosen when the future is first polled.
+    backend: Option<Backend>,
+    /// The path passed to `io_uring`, which must outlive the operation.
+    #[cfg(feature = "io-uring")]
+    c_path: Option<CString>,
+}
+
+enum OpenState {
+    Idle,
+    #[cfg(feature = "io-uring")]
+    Uring(Op),
+    Blocking(SpawnBlocking<Result<StdFile>>),
+}
+
+impl Open {
+    fn new(path: &Path, write: bool) -> Self {
+        Self {
+            state: OpenState::Idle,
+            path: path.to_owned(),
+            write,
+            backend: None,
+            #[cfg(feature = "io-uring")]
+            c_path: None,
+        }
+    }
+
+    #[cfg_attr(not(feature = "io-uring"), allow(clippy::unnecessary_wraps))]
+    fn start(&mut self) -> Result<OpenState> {
+        match self.backend.get_or_insert_with(Backend::get) {
+            #[cfg(feature = "io-uring")]
+            Backend::Uring(driver) => {
+                let path = CString::new(self.path.as_os_str().as_bytes()).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "file path contained a null byte",
+                    )
+                })?;
+                let flags = if self.write {
+                    libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+                } else {
+                    libc::O_RDONLY
+                };
+                let entry = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+                    .flags(flags | libc::O_CLOEXEC)
+                    .mode(0o666)
+                    .build();
+                let driver = Arc::clone(driver);
+                self.c_path = Some(path);
+                // Safety: the path lives in the future, which will not be dropped while the
+                // operation runs.
+                unsafe { Op::submit(driver, entry) }.map(OpenState::Uring)
+            }
+            Backend::Blocking => {
+                let mut options = OpenOptions::new();
+                if self.write {
+                    options.write(true).create(true).truncate(true);
+                } else {
+                    options.read(true);
+                }
+                let path = self.path.clone();
+                Ok(OpenState::Blocking(spawn_blocking(move || {
+                    options.open(path)
+                })))
+            }
+        }
+    }
+}
+
+impl CompletionFuture for Open {
+    type Output = Result<File>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if let OpenState::Idle = this.state {
+            match this.start() {
+                Ok(state) => this.state = state,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = match &mut this.state {
+            OpenState::Idle => unreachable!(),
+            #[cfg(feature = "io-uring")]
+            OpenState::Uring(op) =>
+            {
+                #[allow(clippy::cast_possible_wrap)]
+                ready!(op.poll(cx)).map(|fd| StdFile::from_raw_fd(fd as RawFd))
+            }
+            OpenState::Blocking(task) => ready!(Future::poll(Pin::new(task), cx)),
+        };
+        this.state = OpenState::Idle;
+
+        let backend = this.backend.clone().unwrap();
+        Poll::Ready(res.map(|file| File::with_backend(file, backend)))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.state {
+            OpenState::Idle => {}
+            #[cfg(feature = "io-uring")]
+            OpenState::Uring(op) => {
+                // The file may have been opened before the operation could be cancelled.
+                #[allow(clippy::cast_possible_wrap)]
+                if let Ok(fd) = ready!(op.poll_cancel(cx)) {
+                    drop(StdFile::from_raw_fd(fd as RawFd));
+                }
+            }
+            // A file opened on the blocking pool is closed along with the task's output.
+            OpenState::Blocking(task) => ready!(task.poll_stop(cx).map(drop)),
+        }
+        self.state = OpenState::Idle;
+        Poll::Ready(())
+    }
+}
+
+impl Debug for Open {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Open")
+            .field("path", &self.path)
+            .field("write", &self.write)
+            .field("running", &!matches!(self.state, OpenState::Idle))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> AsyncReadWith<'a> for File {
+    type ReadFuture = ReadFile<'a>;
+
+    fn read(&'a mut self, buf: ReadBufMut<'a>) -> Self::ReadFuture {
+        ReadFile {
+            file: self,
+            buf,
+            op: None,
+        }
+    }
+}
+
+/// Future for [`read`](AsyncReadWith::read) on a [`File`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct ReadFile<'a> {
+    file: &'a mut File,
+    buf: ReadBufMut<'a>,
+    op: Option<Operation>,
+}
+
+impl ReadFile<'_> {
+    fn fill(&mut self, n: usize) {
+        // Safety: the operation initialized this many bytes.
+        unsafe { self.buf.assume_init(n) };
+        self.buf.add_filled(n);
+        if let Some(pos) = &mut self.file.pos {
+            *pos += n as u64;
+        }
+    }
+}
+
+impl CompletionFuture for ReadFile<'_> {
+    type Output = Result<()>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let file = &*this.file;
+            // Safety: the file and buffer are borrowed for the lifetime of the future, which will
+            // not be dropped while the operation runs.
+            match Operation::read(&file.backend, &file.inner, file.pos, &mut this.buf) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        this.fill(res?);
+        Poll::Ready(Ok(()))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if let Some(op) = &mut this.op {
+            let read = ready!(op.poll_cancel(cx));
+            this.op = None;
+            // Data that was read before the operation could be cancelled can't be put back into
+            // pipes and sockets, so keep it in the buffer.
+            if let Some(n) = read {
+                this.fill(n);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for ReadFile<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadFile")
+            .field("file", &self.file)
+            .field("buf", &self.buf)
+            .field("running", &self.op.is_some())
+            .finish()
+    }
+}
+
+impl<'a> AsyncWriteWith<'a> for File {
+    type WriteFuture = WriteFile<'a>;
+    type WriteVectoredFuture = DefaultWriteVectored<'a, Self>;
+    type FlushFuture = future::Ready<Result<()>>;
+
+    fn write(&'a mut self, buf: &'a [u8]) -> Self::WriteFuture {
+        WriteFile {
+            file: self,
+            buf,
+            op: None,
+        }
+    }
+    fn write_vectored(&'a mut self, bufs: &'a [IoSlice<'a>]) -> Self::WriteVectoredFuture {
+        DefaultWriteVectored::new(self, bufs)
+    }
+    fn flush(&'a mut self) -> Self::FlushFuture {
+        future::ready(Ok(()))
+    }
+}
+
+/// Future for [`write`](AsyncWriteWith::write) on a [`File`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct WriteFile<'a> {
+    file: &'a mut File,
+    buf: &'a [u8],
+    op: Option<Operation>,
+}
+
+impl WriteFile<'_> {
+    fn advance(&mut self, n: usize) {
+        if let Some(pos) = &mut self.file.pos {
+            *pos += n as u64;
+        }
+    }
+}
+
+impl CompletionFuture for WriteFile<'_> {
+    type Output = Result<usize>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let file = &*this.file;
+            // Safety: the file and buffer are borrowed for the lifetime of the future, which will
+            // not be dropped while the operation runs.
+            match Operation::write(&file.backend, &file.inner, file.pos, this.buf) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        let n = res?;
+        this.advance(n);
+        Poll::Ready(Ok(n))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if let Some(op) = &mut this.op {
+            let written = ready!(op.poll_cancel(cx));
+            this.op = None;
+            // Bytes that were written before the operation could be cancelled can't be unwritten,
+            // so keep the position in sync with them.
+            if let Some(n) = written {
+                this.advance(n);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for WriteFile<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteFile")
+            .field("file", &self.file)
+            .field("buf", &self.buf)
+            .field("running", &self.op.is_some())
+            .finish()
+    }
+}
+
+impl<'a> AsyncSeekWith<'a> for File {
+    type SeekFuture = future::Ready<Result<u64>>;
+
+    fn seek(&'a mut self, pos: SeekFrom) -> Self::SeekFuture {
+        future::ready(self.seek_sync(pos))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    use std::mem::MaybeUninit;
+
+    use completion_io::ReadBuf;
+
+    use crate::future::block_on;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::test_utils::{poll_cancel_once, poll_once};
+
+    /// All the backends available on this system.
+    fn backends() -> Vec<Backend> {
+        #[cfg_attr(not(feature = "io-uring"), allow(unused_mut))]
+        let mut backends = vec![Backend::Blocking];
+        #[cfg(feature = "io-uring")]
+        if crate::uring::available() {
+            backends.push(Backend::get());
+        }
+        backends
+    }
+
+    /// A path in the temporary directory that is unique to this test.
+    pub(in crate::fs) fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("completion-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn read_write_seek() {
+        let path = temp_path("read-write-seek");
+
+        for backend in backends() {
+            let mut create = File::create(&path);
+            create.backend = Some(backend.clone());
+            let mut file = block_on(create).unwrap();
+            block_on(file.write_all(b"Hello world!")).unwrap();
+            drop(file);
+
+            let mut open = File::open(&path);
+            open.backend = Some(backend);
+            let mut file = block_on(open).unwrap();
+            let mut bytes = [MaybeUninit::uninit(); 5];
+            let mut buf = ReadBuf::uninit(&mut bytes);
+            block_on(file.read(buf.as_mut())).unwrap();
+            assert_eq!(buf.as_mut().filled(), b"Hello");
+
+            assert_eq!(block_on(file.seek(SeekFrom::Current(1))).unwrap(), 6);
+            let mut rest = Vec::new();
+            block_on(file.read_to_end(&mut rest)).unwrap();
+            assert_eq!(rest, b"world!");
+
+            assert_eq!(block_on(file.seek(SeekFrom::End(-6))).unwrap(), 6);
+            assert!(block_on(file.seek(SeekFrom::Current(-7))).is_err());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(block_on(File::open(&path)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_read() {
+        use std::os::unix::io::OwnedFd;
+        use std::os::unix::net::UnixStream;
+
+        for backend in backends() {
+            // A socket can't be seeked, and reads from it wait for data like a pipe's.
+            let (reader, writer) = UnixStream::pair().unwrap();
+            let blocking = matches!(backend, Backend::Blocking);
+            let mut reader = File::with_backend(OwnedFd::from(reader).into(), backend.clone());
+            let mut writer = File::with_backend(OwnedFd::from(writer).into(), backend);
+
+            // Nothing has been written, so the read waits until it is cancelled.
+            let mut bytes = [MaybeUninit::uninit(); 5];
+            let mut buf = ReadBuf::uninit(&mut bytes);
+            let mut read = reader.read(buf.as_mut());
+            assert!(poll_once(&mut read).is_none());
+            let mut waited = false;
+            if !poll_cancel_once(&mut read) {
+                // A read that has started on the blocking pool can't be interrupted, so
+                // cancelling it waits for it to finish and the data it read is kept.
+                if blocking {
+                    block_on(writer.write_all(b"kept")).unwrap();
+                    waited = true;
+                }
+                while !poll_cancel_once(&mut read) {
+                    std::thread::yield_now();
+                }
+            }
+            drop(read);
+            assert_eq!(
+                buf.as_mut().filled(),
+                if waited { &b"kept"[..] } else { b"" }
+            );
+            buf.clear();
+
+            // The socket is still usable.
+            assert!(block_on(writer.seek(SeekFrom::Start(0))).is_err());
+            block_on(writer.write_all(b"data")).unwrap();
+            block_on(reader.read(buf.as_mut())).unwrap();
+            assert_eq!(buf.as_mut().filled(), b"data");
+        }
+    }
+}
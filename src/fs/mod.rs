@@ -0,0 +1,224 @@
+//! Filesystem operations.
+//!
+//! When the `io-uring` feature is enabled and the system supports it, reads and writes of
+//! [`File`]s are submitted to a shared `io_uring` instance. Otherwise, and for operations
+//! `io_uring` can't perform, they run on the [blocking pool](crate::executor::spawn_blocking).
+//!
+//! Either way, [`File`] reads and writes borrow the caller's buffers directly: because completion
+//! futures cannot be dropped while an operation is in flight, the buffers can be handed to the
+//! kernel or the worker thread without copying them into intermediate buffers.
+
+use core::fmt::{self, Debug, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::fs::Metadata;
+use std::io::Result;
+use std::path::Path;
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+
+use crate::executor::{spawn_blocking, SpawnBlocking};
+
+mod backend;
+
+mod dir;
+pub use dir::ReadDir;
+
+mod file;
+pub use file::*;
+
+/// Read the entire contents of a file into a vector of bytes.
+///
+/// # Errors
+///
+/// Fails if the file cannot be opened or read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{fs, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let contents = fs::read("hello.txt").await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub fn read<P: AsRef<Path>>(path: P) -> Blocking<Result<Vec<u8>>> {
+    let path = path.as_ref().to_owned();
+    Blocking::new(move || std::fs::read(path))
+}
+
+/// Write a slice as the entire contents of a file, creating it if it doesn't exist and truncating
+/// it if it does.
+///
+/// The contents are moved to the blocking pool, so they must be owned. To write borrowed data,
+/// open the file with [`File::create`] and use
+/// [`AsyncWriteExt::write_all`](crate::io::AsyncWriteExt::write_all).
+///
+/// # Errors
+///
+/// Fails if the file cannot be opened or written to.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{fs, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// fs::write("hello.txt", "Hello world!").await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub fn write<P, C>(path: P, contents: C) -> Blocking<Result<()>>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]> + Send + 'static,
+{
+    let path = path.as_ref().to_owned();
+    Blocking::new(move || std::fs::write(path, contents))
+}
+
+/// Query the metadata of a file or directory, following symbolic links.
+///
+/// # Errors
+///
+/// Fails if the path does not exist or cannot be accessed.
+pub fn metadata<P: AsRef<Path>>(path: P) -> Blocking<Result<Metadata>> {
+    let path = path.as_ref().to_owned();
+    Blocking::new(move || std::fs::metadata(path))
+}
+
+/// Get a stream of the entries in a directory.
+///
+/// # Errors
+///
+/// Fails if the path does not exist or is not a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{fs, CompletionStreamExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let mut entries = fs::read_dir(".").await?;
+/// while let Some(entry) = entries.next().await {
+///     println!("{}", entry?.path().display());
+/// }
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Blocking<Result<ReadDir>> {
+    let path = path.as_ref().to_owned();
+    Blocking::new(move || std::fs::read_dir(path).map(ReadDir::new))
+}
+
+/// Future for filesystem operations that run on the blocking pool, such as [`read`] and
+/// [`write`](fn@write).
+///
+/// The operation is started when the future is first polled.
+#[must_use = "futures do nothing unless you use them"]
+pub struct Blocking<T> {
+    f: Option<Box<dyn FnOnce() -> T + Send>>,
+    task: Option<SpawnBlocking<T>>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn new(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self {
+            f: Some(Box::new(f)),
+            task: None,
+        }
+    }
+}
+
+impl<T: Send + 'static> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(f) = self.f.take() {
+            self.task = Some(spawn_blocking(f));
+        }
+        let task = self
+            .task
+            .as_mut()
+            .expect("`Blocking` polled after completion");
+        let output = ready!(Future::poll(Pin::new(task), cx));
+        self.task = None;
+        Poll::Ready(output)
+    }
+}
+
+impl<T: Send + 'static> CompletionFuture for Blocking<T> {
+    type Output = T;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.f = None;
+        if let Some(task) = &mut self.task {
+            ready!(task.poll_stop(cx));
+            self.task = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+impl<T> Debug for Blocking<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blocking")
+            .field("task", &self.task)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::future::block_on;
+    use crate::CompletionStreamExt;
+
+    #[test]
+    fn read_write_metadata() {
+        let path = file::tests::temp_path("read-write-metadata");
+
+        block_on(write(&path, b"Hello world!".to_vec())).unwrap();
+        assert_eq!(block_on(read(&path)).unwrap(), b"Hello world!");
+        assert_eq!(block_on(metadata(&path)).unwrap().len(), 12);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(block_on(read(&path)).is_err());
+        assert!(block_on(metadata(&path)).is_err());
+    }
+
+    #[test]
+    fn read_dir_entries() {
+        let path = file::tests::temp_path("read-dir");
+        std::fs::create_dir(&path).unwrap();
+        for i in 0..100 {
+            std::fs::write(path.join(i.to_string()), "").unwrap();
+        }
+
+        let mut entries = block_on(read_dir(&path)).unwrap();
+        let mut names: Vec<usize> = Vec::new();
+        while let Some(entry) = block_on(entries.next()) {
+            names.push(
+                entry
+                    .unwrap()
+                    .file_name()
+                    .to_str()
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+            );
+        }
+        names.sort_unstable();
+        assert_eq!(names, (0..100).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(&path).unwrap();
+        assert!(block_on(read_dir(&path)).is_err());
+    }
+}
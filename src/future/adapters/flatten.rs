@@ -0,0 +1,111 @@
+//! `Flatten`.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::flatten`](crate::CompletionFutureExt::flatten).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Flatten<Fut: CompletionFuture> {
+        // `None` once it has completed.
+        #[pin]
+        outer: Option<Fut>,
+        #[pin]
+        inner: Option<Fut::Output>,
+    }
+}
+
+impl<Fut: CompletionFuture> Flatten<Fut> {
+    pub(crate) fn new(future: Fut) -> Self {
+        Self {
+            outer: Some(future),
+            inner: None,
+        }
+    }
+}
+
+impl<Fut> CompletionFuture for Flatten<Fut>
+where
+    Fut: CompletionFuture,
+    Fut::Output: CompletionFuture,
+{
+    type Output = <Fut::Output as CompletionFuture>::Output;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if let Some(outer) = this.outer.as_mut().as_pin_mut() {
+            let inner = ready!(outer.poll(cx));
+            this.outer.set(None);
+            this.inner.set(Some(inner));
+        }
+
+        this.inner
+            .as_pin_mut()
+            .expect("`Flatten` polled after panicking")
+            .poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if let Some(inner) = this.inner.as_pin_mut() {
+            inner.poll_cancel(cx)
+        } else if let Some(outer) = this.outer.as_pin_mut() {
+            outer.poll_cancel(cx)
+        } else {
+            // The outer future completed, and then dropping it panicked.
+            Poll::Ready(())
+        }
+    }
+}
+
+impl<Fut> Future for Flatten<Fut>
+where
+    Fut: CompletionFuture + Future<Output = <Fut as CompletionFuture>::Output>,
+    <Fut as CompletionFuture>::Output: CompletionFuture
+        + Future<Output = <<Fut as CompletionFuture>::Output as CompletionFuture>::Output>,
+{
+    type Output = <<Fut as CompletionFuture>::Output as CompletionFuture>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::ready;
+
+    use crate::test_utils::{poll_cancel_once, poll_once, Forever, Polls};
+    use crate::CompletionFutureExt;
+
+    #[test]
+    fn cancel_outer() {
+        let outer = Polls::new();
+        let fut = Forever::<Forever<()>>::new(&outer, 1).flatten();
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(outer.cancel_polls(), 2);
+        assert_eq!(outer.cancelled(), 1);
+    }
+
+    #[test]
+    fn cancel_inner() {
+        let inner = Polls::new();
+        let fut = ready(Forever::<()>::new(&inner, 1)).flatten();
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert_eq!(inner.polled(), 1);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(inner.cancel_polls(), 2);
+        assert_eq!(inner.cancelled(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use completion_core::{CompletionFuture, FusedCompletionFuture};
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::fuse`](crate::CompletionFutureExt::fuse).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Fuse<Fut> {
+        #[pin]
+        future: Option<Fut>,
+    }
+}
+
+impl<Fut> Fuse<Fut> {
+    pub(crate) fn new(future: Fut) -> Self {
+        Self {
+            future: Some(future),
+        }
+    }
+}
+
+impl<Fut: CompletionFuture> CompletionFuture for Fuse<Fut> {
+    type Output = Fut::Output;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        match this.future.as_mut().as_pin_mut() {
+            Some(future) => {
+                let output = ready!(future.poll(cx));
+                this.future.set(None);
+                Poll::Ready(output)
+            }
+            None => Poll::Pending,
+        }
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut this = self.project();
+        if let Some(future) = this.future.as_mut().as_pin_mut() {
+            ready!(future.poll_cancel(cx));
+            this.future.set(None);
+        }
+        Poll::Ready(())
+    }
+}
+
+impl<Fut: CompletionFuture> FusedCompletionFuture for Fuse<Fut> {
+    fn is_terminated(&self) -> bool {
+        self.future.is_none()
+    }
+}
+
+impl<Fut> Future for Fuse<Fut>
+where
+    Fut: CompletionFuture + Future<Output = <Fut as CompletionFuture>::Output>,
+{
+    type Output = <Fut as CompletionFuture>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::future::ready;
+
+    use crate::test_utils::{poll_cancel_once, poll_once, Yield};
+    use crate::CompletionFutureExt;
+
+    #[test]
+    fn terminated() {
+        let fut = Yield::once(ready(1)).fuse();
+        futures_lite::pin!(fut);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), Some(1));
+        assert!(fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), None);
+
+        let fut = Yield::new(2, ready(1)).fuse();
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(!fut.is_terminated());
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert!(fut.is_terminated());
+    }
+}
@@ -0,0 +1,62 @@
+//! `Inspect`.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::inspect`](crate::CompletionFutureExt::inspect).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Inspect<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F> Inspect<Fut, F> {
+    pub(crate) fn new(future: Fut, f: F) -> Self {
+        Self { future, f: Some(f) }
+    }
+}
+
+impl<Fut, F> CompletionFuture for Inspect<Fut, F>
+where
+    Fut: CompletionFuture,
+    F: FnOnce(&Fut::Output),
+{
+    type Output = Fut::Output;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("`Inspect` polled after completion");
+        f(&output);
+        Poll::Ready(output)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if this.f.is_none() {
+            // The future completed, and then `f` panicked.
+            return Poll::Ready(());
+        }
+        this.future.poll_cancel(cx)
+    }
+}
+
+impl<Fut, F> Future for Inspect<Fut, F>
+where
+    Fut: CompletionFuture + Future<Output = <Fut as CompletionFuture>::Output>,
+    F: FnOnce(&<Fut as CompletionFuture>::Output),
+{
+    type Output = <Fut as CompletionFuture>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
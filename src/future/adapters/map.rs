@@ -0,0 +1,174 @@
+//! `Map` and `Then`.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::map`](crate::CompletionFutureExt::map).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Map<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F> Map<Fut, F> {
+    pub(crate) fn new(future: Fut, f: F) -> Self {
+        Self { future, f: Some(f) }
+    }
+}
+
+impl<Fut, F, T> CompletionFuture for Map<Fut, F>
+where
+    Fut: CompletionFuture,
+    F: FnOnce(Fut::Output) -> T,
+{
+    type Output = T;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("`Map` polled after completion");
+        Poll::Ready(f(output))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if this.f.is_none() {
+            // The future completed, and then `f` panicked.
+            return Poll::Ready(());
+        }
+        this.future.poll_cancel(cx)
+    }
+}
+
+impl<Fut, F, T> Future for Map<Fut, F>
+where
+    Fut: CompletionFuture + Future<Output = <Fut as CompletionFuture>::Output>,
+    F: FnOnce(<Fut as CompletionFuture>::Output) -> T,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::then`](crate::CompletionFutureExt::then).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Then<Fut1, F, Fut2> {
+        #[pin]
+        first: Fut1,
+        #[pin]
+        second: Option<Fut2>,
+        f: Option<F>,
+    }
+}
+
+impl<Fut1, F, Fut2> Then<Fut1, F, Fut2> {
+    pub(crate) fn new(future: Fut1, f: F) -> Self {
+        Self {
+            first: future,
+            second: None,
+            f: Some(f),
+        }
+    }
+}
+
+impl<Fut1, F, Fut2> CompletionFuture for Then<Fut1, F, Fut2>
+where
+    Fut1: CompletionFuture,
+    F: FnOnce(Fut1::Output) -> Fut2,
+    Fut2: CompletionFuture,
+{
+    type Output = Fut2::Output;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if this.second.is_none() {
+            let output = ready!(this.first.poll(cx));
+            let f = this.f.take().expect("`Then` polled after completion");
+            this.second.set(Some(f(output)));
+        }
+
+        this.second.as_pin_mut().unwrap().poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if let Some(second) = this.second.as_pin_mut() {
+            second.poll_cancel(cx)
+        } else if this.f.is_some() {
+            this.first.poll_cancel(cx)
+        } else {
+            // The first future completed, and then `f` panicked.
+            Poll::Ready(())
+        }
+    }
+}
+
+impl<Fut1, F, Fut2> Future for Then<Fut1, F, Fut2>
+where
+    Fut1: CompletionFuture + Future<Output = <Fut1 as CompletionFuture>::Output>,
+    F: FnOnce(<Fut1 as CompletionFuture>::Output) -> Fut2,
+    Fut2: CompletionFuture + Future<Output = <Fut2 as CompletionFuture>::Output>,
+{
+    type Output = <Fut2 as CompletionFuture>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::{ready, Ready};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use crate::test_utils::{poll_cancel_once, poll_once, Forever, Polls};
+    use crate::{CheckedCompletion, CompletionFutureExt};
+
+    #[test]
+    fn then_cancel_first() {
+        let first = Polls::new();
+        let second = Polls::new();
+        let fut = Forever::<()>::new(&first, 1).then(|()| Forever::<()>::new(&second, 0));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(first.cancelled(), 1);
+        assert_eq!(second.polled(), 0);
+        assert_eq!(second.cancel_polls(), 0);
+    }
+
+    #[test]
+    fn then_cancel_second() {
+        let second = Polls::new();
+        let fut = ready(()).then(|()| Forever::<()>::new(&second, 1));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert_eq!(second.polled(), 1);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(second.cancel_polls(), 2);
+        assert_eq!(second.cancelled(), 1);
+    }
+
+    #[test]
+    fn then_cancel_after_panic() {
+        let fut = CheckedCompletion::new(ready(())).then(|()| -> Ready<()> { panic!() });
+        futures_lite::pin!(fut);
+        assert!(catch_unwind(AssertUnwindSafe(|| poll_once(fut.as_mut()))).is_err());
+        // The first future has completed, so it must not be cancelled.
+        assert!(poll_cancel_once(fut.as_mut()));
+    }
+}
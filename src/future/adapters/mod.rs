@@ -0,0 +1,14 @@
+mod map;
+pub use map::*;
+
+mod result;
+pub use result::*;
+
+mod inspect;
+pub use inspect::*;
+
+mod flatten;
+pub use flatten::*;
+
+mod fuse;
+pub use fuse::*;
@@ -0,0 +1,179 @@
+//! `AndThen` and `MapErr`.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::and_then`](crate::CompletionFutureExt::and_then).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct AndThen<Fut1, F, Fut2> {
+        #[pin]
+        first: Fut1,
+        #[pin]
+        second: Option<Fut2>,
+        f: Option<F>,
+    }
+}
+
+impl<Fut1, F, Fut2> AndThen<Fut1, F, Fut2> {
+    pub(crate) fn new(future: Fut1, f: F) -> Self {
+        Self {
+            first: future,
+            second: None,
+            f: Some(f),
+        }
+    }
+}
+
+impl<Fut1, F, Fut2, T, U, E> CompletionFuture for AndThen<Fut1, F, Fut2>
+where
+    Fut1: CompletionFuture<Output = Result<T, E>>,
+    F: FnOnce(T) -> Fut2,
+    Fut2: CompletionFuture<Output = Result<U, E>>,
+{
+    type Output = Result<U, E>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if this.second.is_none() {
+            let output = ready!(this.first.poll(cx));
+            let f = this.f.take().expect("`AndThen` polled after completion");
+            match output {
+                Ok(value) => this.second.set(Some(f(value))),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        this.second.as_pin_mut().unwrap().poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if let Some(second) = this.second.as_pin_mut() {
+            second.poll_cancel(cx)
+        } else if this.f.is_some() {
+            this.first.poll_cancel(cx)
+        } else {
+            // The first future completed, and then `f` panicked or it failed.
+            Poll::Ready(())
+        }
+    }
+}
+
+impl<Fut1, F, Fut2, T, U, E> Future for AndThen<Fut1, F, Fut2>
+where
+    Fut1: CompletionFuture<Output = Result<T, E>> + Future<Output = Result<T, E>>,
+    F: FnOnce(T) -> Fut2,
+    Fut2: CompletionFuture<Output = Result<U, E>> + Future<Output = Result<U, E>>,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+pin_project! {
+    /// Future for [`CompletionFutureExt::map_err`](crate::CompletionFutureExt::map_err).
+    #[derive(Debug, Clone)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct MapErr<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F> MapErr<Fut, F> {
+    pub(crate) fn new(future: Fut, f: F) -> Self {
+        Self { future, f: Some(f) }
+    }
+}
+
+impl<Fut, F, T, E1, E2> CompletionFuture for MapErr<Fut, F>
+where
+    Fut: CompletionFuture<Output = Result<T, E1>>,
+    F: FnOnce(E1) -> E2,
+{
+    type Output = Result<T, E2>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("`MapErr` polled after completion");
+        Poll::Ready(output.map_err(f))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.project();
+        if this.f.is_none() {
+            // The future completed, and then `f` panicked.
+            return Poll::Ready(());
+        }
+        this.future.poll_cancel(cx)
+    }
+}
+
+impl<Fut, F, T, E1, E2> Future for MapErr<Fut, F>
+where
+    Fut: CompletionFuture<Output = Result<T, E1>> + Future<Output = Result<T, E1>>,
+    F: FnOnce(E1) -> E2,
+{
+    type Output = Result<T, E2>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::{ready, Ready};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use crate::test_utils::{poll_cancel_once, poll_once, Forever, Polls};
+    use crate::{CheckedCompletion, CompletionFutureExt};
+
+    #[test]
+    fn and_then_cancel_first() {
+        let first = Polls::new();
+        let second = Polls::new();
+        let fut = Forever::<Result<(), ()>>::new(&first, 1)
+            .and_then(|()| Forever::<Result<(), ()>>::new(&second, 0));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(first.cancelled(), 1);
+        assert_eq!(second.polled(), 0);
+        assert_eq!(second.cancel_polls(), 0);
+    }
+
+    #[test]
+    fn and_then_cancel_second() {
+        let second = Polls::new();
+        let fut = ready(Ok::<(), ()>(())).and_then(|()| Forever::<Result<(), ()>>::new(&second, 1));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert_eq!(second.polled(), 1);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert_eq!(second.cancel_polls(), 2);
+        assert_eq!(second.cancelled(), 1);
+    }
+
+    #[test]
+    fn and_then_cancel_after_panic() {
+        let fut = CheckedCompletion::new(ready(Ok::<_, ()>(())))
+            .and_then(|()| -> Ready<Result<(), ()>> { panic!() });
+        futures_lite::pin!(fut);
+        assert!(catch_unwind(AssertUnwindSafe(|| poll_once(fut.as_mut()))).is_err());
+        // The first future has completed, so it must not be cancelled.
+        assert!(poll_cancel_once(fut.as_mut()));
+    }
+}
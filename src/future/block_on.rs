@@ -49,7 +49,8 @@ pub fn block_on<F: CompletionFuture>(mut future: F) -> F::Output {
     })
 }
 
-fn wake_pair() -> (Parker, Waker) {
+/// Create a parker for the current thread and a waker that unparks it.
+pub(crate) fn wake_pair() -> (Parker, Waker) {
     let inner = Arc::new(WakerInner {
         woken: AtomicBool::new(false),
         sleeping_thread: thread::current(),
@@ -63,13 +64,14 @@ fn wake_pair() -> (Parker, Waker) {
     )
 }
 
-struct Parker {
+/// Blocks the thread it was created on until its associated waker is woken.
+pub(crate) struct Parker {
     inner: Arc<WakerInner>,
     not_send_or_sync: PhantomData<*mut ()>,
 }
 
 impl Parker {
-    fn park(&self) {
+    pub(crate) fn park(&self) {
         while !self.inner.woken.swap(false, Ordering::SeqCst) {
             thread::park();
         }
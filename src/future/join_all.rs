@@ -0,0 +1,661 @@
+use core::array;
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use completion_core::{CompletionFuture, FusedCompletionFuture};
+use pin_project_lite::pin_project;
+
+use super::ready_queue::ReadyQueue;
+use super::try_zip::{try_zip, IsResult, TryZip, TryZippable};
+use super::zip::{zip, Panicked, Zip, Zippable};
+
+/// Joins an iterator of futures, waiting for them all to complete.
+///
+/// The outputs are returned in the same order as the futures. Only the futures that have been
+/// woken are polled again. [`zip`] can also be used directly on arrays of futures, which avoids
+/// allocating.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, completion_async_move};
+///
+/// # future::block_on(completion_async_move! {
+/// let futures = (0..5).map(|i| completion_async_move!(i * 2));
+/// assert_eq!(future::join_all(futures).await, [0, 2, 4, 6, 8]);
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: CompletionFuture,
+{
+    JoinAll {
+        inner: zip(futures.into_iter().collect::<Box<[_]>>().into()),
+    }
+}
+
+pin_project! {
+    /// Future for [`join_all`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct JoinAll<F: CompletionFuture> {
+        #[pin]
+        inner: Zip<Pin<Box<[F]>>>,
+    }
+}
+
+impl<F: CompletionFuture> Debug for JoinAll<F>
+where
+    Zip<Pin<Box<[F]>>>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinAll")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<F: CompletionFuture> CompletionFuture for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().inner.poll_cancel(cx)
+    }
+}
+
+impl<F: CompletionFuture> FusedCompletionFuture for JoinAll<F> {
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+/// Joins an iterator of fallible futures, waiting for them all to succeed or one of them to fail.
+///
+/// As soon as one of the futures fails, all the others are cancelled, and the error is returned
+/// once their cancellation has finished. [`try_zip`] can also be used directly on arrays of
+/// futures.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, completion_async_move};
+///
+/// # future::block_on(completion_async_move! {
+/// let futures = (0..5).map(|i| completion_async_move!(if i < 3 { Ok(i) } else { Err(i) }));
+/// assert_eq!(future::try_join_all(futures).await, Err(3));
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn try_join_all<I>(futures: I) -> TryJoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: CompletionFuture,
+    <I::Item as CompletionFuture>::Output: IsResult,
+{
+    TryJoinAll {
+        inner: try_zip(futures.into_iter().collect::<Box<[_]>>().into()),
+    }
+}
+
+pin_project! {
+    /// Future for [`try_join_all`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct TryJoinAll<F>
+    where
+        F: CompletionFuture,
+        F::Output: IsResult,
+    {
+        #[pin]
+        inner: TryZip<Pin<Box<[F]>>>,
+    }
+}
+
+impl<F> Debug for TryJoinAll<F>
+where
+    F: CompletionFuture,
+    F::Output: IsResult,
+    TryZip<Pin<Box<[F]>>>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryJoinAll")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<F> CompletionFuture for TryJoinAll<F>
+where
+    F: CompletionFuture,
+    F::Output: IsResult,
+{
+    type Output = Result<Vec<<F::Output as IsResult>::Ok>, <F::Output as IsResult>::Error>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().inner.poll_cancel(cx)
+    }
+}
+
+// The implementations of `Zippable` for arrays and boxed slices share these functions, which
+// operate on slices of futures. Only the futures at `indices` are polled, with the waker at their
+// index in `wakers` if it is given, and `on_ready` is called with the index of each future that
+// completes.
+
+fn index<F>(futures: Pin<&mut [F]>, i: usize) -> Pin<&mut F> {
+    unsafe { futures.map_unchecked_mut(|futures| &mut futures[i]) }
+}
+
+fn poll_future<F: CompletionFuture>(
+    future: Pin<&mut F>,
+    waker: Option<&Waker>,
+    cx: &mut Context<'_>,
+) -> std::thread::Result<Poll<F::Output>> {
+    catch_unwind(AssertUnwindSafe(|| unsafe {
+        match waker {
+            Some(waker) => future.poll(&mut Context::from_waker(waker)),
+            None => future.poll(cx),
+        }
+    }))
+}
+
+fn cancel_future<F: CompletionFuture>(
+    future: Pin<&mut F>,
+    waker: Option<&Waker>,
+    cx: &mut Context<'_>,
+) -> std::thread::Result<Poll<()>> {
+    catch_unwind(AssertUnwindSafe(|| unsafe {
+        match waker {
+            Some(waker) => future.poll_cancel(&mut Context::from_waker(waker)),
+            None => future.poll_cancel(cx),
+        }
+    }))
+}
+
+fn poll_all<F: CompletionFuture>(
+    mut futures: Pin<&mut [F]>,
+    outputs: &mut [Option<F::Output>],
+    indices: impl IntoIterator<Item = usize>,
+    wakers: Option<&[Waker]>,
+    cx: &mut Context<'_>,
+    mut on_ready: impl FnMut(usize),
+) -> Result<(), Panicked> {
+    for i in indices {
+        if outputs[i].is_none() {
+            let future = index(futures.as_mut(), i);
+            let poll = poll_future(future, wakers.map(|wakers| &wakers[i]), cx)
+                .map_err(|payload| Panicked { i, payload })?;
+            if let Poll::Ready(val) = poll {
+                outputs[i] = Some(val);
+                on_ready(i);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn poll_cancel<F: CompletionFuture>(
+    mut futures: Pin<&mut [F]>,
+    cancelling: &mut [bool],
+    indices: impl IntoIterator<Item = usize>,
+    wakers: Option<&[Waker]>,
+    cx: &mut Context<'_>,
+    mut on_ready: impl FnMut(usize),
+) -> Result<(), Panicked> {
+    for i in indices {
+        if !cancelling[i] {
+            let future = index(futures.as_mut(), i);
+            let poll = cancel_future(future, wakers.map(|wakers| &wakers[i]), cx)
+                .map_err(|payload| Panicked { i, payload })?;
+            if poll.is_ready() {
+                cancelling[i] = true;
+                on_ready(i);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn poll_panicked<F: CompletionFuture>(
+    mut futures: Pin<&mut [F]>,
+    cancelling: &mut [bool],
+    indices: impl IntoIterator<Item = usize>,
+    wakers: Option<&[Waker]>,
+    cx: &mut Context<'_>,
+    mut on_ready: impl FnMut(usize),
+) {
+    for i in indices {
+        if !cancelling[i] {
+            let future = index(futures.as_mut(), i);
+            let ready = cancel_future(future, wakers.map(|wakers| &wakers[i]), cx)
+                .map_or(true, |poll| poll.is_ready());
+            if ready {
+                cancelling[i] = true;
+                on_ready(i);
+            }
+        }
+    }
+}
+
+fn take_error<T: IsResult>(
+    outputs: &mut [Option<T>],
+    indices: impl IntoIterator<Item = usize>,
+) -> Option<(usize, T::Error)> {
+    let i = indices
+        .into_iter()
+        .find(|&i| matches!(&outputs[i], Some(output) if output.is_err()))?;
+    outputs[i]
+        .take()
+        .unwrap()
+        .into_result()
+        .err()
+        .map(|e| (i, e))
+}
+
+fn array_slice<F, const N: usize>(array: Pin<&mut [F; N]>) -> Pin<&mut [F]> {
+    unsafe { array.map_unchecked_mut(|array| &mut array[..]) }
+}
+
+impl<F: CompletionFuture, const N: usize> Zippable for [F; N] {
+    type Running = [Option<F::Output>; N];
+    type Cancelling = [bool; N];
+    type Outputs = [F::Output; N];
+
+    fn make_running(&self) -> Self::Running {
+        array::from_fn(|_| None)
+    }
+
+    fn make_cancelling(running: &mut Self::Running) -> Self::Cancelling {
+        array::from_fn(|i| running[i].take().is_some())
+    }
+
+    fn set_cancelled(cancelling: &mut Self::Cancelling, i: usize) {
+        cancelling[i] = true;
+    }
+
+    fn poll_all(
+        self: Pin<&mut Self>,
+        running: &mut Self::Running,
+        cx: &mut Context<'_>,
+    ) -> Result<Poll<Self::Outputs>, Panicked> {
+        poll_all(array_slice(self), running, 0..N, None, cx, |_| {})?;
+        Ok(if running.iter().all(Option::is_some) {
+            Poll::Ready(array::from_fn(|i| running[i].take().unwrap()))
+        } else {
+            Poll::Pending
+        })
+    }
+
+    fn poll_cancel(
+        self: Pin<&mut Self>,
+        cancelling: &mut Self::Cancelling,
+        cx: &mut Context<'_>,
+    ) -> Result<Poll<()>, Panicked> {
+        poll_cancel(array_slice(self), cancelling, 0..N, None, cx, |_| {})?;
+        Ok(if cancelling.iter().all(|&done| done) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        })
+    }
+
+    fn poll_panicked(
+        self: Pin<&mut Self>,
+        cancelling: &mut Self::Cancelling,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        poll_panicked(array_slice(self), cancelling, 0..N, None, cx, |_| {});
+        if cancelling.iter().all(|&done| done) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<F: CompletionFuture, const N: usize> TryZippable for [F; N]
+where
+    F::Output: IsResult,
+{
+    type Ok = [<F::Output as IsResult>::Ok; N];
+    type Error = <F::Output as IsResult>::Error;
+
+    fn take_error(running: &mut Self::Running) -> Option<(usize, Self::Error)> {
+        take_error(running, 0..N)
+    }
+
+    fn into_ok(outputs: Self::Outputs) -> Result<Self::Ok, Self::Error> {
+        let mut error = None;
+        let oks = outputs.map(|output| match output.into_result() {
+            Ok(ok) => Some(ok),
+            Err(e) => {
+                error.get_or_insert(e);
+                None
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(oks.map(Option::unwrap)),
+        }
+    }
+}
+
+/// The wakers given to the futures in a boxed slice, which record which of them need polling.
+#[derive(Debug, Default)]
+pub struct SliceWakers {
+    queue: ReadyQueue,
+    wakers: Vec<Waker>,
+}
+
+impl SliceWakers {
+    /// Register the task's waker, and take the indices of the futures that have been woken.
+    fn take(&self, cx: &Context<'_>) -> Vec<usize> {
+        self.queue.register(cx.waker());
+        self.queue.take()
+    }
+
+    /// Make sure the given futures are polled again, after a panic interrupted polling them.
+    fn restore(&self, indices: Vec<usize>) {
+        for i in indices {
+            self.queue.push(i);
+        }
+    }
+}
+
+/// The running state of a boxed slice of futures.
+#[derive(Debug)]
+pub struct SliceRunning<T> {
+    outputs: Vec<Option<T>>,
+    /// The number of futures that haven't completed.
+    remaining: usize,
+    /// The futures that completed in the last call to `poll_all`.
+    completed: Vec<usize>,
+    wakers: SliceWakers,
+}
+
+/// The cancelling state of a boxed slice of futures.
+#[derive(Debug)]
+pub struct SliceCancelling {
+    cancelled: Vec<bool>,
+    /// The number of futures that haven't been cancelled.
+    remaining: usize,
+    wakers: SliceWakers,
+}
+
+impl<F: CompletionFuture> Zippable for Pin<Box<[F]>> {
+    type Running = SliceRunning<F::Output>;
+    type Cancelling = SliceCancelling;
+    type Outputs = Vec<F::Output>;
+
+    fn make_running(&self) -> Self::Running {
+        let queue = ReadyQueue::default();
+        let wakers = (0..self.len())
+            .map(|i| {
+                queue.push(i);
+                queue.waker(i)
+            })
+            .collect();
+        SliceRunning {
+            outputs: (0..self.len()).map(|_| None).collect(),
+            remaining: self.len(),
+            completed: Vec::new(),
+            wakers: SliceWakers { queue, wakers },
+        }
+    }
+
+    fn make_cancelling(running: &mut Self::Running) -> Self::Cancelling {
+        let wakers = mem::take(&mut running.wakers);
+        let cancelled: Vec<bool> = running
+            .outputs
+            .iter_mut()
+            .map(|output| output.take().is_some())
+            .collect();
+
+        // Every future that hasn't completed must be polled at least once to cancel it.
+        let mut remaining = 0;
+        for (i, _) in cancelled.iter().enumerate().filter(|(_, &done)| !done) {
+            wakers.queue.push(i);
+            remaining += 1;
+        }
+        SliceCancelling {
+            cancelled,
+            remaining,
+            wakers,
+        }
+    }
+
+    fn set_cancelled(cancelling: &mut Self::Cancelling, i: usize) {
+        if !cancelling.cancelled[i] {
+            cancelling.cancelled[i] = true;
+            cancelling.remaining -= 1;
+        }
+    }
+
+    fn poll_all(
+        self: Pin<&mut Self>,
+        running: &mut Self::Running,
+        cx: &mut Context<'_>,
+    ) -> Result<Poll<Self::Outputs>, Panicked> {
+        let SliceRunning {
+            outputs,
+            remaining,
+            completed,
+            wakers,
+        } = running;
+
+        completed.clear();
+        let indices = wakers.take(cx);
+        let futures = self.get_mut().as_mut();
+        poll_all(futures, outputs, indices, Some(&wakers.wakers), cx, |i| {
+            completed.push(i);
+            *remaining -= 1;
+        })?;
+
+        Ok(if *remaining == 0 {
+            Poll::Ready(outputs.iter_mut().map(|o| o.take().unwrap()).collect())
+        } else {
+            Poll::Pending
+        })
+    }
+
+    fn poll_cancel(
+        self: Pin<&mut Self>,
+        cancelling: &mut Self::Cancelling,
+        cx: &mut Context<'_>,
+    ) -> Result<Poll<()>, Panicked> {
+        let SliceCancelling {
+            cancelled,
+            remaining,
+            wakers,
+        } = cancelling;
+
+        let indices = wakers.take(cx);
+        let futures = self.get_mut().as_mut();
+        let res = poll_cancel(
+            futures,
+            cancelled,
+            indices.iter().copied(),
+            Some(&wakers.wakers),
+            cx,
+            |_| *remaining -= 1,
+        );
+        if res.is_err() {
+            wakers.restore(indices);
+        }
+        res?;
+
+        Ok(if *remaining == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        })
+    }
+
+    fn poll_panicked(
+        self: Pin<&mut Self>,
+        cancelling: &mut Self::Cancelling,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let SliceCancelling {
+            cancelled,
+            remaining,
+            wakers,
+        } = cancelling;
+
+        let indices = wakers.take(cx);
+        let futures = self.get_mut().as_mut();
+        poll_panicked(
+            futures,
+            cancelled,
+            indices,
+            Some(&wakers.wakers),
+            cx,
+            |_| *remaining -= 1,
+        );
+
+        if *remaining == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<F: CompletionFuture> TryZippable for Pin<Box<[F]>>
+where
+    F::Output: IsResult,
+{
+    type Ok = Vec<<F::Output as IsResult>::Ok>;
+    type Error = <F::Output as IsResult>::Error;
+
+    fn take_error(running: &mut Self::Running) -> Option<(usize, Self::Error)> {
+        let completed = mem::take(&mut running.completed);
+        take_error(&mut running.outputs, completed)
+    }
+
+    fn into_ok(outputs: Self::Outputs) -> Result<Self::Ok, Self::Error> {
+        outputs.into_iter().map(IsResult::into_result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::ready;
+
+    use crate::future::{block_on, CompletionFutureExt, FutureExt};
+    use crate::test_utils::{noop_waker, poll_cancel_once, poll_once, Forever, Polls, Yield};
+
+    #[test]
+    fn join() {
+        let futures = (0..10).map(|i| Yield::new(10 - i, ready(Box::new(i))));
+        assert_eq!(
+            block_on(join_all(futures)),
+            (0..10).map(Box::new).collect::<Vec<_>>()
+        );
+
+        assert_eq!(block_on(join_all(Vec::<std::future::Ready<()>>::new())), []);
+    }
+
+    #[test]
+    fn array() {
+        let futures = [0, 1, 2].map(|i| Yield::new(i, ready(Box::new(i))));
+        assert_eq!(block_on(zip(futures)), [0, 1, 2].map(Box::new));
+
+        let futures = [1, 2].map(|i| ready(Ok::<_, ()>(i)));
+        assert_eq!(block_on(try_zip(futures)), Ok([1, 2]));
+    }
+
+    #[test]
+    fn terminated() {
+        let fut = join_all(vec![Yield::once(ready(1)), Yield::new(0, ready(2))]);
+        futures_lite::pin!(fut);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), Some(vec![1, 2]));
+        assert!(fut.is_terminated());
+
+        let fut = join_all(vec![Yield::new(2, ready(1)), Yield::new(0, ready(2))]);
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(!fut.is_terminated());
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert!(fut.is_terminated());
+    }
+
+    #[test]
+    fn polls_woken() {
+        let polls = Polls::new();
+        let futures = (0..10).map(|i| {
+            if i == 0 {
+                Yield::new(5, std::future::pending::<()>()).boxed_local()
+            } else {
+                Forever::new(&polls, 0).boxed_local()
+            }
+        });
+        let join = join_all(futures);
+        futures_lite::pin!(join);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..5 {
+            assert!(unsafe { join.as_mut().poll(&mut cx) }.is_pending());
+        }
+        assert_eq!(polls.polled(), 9);
+        assert!(unsafe { join.as_mut().poll_cancel(&mut cx) }.is_ready());
+    }
+
+    #[test]
+    fn try_join() {
+        let futures = (0..5).map(|i| Yield::new(i, ready(Ok::<_, ()>(Box::new(i)))));
+        assert_eq!(
+            block_on(try_join_all(futures)),
+            Ok((0..5).map(Box::new).collect())
+        );
+
+        let polls = Polls::new();
+        let futures = (0..5).map(|i| {
+            if i == 2 {
+                Yield::new(3, ready(Err::<(), _>(Box::new(7)))).boxed_local()
+            } else {
+                Forever::new(&polls, i).boxed_local()
+            }
+        });
+        assert_eq!(block_on(try_join_all(futures)), Err(Box::new(7)));
+        assert_eq!(polls.cancelled(), 4);
+    }
+
+    #[test]
+    fn panics() {
+        let polls = Polls::new();
+        let futures = (0..3).map(|i| {
+            if i == 1 {
+                Yield::once(async { std::panic::panic_any(5) }.into_completion()).boxed_local()
+            } else {
+                Forever::new(&polls, i).boxed_local()
+            }
+        });
+        let payload = block_on(AssertUnwindSafe(join_all(futures)).catch_unwind()).unwrap_err();
+        assert_eq!(*payload.downcast::<i32>().unwrap(), 5);
+        assert_eq!(polls.cancelled(), 2);
+    }
+}
@@ -9,25 +9,61 @@ use core::pin::Pin;
 use core::task::{Context, Poll};
 #[cfg(feature = "std")]
 use std::panic::{catch_unwind, AssertUnwindSafe, UnwindSafe};
+#[cfg(feature = "std")]
+use std::time::Duration;
 
 #[cfg(feature = "std")]
 use pin_project_lite::pin_project;
 
 #[doc(no_inline)]
-pub use completion_core::CompletionFuture;
+pub use completion_core::{CompletionFuture, FusedCompletionFuture};
 
 use super::{Adapter, MustComplete};
+#[cfg(feature = "std")]
+use crate::sync::{CancellationToken, WithCancellation};
+#[cfg(feature = "std")]
+use crate::time::Timeout;
+
+mod adapters;
+pub use adapters::*;
 
 #[cfg(feature = "std")]
 mod block_on;
 #[cfg(feature = "std")]
 pub use block_on::block_on;
+#[cfg(feature = "std")]
+pub(crate) use block_on::{wake_pair, Parker};
+
+#[cfg(feature = "std")]
+mod join_all;
+#[cfg(feature = "std")]
+pub use join_all::{join_all, try_join_all, JoinAll, TryJoinAll};
+
+#[cfg(feature = "std")]
+mod race;
+#[cfg(feature = "std")]
+pub use race::{race, select, Either, Race, Select};
+
+#[cfg(feature = "std")]
+mod ready_queue;
+#[cfg(feature = "std")]
+pub(crate) use ready_queue::ReadyQueue;
+
+#[cfg(feature = "std")]
+mod scope;
+#[cfg(feature = "std")]
+pub use scope::{scope, Scope, ScopedJoinHandle, Spawner};
 
 #[cfg(feature = "std")]
 mod zip;
 #[cfg(feature = "std")]
 pub use zip::{zip, Zip};
 
+#[cfg(feature = "std")]
+mod try_zip;
+#[cfg(feature = "std")]
+pub use try_zip::{try_zip, TryZip};
+
 /// Extension trait for [`CompletionFuture`].
 pub trait CompletionFutureExt: CompletionFuture {
     /// A convenience for calling [`CompletionFuture::poll`] on [`Unpin`] futures.
@@ -63,6 +99,163 @@ pub trait CompletionFutureExt: CompletionFuture {
         MustComplete { inner: self }
     }
 
+    /// Map this future's output with a closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(5).map(|x| x * 2);
+    /// assert_eq!(future.await, 10);
+    /// # });
+    /// ```
+    fn map<T, F: FnOnce(Self::Output) -> T>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+    {
+        Map::new(self, f)
+    }
+
+    /// Run a second future using this future's output.
+    ///
+    /// Cancelling the returned future cancels whichever of the two futures is currently running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async, completion_async_move};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(5).then(|x| completion_async_move!(x + 1));
+    /// assert_eq!(future.await, 6);
+    /// # });
+    /// ```
+    fn then<F, Fut>(self, f: F) -> Then<Self, F, Fut>
+    where
+        F: FnOnce(Self::Output) -> Fut,
+        Fut: CompletionFuture,
+        Self: Sized,
+    {
+        Then::new(self, f)
+    }
+
+    /// Run a second fallible future if this future succeeds.
+    ///
+    /// If this future outputs an error, the closure is not called and the error is returned.
+    /// Cancelling the returned future cancels whichever of the two futures is currently running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async, completion_async_move};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(Ok::<_, ()>(5)).and_then(|x| completion_async_move!(Ok(x + 1)));
+    /// assert_eq!(future.await, Ok(6));
+    ///
+    /// let future = completion_async!(Err::<i32, _>(())).and_then(|x| completion_async_move!(Ok(x + 1)));
+    /// assert_eq!(future.await, Err(()));
+    /// # });
+    /// ```
+    fn and_then<T, U, E, F, Fut>(self, f: F) -> AndThen<Self, F, Fut>
+    where
+        Self: CompletionFuture<Output = Result<T, E>> + Sized,
+        F: FnOnce(T) -> Fut,
+        Fut: CompletionFuture<Output = Result<U, E>>,
+    {
+        AndThen::new(self, f)
+    }
+
+    /// Map the error of this fallible future with a closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(Err::<(), _>(5)).map_err(|e| e.to_string());
+    /// assert_eq!(future.await, Err("5".to_owned()));
+    /// # });
+    /// ```
+    fn map_err<T, E1, E2, F>(self, f: F) -> MapErr<Self, F>
+    where
+        Self: CompletionFuture<Output = Result<T, E1>> + Sized,
+        F: FnOnce(E1) -> E2,
+    {
+        MapErr::new(self, f)
+    }
+
+    /// Call a closure with a reference to this future's output before passing it on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(5).inspect(|x| println!("Output: {}", x));
+    /// assert_eq!(future.await, 5);
+    /// # });
+    /// ```
+    fn inspect<F: FnOnce(&Self::Output)>(self, f: F) -> Inspect<Self, F>
+    where
+        Self: Sized,
+    {
+        Inspect::new(self, f)
+    }
+
+    /// Flatten a future that outputs another future.
+    ///
+    /// Cancelling the returned future cancels whichever of the two futures is currently running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(completion_async!(5)).flatten();
+    /// assert_eq!(future.await, 5);
+    /// # });
+    /// ```
+    fn flatten(self) -> Flatten<Self>
+    where
+        Self::Output: CompletionFuture,
+        Self: Sized,
+    {
+        Flatten::new(self)
+    }
+
+    /// Fuse the future, so that it tracks whether it has completed.
+    ///
+    /// The returned future implements [`FusedCompletionFuture`], and once it has completed or
+    /// been cancelled polling it will always return [`Poll::Pending`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, FusedCompletionFuture, completion_async};
+    /// use futures_lite::pin;
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(5).fuse();
+    /// pin!(future);
+    ///
+    /// assert!(!future.is_terminated());
+    /// assert_eq!((&mut future).await, 5);
+    /// assert!(future.is_terminated());
+    /// # });
+    /// ```
+    fn fuse(self) -> Fuse<Self>
+    where
+        Self: Sized,
+    {
+        Fuse::new(self)
+    }
+
     /// Catch panics in the future.
     ///
     /// # Examples
@@ -84,6 +277,68 @@ pub trait CompletionFutureExt: CompletionFuture {
         CatchUnwind { inner: self }
     }
 
+    /// Run the future until the token is cancelled.
+    ///
+    /// Once the token is cancelled, the future is cancelled with
+    /// [`poll_cancel`](CompletionFuture::poll_cancel) and the returned future resolves to
+    /// [`Err`]`(`[`Cancelled`](crate::sync::Cancelled)`)` once that cancellation has finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::{CompletionFutureExt, completion_async};
+    /// use completion::sync::{CancellationToken, Cancelled};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let token = CancellationToken::new();
+    ///
+    /// let future = completion_async!(5).with_cancellation(token.clone());
+    /// assert_eq!(future.await, Ok(5));
+    ///
+    /// token.cancel();
+    /// let future = completion_async!(5).with_cancellation(token);
+    /// assert_eq!(future.await, Err(Cancelled));
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    fn with_cancellation(self, token: CancellationToken) -> WithCancellation<Self>
+    where
+        Self: Sized,
+    {
+        WithCancellation::new(self, token)
+    }
+
+    /// Run the future until a duration has elapsed.
+    ///
+    /// If the future hasn't completed by the time the duration has elapsed, it is cancelled with
+    /// [`poll_cancel`](CompletionFuture::poll_cancel) and the returned future resolves to
+    /// [`Err`]`(`[`Elapsed`](crate::time::Elapsed)`)` once that cancellation has finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use completion::{CompletionFutureExt, FutureExt, completion_async};
+    ///
+    /// # completion::future::block_on(completion_async! {
+    /// let future = completion_async!(5).timeout(Duration::from_secs(5));
+    /// assert_eq!(future.await, Ok(5));
+    ///
+    /// let future = std::future::pending::<()>().into_completion();
+    /// assert!(future.timeout(Duration::from_millis(10)).await.is_err());
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    fn timeout(self, duration: Duration) -> Timeout<Self>
+    where
+        Self: Sized,
+    {
+        crate::time::timeout(duration, self)
+    }
+
     /// Box the future, erasing its type.
     ///
     /// # Examples
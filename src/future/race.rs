@@ -0,0 +1,392 @@
+use core::any::Any;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+use completion_core::CompletionFuture;
+use pin_project_lite::pin_project;
+
+use super::zip::{Panicked, Zippable};
+
+/// Races futures, waiting for the first one to complete.
+///
+/// This takes any tuple of two or more futures with the same output type, and outputs the output
+/// of the first future to complete. All the other futures are then cancelled, and the output is
+/// only returned once their cancellation has finished. If several futures are ready at once, the
+/// earliest one in the tuple wins.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, completion_async};
+///
+/// # future::block_on(completion_async! {
+/// let output = future::race((
+///     completion_async!(5),
+///     std::future::pending(),
+/// ))
+/// .await;
+/// assert_eq!(output, 5);
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn race<T: Raceable>(futures: T) -> Race<T> {
+    Race {
+        futures,
+        state: State::Running,
+        _correct_debug_bounds: PhantomData,
+    }
+}
+
+pin_project! {
+    /// Future for [`race`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Race<T: Raceable> {
+        #[pin]
+        futures: T,
+        state: State<T>,
+        _correct_debug_bounds: PhantomData<(T::Output, T::Cancelling)>,
+    }
+}
+
+#[derive(Debug)]
+enum State<T: Raceable> {
+    Running,
+    /// Cancelling the futures, either because one of them won, in which case this holds its
+    /// output, or because the race itself is being cancelled.
+    Cancelling(Option<T::Output>, T::Cancelling),
+    Panicked(Box<dyn Any + Send>, T::Cancelling),
+    Dummy,
+}
+
+/// Create the cancelling state in which every future except the `i`th needs to be cancelled.
+fn cancel_all_except<T: Zippable>(futures: &T, i: Option<usize>) -> T::Cancelling {
+    let mut cancelling = T::make_cancelling(&mut futures.make_running());
+    if let Some(i) = i {
+        T::set_cancelled(&mut cancelling, i);
+    }
+    cancelling
+}
+
+impl<T: Raceable> Race<T> {
+    fn poll_panicked(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        let this = self.project();
+
+        let cancelling = match this.state {
+            State::Panicked(_, cancelling) => cancelling,
+            State::Dummy => panic!("Called `poll` or `poll_cancel` after panicking on `Race`"),
+            _ => unreachable!(),
+        };
+        if this.futures.poll_panicked(cancelling, cx).is_ready() {
+            let State::Panicked(payload, _) = std::mem::replace(this.state, State::Dummy) else {
+                unreachable!()
+            };
+            resume_unwind(payload)
+        }
+    }
+
+    /// Drive the cancellation of the futures, returning the winner's output if there is one.
+    fn poll_cancelling(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T::Output>> {
+        let this = self.project();
+
+        let State::Cancelling(_, cancelling) = this.state else {
+            unreachable!()
+        };
+        match this.futures.poll_cancel(cancelling, cx) {
+            Ok(Poll::Ready(())) => match std::mem::replace(this.state, State::Dummy) {
+                State::Cancelling(output, _) => Poll::Ready(output),
+                _ => unreachable!(),
+            },
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(Panicked { i, payload }) => {
+                let State::Cancelling(_, mut cancelling) =
+                    std::mem::replace(this.state, State::Dummy)
+                else {
+                    unreachable!()
+                };
+                T::set_cancelled(&mut cancelling, i);
+                *this.state = State::Panicked(payload, cancelling);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T: Raceable> CompletionFuture for Race<T> {
+    type Output = T::Output;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+
+        if let State::Running = this.state {
+            match this.futures.as_mut().poll_race(cx) {
+                Ok(Poll::Ready((i, output))) => {
+                    *this.state =
+                        State::Cancelling(Some(output), cancel_all_except(&*this.futures, Some(i)));
+                }
+                Ok(Poll::Pending) => return Poll::Pending,
+                Err(Panicked { i, payload }) => {
+                    *this.state =
+                        State::Panicked(payload, cancel_all_except(&*this.futures, Some(i)));
+                }
+            }
+        }
+        if let State::Cancelling(output, _) = &self.state {
+            assert!(
+                output.is_some(),
+                "Called `poll` after `poll_cancel` on `Race`"
+            );
+            if let Poll::Ready(output) = self.as_mut().poll_cancelling(cx) {
+                return Poll::Ready(output.unwrap());
+            }
+        }
+        if let State::Panicked(..) | State::Dummy = self.state {
+            self.poll_panicked(cx);
+        }
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.as_mut().project();
+
+        if let State::Running = this.state {
+            *this.state = State::Cancelling(None, cancel_all_except(&*this.futures, None));
+        }
+        if let State::Cancelling(..) = self.state {
+            if self.as_mut().poll_cancelling(cx).is_ready() {
+                return Poll::Ready(());
+            }
+        }
+        if let State::Panicked(..) | State::Dummy = self.state {
+            self.poll_panicked(cx);
+        }
+        Poll::Pending
+    }
+}
+
+/// Selects between two futures, waiting for the first one to complete.
+///
+/// Unlike [`race`], the futures can have different output types; the output says which of the
+/// futures completed first. The other future is cancelled, and the output is only returned once
+/// its cancellation has finished. If both futures are ready at once, the first one wins.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, completion_async};
+/// use completion::future::Either;
+///
+/// # future::block_on(completion_async! {
+/// let output = future::select(
+///     std::future::pending::<i32>(),
+///     completion_async!("done"),
+/// )
+/// .await;
+/// assert_eq!(output, Either::Right("done"));
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn select<A: CompletionFuture, B: CompletionFuture>(a: A, b: B) -> Select<A, B> {
+    Select {
+        inner: race((
+            MapEither {
+                inner: a,
+                f: Either::Left,
+            },
+            MapEither {
+                inner: b,
+                f: Either::Right,
+            },
+        )),
+    }
+}
+
+type SelectOutput<A, B> = Either<<A as CompletionFuture>::Output, <B as CompletionFuture>::Output>;
+
+pin_project! {
+    /// Future for [`select`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Select<A: CompletionFuture, B: CompletionFuture> {
+        #[pin]
+        inner: Race<(
+            MapEither<A, fn(A::Output) -> SelectOutput<A, B>>,
+            MapEither<B, fn(B::Output) -> SelectOutput<A, B>>,
+        )>,
+    }
+}
+
+impl<A: CompletionFuture, B: CompletionFuture> CompletionFuture for Select<A, B> {
+    type Output = SelectOutput<A, B>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().inner.poll_cancel(cx)
+    }
+}
+
+pin_project! {
+    /// Wraps the output of one side of a [`Select`] in an [`Either`].
+    #[derive(Debug)]
+    struct MapEither<F, M> {
+        #[pin]
+        inner: F,
+        f: M,
+    }
+}
+
+impl<F: CompletionFuture, A, B> CompletionFuture for MapEither<F, fn(F::Output) -> Either<A, B>> {
+    type Output = Either<A, B>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        this.inner.poll(cx).map(*this.f)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().inner.poll_cancel(cx)
+    }
+}
+
+/// A value of one of two types, output by [`select`].
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Either<A, B> {
+    /// The output of the first future.
+    Left(A),
+    /// The output of the second future.
+    Right(B),
+}
+
+pub trait Raceable: Zippable {
+    type Output;
+
+    fn poll_race(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Result<Poll<(usize, Self::Output)>, Panicked>;
+}
+
+macro_rules! implement_raceable_for_tuples {
+    ($(($($param:ident),*),)*) => { $(#[allow(non_snake_case)] const _: () = {
+        impl<T, $($param: CompletionFuture<Output = T>,)*> Raceable for ($($param,)*) {
+            type Output = T;
+
+            fn poll_race(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Result<Poll<(usize, Self::Output)>, Panicked> {
+                let ($($param,)*) = unsafe { Pin::into_inner_unchecked(self) };
+
+                let mut i = 0;
+                $(
+                    let future = unsafe { Pin::new_unchecked($param) };
+                    let poll = catch_unwind(AssertUnwindSafe(|| unsafe { future.poll(cx) }))
+                        .map_err(|payload| Panicked { i, payload })?;
+                    if let Poll::Ready(output) = poll {
+                        return Ok(Poll::Ready((i, output)));
+                    }
+                    #[allow(unused_assignments)]
+                    {i += 1}
+                )*
+                Ok(Poll::Pending)
+            }
+        }
+    };)*}
+}
+
+implement_raceable_for_tuples! {
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::{pending, ready};
+
+    use crate::future::{block_on, CompletionFutureExt, FutureExt};
+    use crate::test_utils::{Forever, Polls, Yield};
+
+    #[test]
+    fn first_wins() {
+        for i in 0..5 {
+            for j in 0..5 {
+                let output = block_on(race((
+                    Yield::new(i, ready(Box::new(1))),
+                    Yield::new(j, ready(Box::new(2))),
+                    pending(),
+                )));
+                assert_eq!(*output, if i <= j { 1 } else { 2 });
+            }
+        }
+    }
+
+    #[test]
+    fn cancels_losers() {
+        let polls = Polls::new();
+        let output = block_on(race((
+            Forever::new(&polls, 3),
+            Yield::new(2, ready(Box::new(5))),
+            Forever::new(&polls, 1),
+        )));
+        assert_eq!(*output, 5);
+        assert_eq!(polls.cancelled(), 2);
+    }
+
+    #[test]
+    fn panics() {
+        let polls = Polls::new();
+        let payload = block_on(
+            AssertUnwindSafe(race((
+                Forever::<()>::new(&polls, 2),
+                Yield::once(async { std::panic::panic_any(3) }.into_completion()),
+                Forever::new(&polls, 0),
+            )))
+            .catch_unwind(),
+        )
+        .unwrap_err();
+        assert_eq!(*payload.downcast::<i32>().unwrap(), 3);
+        assert_eq!(polls.cancelled(), 2);
+    }
+
+    #[test]
+    fn select_either() {
+        let polls = Polls::new();
+        assert_eq!(
+            block_on(select(
+                Yield::once(ready(Box::new(1))),
+                Forever::<()>::new(&polls, 2),
+            )),
+            Either::Left(Box::new(1)),
+        );
+        assert_eq!(
+            block_on(select(
+                Forever::<()>::new(&polls, 0),
+                Yield::new(3, ready(Box::new("a"))),
+            )),
+            Either::Right(Box::new("a")),
+        );
+        assert_eq!(polls.cancelled(), 2);
+    }
+}
@@ -0,0 +1,72 @@
+//! A queue of indices of futures that have been woken, used to poll only the futures that are
+//! ready.
+
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::task::{Wake, Waker};
+
+use crate::lock;
+
+#[derive(Debug, Default)]
+pub(crate) struct ReadyQueue {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    ready: Vec<usize>,
+    /// The waker of the task that owns the queue.
+    waker: Option<Waker>,
+}
+
+impl ReadyQueue {
+    /// Create a waker that marks the given index as ready and wakes the owning task.
+    pub(crate) fn waker(&self, index: usize) -> Waker {
+        Waker::from(Arc::new(IndexWaker {
+            index,
+            shared: Arc::clone(&self.shared),
+        }))
+    }
+
+    /// Mark an index as ready without waking the owning task.
+    pub(crate) fn push(&self, index: usize) {
+        lock(&self.shared).ready.push(index);
+    }
+
+    /// Set the waker of the task that owns the queue.
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut shared = lock(&self.shared);
+        if !matches!(&shared.waker, Some(old) if old.will_wake(waker)) {
+            shared.waker = Some(waker.clone());
+        }
+    }
+
+    /// Take all the ready indices, without duplicates.
+    pub(crate) fn take(&self) -> Vec<usize> {
+        let mut ready = mem::take(&mut lock(&self.shared).ready);
+        ready.sort_unstable();
+        ready.dedup();
+        ready
+    }
+}
+
+struct IndexWaker {
+    index: usize,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Wake for IndexWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut shared = lock(&self.shared);
+        shared.ready.push(self.index);
+        let waker = shared.waker.clone();
+        drop(shared);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::mem;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use completion_core::CompletionFuture;
+use pin_project_lite::pin_project;
+
+use super::ReadyQueue;
+
+/// Run a future that can spawn child futures borrowing from the current scope.
+///
+/// The closure is called immediately with a [`Spawner`], and returns the body of the scope. Any
+/// future spawned with [`Spawner::spawn`] is polled concurrently with the body, and the scope
+/// only completes once the body and every child have completed. Since completion futures can't be
+/// dropped before they complete, the children can soundly borrow data that outlives the scope,
+/// unlike tasks spawned on an executor which must be `'static`.
+///
+/// Cancelling the scope cancels the body and all the children, and waits for all of their
+/// cancellations to complete. If the body or a child panics, everything else is cancelled and the
+/// panic is resumed once that has finished.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// use completion::{completion_async_move, future};
+///
+/// let numbers = vec![1, 2, 3, 4];
+/// let sum = Cell::new(0);
+///
+/// # future::block_on(completion_async_move! {
+/// let output = future::scope(|s| {
+///     let (numbers, sum) = (&numbers, &sum);
+///     completion_async_move! {
+///         for &n in numbers {
+///             s.spawn(completion_async_move!(sum.set(sum.get() + n)));
+///         }
+///         let doubled = s.spawn(completion_async_move!(numbers.len() * 2));
+///         doubled.await
+///     }
+/// })
+/// .await;
+///
+/// assert_eq!(output, 8);
+/// assert_eq!(sum.get(), 10);
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn scope<'a, F, Fut>(f: F) -> Scope<'a, Fut>
+where
+    F: FnOnce(Spawner<'a>) -> Fut,
+    Fut: CompletionFuture,
+{
+    let spawner = Spawner {
+        shared: Rc::new(RefCell::new(Shared {
+            spawned: Vec::new(),
+            waker: None,
+            finished: false,
+        })),
+    };
+    let body = f(spawner.clone());
+
+    let queue = ReadyQueue::default();
+    queue.push(BODY);
+    let body_waker = queue.waker(BODY);
+
+    Scope {
+        body,
+        body_done: false,
+        output: None,
+        body_waker,
+        children: Vec::new(),
+        free: Vec::new(),
+        live: 0,
+        spawner,
+        queue,
+        state: State::Running,
+    }
+}
+
+/// The index of the body in the ready queue.
+const BODY: usize = usize::MAX;
+
+type BoxedChild<'a> = Pin<Box<dyn CompletionFuture<Output = ()> + 'a>>;
+
+pin_project! {
+    /// Future for [`scope`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Scope<'a, F: CompletionFuture> {
+        #[pin]
+        body: F,
+        body_done: bool,
+        output: Option<F::Output>,
+        body_waker: Waker,
+        // The children that have not yet completed, along with their wakers.
+        children: Vec<Option<(BoxedChild<'a>, Waker)>>,
+        // Unused indices in `children`.
+        free: Vec<usize>,
+        // The number of children that have not yet completed.
+        live: usize,
+        spawner: Spawner<'a>,
+        queue: ReadyQueue,
+        state: State,
+    }
+}
+
+enum State {
+    Running,
+    Cancelling,
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl<F: CompletionFuture> Scope<'_, F> {
+    /// Move newly spawned futures into the scope, returning their indices. If the scope is
+    /// cancelling they are dropped instead; this is fine as they have never been polled.
+    fn adopt(self: Pin<&mut Self>) -> Vec<usize> {
+        let this = self.project();
+        let spawned = mem::take(&mut this.spawner.shared.borrow_mut().spawned);
+
+        if !matches!(this.state, State::Running) {
+            drop(spawned);
+            return Vec::new();
+        }
+
+        spawned
+            .into_iter()
+            .map(|child| {
+                let index = this.free.pop().unwrap_or_else(|| {
+                    this.children.push(None);
+                    this.children.len() - 1
+                });
+                this.children[index] = Some((child, this.queue.waker(index)));
+                *this.live += 1;
+                index
+            })
+            .collect()
+    }
+
+    /// Register the waker to be woken when the scope can make progress.
+    fn register(self: Pin<&mut Self>, cx: &Context<'_>) {
+        let this = self.project();
+        this.queue.register(cx.waker());
+        this.spawner.shared.borrow_mut().waker = Some(cx.waker().clone());
+    }
+
+    fn is_finished(&self) -> bool {
+        self.body_done && self.live == 0 && self.spawner.shared.borrow().spawned.is_empty()
+    }
+
+    fn finish(self: Pin<&mut Self>) {
+        let mut shared = self.spawner.shared.borrow_mut();
+        shared.finished = true;
+        shared.waker = None;
+    }
+
+    /// Poll the body and all the ready children.
+    fn poll_running(mut self: Pin<&mut Self>) -> Result<(), Box<dyn Any + Send>> {
+        let mut ready = self.queue.take();
+
+        if !self.body_done && ready.last() == Some(&BODY) {
+            let this = self.as_mut().project();
+            let mut cx = Context::from_waker(this.body_waker);
+            let body = this.body;
+            let poll = catch_unwind(AssertUnwindSafe(|| unsafe { body.poll(&mut cx) }));
+            match poll {
+                Ok(Poll::Ready(output)) => {
+                    *this.output = Some(output);
+                    *this.body_done = true;
+                }
+                Ok(Poll::Pending) => {}
+                Err(payload) => {
+                    *this.body_done = true;
+                    return Err(payload);
+                }
+            }
+        }
+
+        // Poll children spawned by the body straight away.
+        ready.extend(self.as_mut().adopt());
+
+        let this = self.project();
+        for index in ready {
+            if let Some(Some((child, waker))) = this.children.get_mut(index) {
+                let mut cx = Context::from_waker(waker);
+                let poll =
+                    catch_unwind(AssertUnwindSafe(|| unsafe { child.as_mut().poll(&mut cx) }));
+                let result = match poll {
+                    Ok(Poll::Pending) => continue,
+                    Ok(Poll::Ready(())) => Ok(()),
+                    Err(payload) => Err(payload),
+                };
+                this.children[index] = None;
+                this.free.push(index);
+                *this.live -= 1;
+                result?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cancel the body and every child. Panics are stored in the state.
+    fn poll_cancelling(mut self: Pin<&mut Self>) -> Poll<()> {
+        // Futures spawned while cancelling are never polled.
+        self.as_mut().adopt();
+
+        let this = self.project();
+        let ready = this.queue.take();
+        let mut panics = Vec::new();
+
+        if !*this.body_done && ready.last() == Some(&BODY) {
+            let mut cx = Context::from_waker(this.body_waker);
+            let body = this.body;
+            match catch_unwind(AssertUnwindSafe(|| unsafe { body.poll_cancel(&mut cx) })) {
+                Ok(Poll::Ready(())) => *this.body_done = true,
+                Ok(Poll::Pending) => {}
+                Err(payload) => {
+                    *this.body_done = true;
+                    panics.push(payload);
+                }
+            }
+        }
+
+        for index in ready {
+            if let Some(Some((child, waker))) = this.children.get_mut(index) {
+                let mut cx = Context::from_waker(waker);
+                let poll = catch_unwind(AssertUnwindSafe(|| unsafe {
+                    child.as_mut().poll_cancel(&mut cx)
+                }));
+                match poll {
+                    Ok(Poll::Pending) => continue,
+                    Ok(Poll::Ready(())) => {}
+                    Err(payload) => panics.push(payload),
+                }
+                this.children[index] = None;
+                this.free.push(index);
+                *this.live -= 1;
+            }
+        }
+
+        for payload in panics {
+            if let State::Cancelling = this.state {
+                *this.state = State::Panicked(payload);
+            }
+        }
+
+        if *this.body_done && *this.live == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Switch from running to cancelling everything.
+    fn start_cancelling(self: Pin<&mut Self>, state: State) {
+        let this = self.project();
+        *this.state = state;
+        this.queue.push(BODY);
+        for (index, child) in this.children.iter().enumerate() {
+            if child.is_some() {
+                this.queue.push(index);
+            }
+        }
+    }
+
+    /// Drive the cancellation, resuming the stored panic if there is one once it has finished.
+    fn poll_cancel_all(mut self: Pin<&mut Self>, cx: &Context<'_>) -> Poll<()> {
+        self.as_mut().register(cx);
+        if self.as_mut().poll_cancelling().is_pending() {
+            return Poll::Pending;
+        }
+        self.as_mut().finish();
+
+        let this = self.project();
+        if let State::Panicked(_) = this.state {
+            if let State::Panicked(payload) = mem::replace(this.state, State::Cancelling) {
+                resume_unwind(payload);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl<F: CompletionFuture> CompletionFuture for Scope<'_, F> {
+    type Output = F::Output;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let State::Running = self.state {
+            self.as_mut().register(cx);
+            if let Err(payload) = self.as_mut().poll_running() {
+                self.as_mut().start_cancelling(State::Panicked(payload));
+            } else if self.is_finished() {
+                self.as_mut().finish();
+                let output = self.project().output.take();
+                return Poll::Ready(output.expect("`Scope` polled after completion"));
+            } else {
+                return Poll::Pending;
+            }
+        }
+        if let State::Cancelling = self.state {
+            panic!("Called `poll` after `poll_cancel` on `Scope`");
+        }
+        match self.poll_cancel_all(cx) {
+            Poll::Ready(()) => unreachable!(),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let State::Running = self.state {
+            self.as_mut().start_cancelling(State::Cancelling);
+        }
+        self.poll_cancel_all(cx)
+    }
+}
+
+impl<F: CompletionFuture> Debug for Scope<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope")
+            .field("body_done", &self.body_done)
+            .field("children", &self.live)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A handle used to spawn futures onto a [`scope`].
+///
+/// Spawners can be cloned and moved into the spawned futures, so that they can spawn futures of
+/// their own.
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub struct Spawner<'a> {
+    shared: Rc<RefCell<Shared<'a>>>,
+}
+
+struct Shared<'a> {
+    /// Futures that have been spawned but not yet moved into the scope.
+    spawned: Vec<BoxedChild<'a>>,
+    /// The waker of the scope.
+    waker: Option<Waker>,
+    /// Whether the scope has completed.
+    finished: bool,
+}
+
+impl<'a> Spawner<'a> {
+    /// Spawn a future onto the scope.
+    ///
+    /// The future will be polled concurrently with the rest of the scope, and the scope will not
+    /// complete until the future has. The returned handle can be used to get its output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scope has already completed.
+    pub fn spawn<F>(&self, future: F) -> ScopedJoinHandle<F::Output>
+    where
+        F: CompletionFuture + 'a,
+        F::Output: 'a,
+    {
+        let state = Rc::new(RefCell::new(ChildState {
+            output: None,
+            waker: None,
+            finished: false,
+        }));
+        let child = Box::pin(Child {
+            future,
+            state: Rc::clone(&state),
+        });
+
+        let mut shared = self.shared.borrow_mut();
+        assert!(!shared.finished, "spawned a future onto a finished scope");
+        shared.spawned.push(child);
+        let waker = shared.waker.clone();
+        drop(shared);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        ScopedJoinHandle { state }
+    }
+}
+
+impl Clone for Spawner<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Rc::clone(&self.shared),
+        }
+    }
+}
+
+impl Debug for Spawner<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spawner").finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a spawned future and its [`ScopedJoinHandle`].
+struct ChildState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+    finished: bool,
+}
+
+pin_project! {
+    /// A spawned future, which sends its output to its handle.
+    struct Child<F: CompletionFuture> {
+        #[pin]
+        future: F,
+        state: Rc<RefCell<ChildState<F::Output>>>,
+    }
+}
+
+impl<F: CompletionFuture> CompletionFuture for Child<F> {
+    type Output = ();
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = match this.future.poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let mut state = this.state.borrow_mut();
+        state.finished = true;
+        let waker = state.waker.take();
+        let output = if Rc::strong_count(this.state) > 1 {
+            state.output = Some(output);
+            None
+        } else {
+            Some(output)
+        };
+        drop(state);
+
+        drop(output);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(())
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().future.poll_cancel(cx)
+    }
+}
+
+/// A handle to a future spawned on a [`scope`], which can be awaited to get the future's output.
+///
+/// Unlike an executor's [`JoinHandle`](crate::executor::JoinHandle), dropping this handle does not
+/// cancel the future; the scope always drives it to completion. If the scope is cancelled before
+/// the future finishes, the handle never resolves, so it should only be awaited from within the
+/// scope.
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub struct ScopedJoinHandle<T> {
+    state: Rc<RefCell<ChildState<T>>>,
+}
+
+impl<T> ScopedJoinHandle<T> {
+    /// Get whether the spawned future has completed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().finished
+    }
+}
+
+impl<T> CompletionFuture for ScopedJoinHandle<T> {
+    type Output = T;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+impl<T> Future for ScopedJoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        if let Some(output) = state.output.take() {
+            return Poll::Ready(output);
+        }
+        assert!(
+            !state.finished,
+            "`ScopedJoinHandle` polled after completion"
+        );
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Debug for ScopedJoinHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedJoinHandle")
+            .field("finished", &self.is_finished())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::future::ready;
+
+    use crate::future::{block_on, CompletionFutureExt, FutureExt};
+    use crate::test_utils::{Forever, PollThenCancel, Polls, Yield};
+
+    #[test]
+    fn borrows() {
+        let mut values = vec![0; 10];
+        let output = block_on(scope(|s| {
+            for (i, value) in values.iter_mut().enumerate() {
+                s.spawn(Yield::new(
+                    i,
+                    async move {
+                        *value = i * 2;
+                    }
+                    .into_completion(),
+                ));
+            }
+            ready(5)
+        }));
+        assert_eq!(output, 5);
+        assert_eq!(values, [0, 2, 4, 6, 8, 10, 12, 14, 16, 18]);
+    }
+
+    #[test]
+    fn handles() {
+        let output = block_on(scope(|s| {
+            async move {
+                let a = s.spawn(Yield::new(3, ready(Box::new(1))));
+                let b = s.spawn(Yield::once(ready(Box::new(2))));
+                *a.await + *b.await
+            }
+            .into_completion()
+        }));
+        assert_eq!(output, 3);
+    }
+
+    #[test]
+    fn nested_spawn() {
+        let count = Cell::new(0);
+        block_on(scope(|s| {
+            let count = &count;
+            for _ in 0..5 {
+                let inner = s.clone();
+                s.spawn(Yield::once(
+                    async move {
+                        for _ in 0..5 {
+                            inner.spawn(Yield::once(
+                                async move { count.set(count.get() + 1) }.into_completion(),
+                            ));
+                        }
+                    }
+                    .into_completion(),
+                ));
+            }
+            ready(())
+        }));
+        assert_eq!(count.get(), 25);
+    }
+
+    #[test]
+    fn cancel() {
+        let polls = Polls::new();
+        let future = scope(|s| {
+            for i in 0..5 {
+                s.spawn(Forever::<()>::new(&polls, i));
+            }
+            Forever::<()>::new(&polls, 2)
+        });
+        block_on(PollThenCancel::new(Box::pin(future)));
+        assert_eq!(polls.cancelled(), 6);
+    }
+
+    #[test]
+    fn panics() {
+        let polls = Polls::new();
+        let payload = block_on(
+            AssertUnwindSafe(scope(|s| {
+                s.spawn(Forever::<()>::new(&polls, 3));
+                s.spawn(Yield::new(
+                    2,
+                    async { std::panic::panic_any(5) }.into_completion(),
+                ));
+                Forever::<()>::new(&polls, 1)
+            }))
+            .catch_unwind(),
+        )
+        .unwrap_err();
+        assert_eq!(*payload.downcast::<i32>().unwrap(), 5);
+        assert_eq!(polls.cancelled(), 2);
+    }
+}
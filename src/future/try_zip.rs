@@ -0,0 +1,359 @@
+use core::any::Any;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::panic::resume_unwind;
+
+use completion_core::{CompletionFuture, FusedCompletionFuture};
+use pin_project_lite::pin_project;
+
+use super::zip::{Panicked, Zippable};
+
+/// Joins fallible futures, waiting for them all to succeed or one of them to fail.
+///
+/// This takes any tuple of two or more futures that output [`Result`]s with the same error type,
+/// and outputs a tuple of the successful values. As soon as one of the futures fails, all the
+/// others are cancelled, and the error is returned once their cancellation has finished.
+///
+/// Requires the `std` feature, as [`catch_unwind`](std::panic::catch_unwind) is needed when
+/// polling the futures to ensure soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, completion_async};
+///
+/// # future::block_on(completion_async! {
+/// assert_eq!(
+///     future::try_zip((
+///         completion_async!(Ok::<_, ()>(5)),
+///         completion_async!(Ok(6)),
+///     ))
+///     .await,
+///     Ok((5, 6)),
+/// );
+///
+/// assert_eq!(
+///     future::try_zip((
+///         completion_async!(Err::<(), _>("failed")),
+///         std::future::pending::<Result<(), _>>(),
+///     ))
+///     .await,
+///     Err("failed"),
+/// );
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub fn try_zip<T: TryZippable>(futures: T) -> TryZip<T> {
+    let running = futures.make_running();
+    TryZip {
+        futures,
+        state: State::Running(running),
+        _correct_debug_bounds: PhantomData,
+    }
+}
+
+pin_project! {
+    /// Future for [`try_zip`].
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct TryZip<T: TryZippable> {
+        #[pin]
+        futures: T,
+        state: State<T>,
+        _correct_debug_bounds: PhantomData<(T::Running, T::Cancelling, T::Error)>,
+    }
+}
+
+#[derive(Debug)]
+enum State<T: TryZippable> {
+    Running(T::Running),
+    /// One of the futures failed, and the others are being cancelled.
+    Failing(T::Error, T::Cancelling),
+    Cancelling(T::Cancelling),
+    Panicked(Box<dyn Any + Send>, T::Cancelling),
+    Done,
+    Dummy,
+}
+
+impl<T: TryZippable> TryZip<T> {
+    fn poll_panicked(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        let this = self.project();
+
+        let cancelling = match this.state {
+            State::Panicked(_, cancelling) => cancelling,
+            State::Cancelling(_) => panic!("Called `poll` after `poll_cancel` on `TryZip`"),
+            State::Done => panic!("Called `poll` after completion on `TryZip`"),
+            State::Dummy => panic!("Called `poll` or `poll_cancel` after panicking on `TryZip`"),
+            _ => unreachable!(),
+        };
+        if this.futures.poll_panicked(cancelling, cx).is_ready() {
+            let State::Panicked(payload, _) = std::mem::replace(this.state, State::Dummy) else {
+                unreachable!()
+            };
+            resume_unwind(payload)
+        }
+    }
+}
+
+impl<T: TryZippable> CompletionFuture for TryZip<T> {
+    type Output = Result<T::Ok, T::Error>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+
+        if let State::Running(running) = this.state {
+            match this.futures.as_mut().poll_all(running, cx) {
+                Ok(Poll::Ready(outputs)) => {
+                    *this.state = State::Done;
+                    return Poll::Ready(T::into_ok(outputs));
+                }
+                Ok(Poll::Pending) => match T::take_error(running) {
+                    Some((i, error)) => {
+                        let mut cancelling = T::make_cancelling(running);
+                        T::set_cancelled(&mut cancelling, i);
+                        *this.state = State::Failing(error, cancelling);
+                    }
+                    None => return Poll::Pending,
+                },
+                Err(Panicked { i, payload }) => {
+                    let mut cancelling = T::make_cancelling(running);
+                    T::set_cancelled(&mut cancelling, i);
+                    *this.state = State::Panicked(payload, cancelling);
+                }
+            }
+        }
+        if let State::Failing(_, cancelling) = this.state {
+            match this.futures.as_mut().poll_cancel(cancelling, cx) {
+                Ok(Poll::Ready(())) => {
+                    let State::Failing(error, _) = std::mem::replace(this.state, State::Done)
+                    else {
+                        unreachable!()
+                    };
+                    return Poll::Ready(Err(error));
+                }
+                Ok(Poll::Pending) => return Poll::Pending,
+                Err(Panicked { i, payload }) => {
+                    let State::Failing(_, mut cancelling) =
+                        std::mem::replace(this.state, State::Dummy)
+                    else {
+                        unreachable!()
+                    };
+                    T::set_cancelled(&mut cancelling, i);
+                    *this.state = State::Panicked(payload, cancelling);
+                }
+            }
+        }
+        self.poll_panicked(cx);
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut this = self.as_mut().project();
+
+        if let State::Done = this.state {
+            return Poll::Ready(());
+        }
+        match std::mem::replace(this.state, State::Dummy) {
+            State::Running(mut running) => {
+                *this.state = State::Cancelling(T::make_cancelling(&mut running));
+            }
+            State::Failing(_, cancelling) => *this.state = State::Cancelling(cancelling),
+            state => *this.state = state,
+        }
+        if let State::Cancelling(cancelling) = this.state {
+            match this.futures.as_mut().poll_cancel(cancelling, cx) {
+                Ok(Poll::Ready(())) => {
+                    *this.state = State::Done;
+                    return Poll::Ready(());
+                }
+                Ok(Poll::Pending) => return Poll::Pending,
+                Err(Panicked { i, payload }) => {
+                    let State::Cancelling(mut cancelling) =
+                        std::mem::replace(this.state, State::Dummy)
+                    else {
+                        unreachable!()
+                    };
+                    T::set_cancelled(&mut cancelling, i);
+                    *this.state = State::Panicked(payload, cancelling);
+                }
+            }
+        }
+        self.poll_panicked(cx);
+        Poll::Pending
+    }
+}
+
+impl<T: TryZippable> FusedCompletionFuture for TryZip<T> {
+    fn is_terminated(&self) -> bool {
+        matches!(self.state, State::Done | State::Dummy)
+    }
+}
+
+/// A type that is a [`Result`].
+pub trait IsResult {
+    type Ok;
+    type Error;
+
+    fn is_err(&self) -> bool;
+    fn into_result(self) -> Result<Self::Ok, Self::Error>;
+}
+
+impl<T, E> IsResult for Result<T, E> {
+    type Ok = T;
+    type Error = E;
+
+    fn is_err(&self) -> bool {
+        self.is_err()
+    }
+    fn into_result(self) -> Self {
+        self
+    }
+}
+
+pub trait TryZippable: Zippable {
+    type Ok;
+    type Error;
+
+    /// Take the error out of the first future that has failed, if any.
+    fn take_error(running: &mut Self::Running) -> Option<(usize, Self::Error)>;
+
+    fn into_ok(outputs: Self::Outputs) -> Result<Self::Ok, Self::Error>;
+}
+
+macro_rules! implement_try_zippable_for_tuples {
+    ($(($($param:ident),*),)*) => { $(#[allow(non_snake_case)] const _: () = {
+        impl<Error, $($param,)*> TryZippable for ($($param,)*)
+        where
+            $($param: CompletionFuture, $param::Output: IsResult<Error = Error>,)*
+        {
+            type Ok = ($(<$param::Output as IsResult>::Ok,)*);
+            type Error = Error;
+
+            fn take_error(($($param,)*): &mut Self::Running) -> Option<(usize, Self::Error)> {
+                let mut i = 0;
+                $(
+                    if matches!($param, Some(output) if output.is_err()) {
+                        return $param.take().unwrap().into_result().err().map(|e| (i, e));
+                    }
+                    #[allow(unused_assignments)]
+                    {i += 1}
+                )*
+                None
+            }
+
+            fn into_ok(($($param,)*): Self::Outputs) -> Result<Self::Ok, Self::Error> {
+                Ok(($($param.into_result()?,)*))
+            }
+        }
+    };)*}
+}
+
+implement_try_zippable_for_tuples! {
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::ready;
+    use std::panic::AssertUnwindSafe;
+
+    use crate::future::{block_on, CompletionFutureExt, FutureExt};
+    use crate::test_utils::{poll_cancel_once, poll_once, Forever, Polls, Yield};
+
+    #[test]
+    fn success() {
+        for i in 0..5 {
+            for j in 0..5 {
+                let f1 = Yield::new(i, ready(Ok::<_, ()>(Box::new(1))));
+                let f2 = Yield::new(j, ready(Ok(Box::new(2))));
+                assert_eq!(block_on(try_zip((f1, f2))), Ok((Box::new(1), Box::new(2))));
+            }
+        }
+    }
+
+    #[test]
+    fn failure() {
+        let polls = Polls::new();
+        let output = block_on(try_zip((
+            Forever::<Result<(), _>>::new(&polls, 2),
+            Yield::new(2, ready(Err::<(), _>(Box::new(5)))),
+            Yield::once(ready(Ok(Box::new(1)))),
+            Forever::<Result<(), _>>::new(&polls, 0),
+        )));
+        assert_eq!(output, Err(Box::new(5)));
+        assert_eq!(polls.cancelled(), 2);
+
+        // When all the futures complete at once, the first error wins.
+        let output = block_on(try_zip((
+            ready(Ok::<_, i32>(())),
+            ready(Err::<(), _>(1)),
+            ready(Err::<(), _>(2)),
+        )));
+        assert_eq!(output, Err(1));
+    }
+
+    #[test]
+    fn terminated() {
+        let fut = try_zip((Yield::once(ready(Ok::<_, ()>(1))), ready(Ok(2))));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), Some(Ok((1, 2))));
+        assert!(fut.is_terminated());
+
+        let fut = try_zip((Yield::once(ready(Ok::<(), _>(()))), ready(Err::<(), _>(1))));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), Some(Err(1)));
+        assert!(fut.is_terminated());
+        assert!(poll_cancel_once(fut.as_mut()));
+
+        let fut = try_zip((Yield::new(2, ready(Ok::<_, ()>(1))), ready(Ok(2))));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(!fut.is_terminated());
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert!(fut.is_terminated());
+    }
+
+    #[test]
+    #[should_panic = "Called `poll` after completion on `TryZip`"]
+    fn poll_after_completion() {
+        let fut = try_zip((ready(Ok::<(), _>(())), ready(Err::<(), _>(1))));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), Some(Err(1)));
+        poll_once(fut.as_mut());
+    }
+
+    #[test]
+    fn panics() {
+        fn fail() -> Result<(), ()> {
+            std::panic::panic_any(3)
+        }
+
+        let polls = Polls::new();
+        let payload = block_on(
+            AssertUnwindSafe(try_zip((
+                Forever::<Result<(), ()>>::new(&polls, 1),
+                Yield::once(async { fail() }.into_completion()),
+            )))
+            .catch_unwind(),
+        )
+        .unwrap_err();
+        assert_eq!(*payload.downcast::<i32>().unwrap(), 3);
+        assert_eq!(polls.cancelled(), 1);
+    }
+}
@@ -4,12 +4,14 @@ use core::pin::Pin;
 use core::task::{Context, Poll};
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 
-use completion_core::CompletionFuture;
+use completion_core::{CompletionFuture, FusedCompletionFuture};
 use pin_project_lite::pin_project;
 
 /// Joins futures, waiting for them all to complete.
 ///
-/// This takes any tuple of two or more futures, and outputs a tuple of the results.
+/// This takes any tuple of two or more futures, and outputs a tuple of the results. It also takes
+/// arrays of futures, outputting an array of the results; use [`join_all`](super::join_all) to
+/// join a number of futures only known at runtime.
 ///
 /// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
 /// soundness.
@@ -32,9 +34,10 @@ use pin_project_lite::pin_project;
 /// ```
 #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub fn zip<T: Zippable>(futures: T) -> Zip<T> {
+    let running = futures.make_running();
     Zip {
         futures,
-        state: State::Running(T::Running::default()),
+        state: State::Running(running),
         _correct_debug_bounds: PhantomData,
     }
 }
@@ -57,6 +60,7 @@ enum State<T: Zippable> {
     Running(T::Running),
     Cancelling(T::Cancelling),
     Panicked(Box<dyn Any + Send>, T::Cancelling),
+    Done,
     Dummy,
 }
 
@@ -67,6 +71,7 @@ impl<T: Zippable> Zip<T> {
         let cancelling = match this.state {
             State::Panicked(_, cancelling) => cancelling,
             State::Cancelling(_) => panic!("Called `poll` after `poll_cancel` on `Zip`"),
+            State::Done => panic!("Called `poll` after completion on `Zip`"),
             State::Dummy => panic!("Called `poll` or `poll_cancel` after panicking on `Zip`"),
             _ => unreachable!(),
         };
@@ -88,7 +93,11 @@ impl<T: Zippable> CompletionFuture for Zip<T> {
 
         if let State::Running(running) = this.state {
             match this.futures.as_mut().poll_all(running, cx) {
-                Ok(res) => return res,
+                Ok(Poll::Ready(outputs)) => {
+                    *this.state = State::Done;
+                    return Poll::Ready(outputs);
+                }
+                Ok(Poll::Pending) => return Poll::Pending,
                 Err(Panicked { i, payload }) => {
                     let mut cancelling = T::make_cancelling(running);
                     T::set_cancelled(&mut cancelling, i);
@@ -102,12 +111,19 @@ impl<T: Zippable> CompletionFuture for Zip<T> {
     unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
         let mut this = self.as_mut().project();
 
+        if let State::Done = this.state {
+            return Poll::Ready(());
+        }
         if let State::Running(running) = this.state {
             *this.state = State::Cancelling(T::make_cancelling(running));
         }
         if let State::Cancelling(cancelling) = this.state {
             match this.futures.as_mut().poll_cancel(cancelling, cx) {
-                Ok(res) => return res,
+                Ok(Poll::Ready(())) => {
+                    *this.state = State::Done;
+                    return Poll::Ready(());
+                }
+                Ok(Poll::Pending) => return Poll::Pending,
                 Err(Panicked { i, payload }) => {
                     let mut cancelling = match std::mem::replace(this.state, State::Dummy) {
                         State::Cancelling(cancelling) => cancelling,
@@ -123,18 +139,26 @@ impl<T: Zippable> CompletionFuture for Zip<T> {
     }
 }
 
+impl<T: Zippable> FusedCompletionFuture for Zip<T> {
+    fn is_terminated(&self) -> bool {
+        matches!(self.state, State::Done | State::Dummy)
+    }
+}
+
 /// The error returned when future in a `Zippable` panics.
 #[allow(missing_debug_implementations)]
 pub struct Panicked {
-    i: usize,
-    payload: Box<dyn Any + Send>,
+    pub(super) i: usize,
+    pub(super) payload: Box<dyn Any + Send>,
 }
 
 pub trait Zippable: Sized {
-    type Running: Default;
+    type Running;
     type Cancelling;
     type Outputs;
 
+    fn make_running(&self) -> Self::Running;
+
     fn make_cancelling(running: &mut Self::Running) -> Self::Cancelling;
 
     fn set_cancelled(cancelling: &mut Self::Cancelling, i: usize);
@@ -178,6 +202,10 @@ macro_rules! implement_zippable_for_tuples {
             type Cancelling = ($(repeat_with!($param, bool),)*);
             type Outputs = ($($param::Output,)*);
 
+            fn make_running(&self) -> Self::Running {
+                ($(None::<$param::Output>,)*)
+            }
+
             fn make_cancelling(($($param,)*): &mut Self::Running) -> Self::Cancelling {
                 ($($param.take().is_some(),)*)
             }
@@ -305,7 +333,7 @@ mod tests {
     use std::panic::AssertUnwindSafe;
 
     use crate::future::{block_on, CompletionFutureExt, FutureExt};
-    use crate::test_utils::Yield;
+    use crate::test_utils::{poll_cancel_once, poll_once, Yield};
 
     use super::zip;
 
@@ -324,6 +352,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn terminated() {
+        let fut = zip((Yield::once(ready(1)), ready(2)));
+        futures_lite::pin!(fut);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!fut.is_terminated());
+        assert_eq!(poll_once(fut.as_mut()), Some((1, 2)));
+        assert!(fut.is_terminated());
+
+        let fut = zip((Yield::new(2, ready(1)), ready(2)));
+        futures_lite::pin!(fut);
+        assert_eq!(poll_once(fut.as_mut()), None);
+        assert!(!poll_cancel_once(fut.as_mut()));
+        assert!(!fut.is_terminated());
+        assert!(poll_cancel_once(fut.as_mut()));
+        assert!(fut.is_terminated());
+    }
+
     #[test]
     fn panics() {
         let mut x = 0;
@@ -20,6 +20,9 @@ pub use copy::*;
 mod buffered;
 pub use buffered::*;
 
+mod owned;
+pub use owned::*;
+
 unsafe fn extend_lifetime_mut<'a, T: ?Sized>(r: &mut T) -> &'a mut T {
     &mut *(r as *mut _)
 }
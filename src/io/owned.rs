@@ -0,0 +1,456 @@
+//! Adapters between the borrowed and owned I/O traits.
+
+use std::cmp;
+use std::future::{self, Future};
+use std::io::{IoSlice, Result};
+use std::mem::{self, MaybeUninit};
+use std::pin::Pin;
+use std::slice;
+use std::task::{Context, Poll};
+
+use aliasable::boxed::AliasableBox;
+use completion_core::CompletionFuture;
+use completion_io::{
+    AsyncRead, AsyncReadWith, AsyncWrite, AsyncWriteWith, DefaultWriteVectored, IoBuf, IoBufMut,
+    OwnedAsyncRead, OwnedAsyncReadWith, OwnedAsyncWrite, OwnedAsyncWriteWith, ReadBuf, ReadBufMut,
+};
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use super::extend_lifetime_mut;
+
+/// Use a [reader](AsyncRead) or [writer](AsyncWrite) that borrows its buffers with owned buffers.
+///
+/// This implements [`OwnedAsyncRead`] and [`OwnedAsyncWrite`] for any buffer type, by lending the
+/// buffer to the inner reader or writer for the duration of each operation.
+///
+/// # Examples
+///
+/// ```
+/// use completion::io::{Owned, OwnedAsyncReadWith};
+///
+/// # completion::future::block_on(completion::completion_async! {
+/// let mut reader = Owned::new(&b"Lorem ipsum"[..]);
+///
+/// let (res, buf) = reader.read_owned(Vec::with_capacity(5)).await;
+/// assert_eq!(res?, 5);
+/// assert_eq!(buf, b"Lorem");
+/// # completion_io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Owned<T> {
+    inner: T,
+}
+
+impl<T> Owned<T> {
+    /// Create a new `Owned` around a reader or writer.
+    #[must_use]
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get a shared reference to the underlying reader or writer.
+    #[must_use]
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader or writer.
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Take the underlying reader or writer.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<'a, B: IoBufMut, T: AsyncRead> OwnedAsyncReadWith<'a, B> for Owned<T> {
+    type ReadOwnedFuture = ReadOwned<'a, T, B>;
+
+    fn read_owned(&'a mut self, buf: B) -> Self::ReadOwnedFuture {
+        // The buffer is boxed so that moving the future doesn't move it. Its memory would stay in
+        // place, but moving some buffers such as `Box<[u8]>` asserts unique access to that memory,
+        // which would invalidate the slice lent to the inner reader.
+        let mut buf = AliasableBox::from_unique(Box::new(buf));
+        let data = unsafe {
+            slice::from_raw_parts_mut(
+                buf.stable_mut_ptr().cast::<MaybeUninit<u8>>(),
+                buf.bytes_total(),
+            )
+        };
+        let mut read_buf = ReadBuf::uninit(data);
+        unsafe { read_buf.assume_init(buf.bytes_init()) };
+
+        let mut read_buf = AliasableBox::from_unique(Box::new(read_buf));
+        let read_buf_mut = unsafe { extend_lifetime_mut(&mut *read_buf) };
+
+        ReadOwned {
+            fut: Some(self.inner.read(read_buf_mut.as_mut())),
+            read_buf: Some(read_buf),
+            buf: Some(buf),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for [`read_owned`](OwnedAsyncReadWith::read_owned) on an [`Owned`].
+    pub struct ReadOwned<'a, T: AsyncRead, B> {
+        #[pin]
+        fut: Option<<T as AsyncReadWith<'a>>::ReadFuture>,
+        // Points into `buf`, and is borrowed by `fut`.
+        read_buf: Option<AliasableBox<ReadBuf<'a>>>,
+        buf: Option<AliasableBox<B>>,
+    }
+}
+
+impl<T: AsyncRead, B: IoBufMut> CompletionFuture for ReadOwned<'_, T, B> {
+    type Output = (Result<usize>, B);
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let fut = this
+            .fut
+            .as_mut()
+            .as_pin_mut()
+            .expect("polled after completion");
+        let res = ready!(fut.poll(cx));
+
+        // Release everything that borrows the buffer before giving it back.
+        this.fut.set(None);
+        let filled = this.read_buf.take().unwrap().filled().len();
+
+        let buf = this.buf.take().unwrap();
+        let mut buf = *AliasableBox::into_unique(buf);
+        buf.set_init(filled);
+        Poll::Ready((res.map(|()| filled), buf))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.project().fut.as_pin_mut() {
+            Some(fut) => fut.poll_cancel(cx),
+            None => Poll::Ready(()),
+        }
+    }
+}
+impl<'a, T: AsyncRead, B: IoBufMut> Future for ReadOwned<'a, T, B>
+where
+    <T as AsyncReadWith<'a>>::ReadFuture: Future<Output = Result<()>>,
+{
+    type Output = (Result<usize>, B);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+impl<'a, B: IoBuf, T: AsyncWrite> OwnedAsyncWriteWith<'a, B> for Owned<T> {
+    type WriteOwnedFuture = WriteOwned<'a, T, B>;
+
+    fn write_owned(&'a mut self, buf: B) -> Self::WriteOwnedFuture {
+        // The buffer is boxed so that moving the future doesn't move it; see `read_owned` above.
+        let buf = AliasableBox::from_unique(Box::new(buf));
+        let data = unsafe { slice::from_raw_parts(buf.stable_ptr(), buf.bytes_init()) };
+        WriteOwned {
+            fut: Some(self.inner.write(data)),
+            buf: Some(buf),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for [`write_owned`](OwnedAsyncWriteWith::write_owned) on an [`Owned`].
+    pub struct WriteOwned<'a, T: AsyncWrite, B> {
+        // Borrows from `buf`.
+        #[pin]
+        fut: Option<<T as AsyncWriteWith<'a>>::WriteFuture>,
+        buf: Option<AliasableBox<B>>,
+    }
+}
+
+impl<T: AsyncWrite, B: IoBuf> CompletionFuture for WriteOwned<'_, T, B> {
+    type Output = (Result<usize>, B);
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let fut = this
+            .fut
+            .as_mut()
+            .as_pin_mut()
+            .expect("polled after completion");
+        let res = ready!(fut.poll(cx));
+
+        // Release the borrow of the buffer before giving it back.
+        this.fut.set(None);
+        let buf = this.buf.take().unwrap();
+        Poll::Ready((res, *AliasableBox::into_unique(buf)))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.project().fut.as_pin_mut() {
+            Some(fut) => fut.poll_cancel(cx),
+            None => Poll::Ready(()),
+        }
+    }
+}
+impl<'a, T: AsyncWrite, B: IoBuf> Future for WriteOwned<'a, T, B>
+where
+    <T as AsyncWriteWith<'a>>::WriteFuture: Future<Output = Result<usize>>,
+{
+    type Output = (Result<usize>, B);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+/// Use a [reader](OwnedAsyncRead) or [writer](OwnedAsyncWrite) that takes owned buffers with
+/// borrowed buffers.
+///
+/// This implements [`AsyncRead`] and [`AsyncWrite`] by passing internal [`Vec`]s to the inner
+/// reader or writer, and copying data between them and the borrowed buffers. The inner reader may
+/// read more data than fits in the borrowed buffer, in which case the rest is kept for the next
+/// read.
+///
+/// Cancelling a read discards any data that the inner reader read before it was cancelled.
+/// Writes are passed directly to the inner writer, so flushing does nothing.
+#[derive(Debug, Default)]
+pub struct Borrowed<T> {
+    inner: T,
+    /// Data read by the inner reader, of which `pos..` has not yet been returned.
+    read_buf: Vec<u8>,
+    pos: usize,
+    write_buf: Vec<u8>,
+}
+
+impl<T> Borrowed<T> {
+    /// Create a new `Borrowed` around a reader or writer.
+    #[must_use]
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            read_buf: Vec::new(),
+            pos: 0,
+            write_buf: Vec::new(),
+        }
+    }
+
+    /// Get a shared reference to the underlying reader or writer.
+    #[must_use]
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader or writer.
+    ///
+    /// It is inadvisable to directly read from the underlying reader, as data that has already
+    /// been read into this type's buffer will be returned out of order.
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Take the underlying reader or writer.
+    ///
+    /// Any data that has been read from the underlying reader but not yet returned is lost.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<'a, T: OwnedAsyncRead<Vec<u8>>> AsyncReadWith<'a> for Borrowed<T> {
+    type ReadFuture = ReadBorrowed<'a, T>;
+
+    fn read(&'a mut self, buf: ReadBufMut<'a>) -> Self::ReadFuture {
+        let fut = if self.pos < self.read_buf.len() {
+            None
+        } else {
+            let mut owned = mem::take(&mut self.read_buf);
+            owned.clear();
+            owned.reserve(buf.remaining());
+            self.pos = 0;
+            Some(self.inner.read_owned(owned))
+        };
+        ReadBorrowed {
+            fut,
+            read_buf: &mut self.read_buf,
+            pos: &mut self.pos,
+            buf,
+        }
+    }
+}
+
+pin_project! {
+    /// Future for [`read`](AsyncReadWith::read) on a [`Borrowed`].
+    pub struct ReadBorrowed<'a, T: OwnedAsyncRead<Vec<u8>>> {
+        #[pin]
+        fut: Option<<T as OwnedAsyncReadWith<'a, Vec<u8>>>::ReadOwnedFuture>,
+        read_buf: &'a mut Vec<u8>,
+        pos: &'a mut usize,
+        buf: ReadBufMut<'a>,
+    }
+}
+
+impl<T: OwnedAsyncRead<Vec<u8>>> CompletionFuture for ReadBorrowed<'_, T> {
+    type Output = Result<()>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if let Some(fut) = this.fut.as_mut().as_pin_mut() {
+            let (res, owned) = ready!(fut.poll(cx));
+            this.fut.set(None);
+            **this.read_buf = owned;
+
+            match res {
+                Ok(n) => this.read_buf.truncate(n),
+                Err(e) => {
+                    this.read_buf.clear();
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+
+        let available = &this.read_buf[**this.pos..];
+        let n = cmp::min(available.len(), this.buf.remaining());
+        this.buf.append(&available[..n]);
+        **this.pos += n;
+        Poll::Ready(Ok(()))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(fut) = self.project().fut.as_pin_mut() {
+            fut.poll_cancel(cx)
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+impl<'a, T: OwnedAsyncRead<Vec<u8>>> Future for ReadBorrowed<'a, T>
+where
+    <T as OwnedAsyncReadWith<'a, Vec<u8>>>::ReadOwnedFuture:
+        Future<Output = (Result<usize>, Vec<u8>)>,
+{
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+impl<'a, T: OwnedAsyncWrite<Vec<u8>>> AsyncWriteWith<'a> for Borrowed<T> {
+    type WriteFuture = WriteBorrowed<'a, T>;
+    type WriteVectoredFuture = DefaultWriteVectored<'a, Self>;
+    type FlushFuture = future::Ready<Result<()>>;
+
+    fn write(&'a mut self, buf: &'a [u8]) -> Self::WriteFuture {
+        let mut owned = mem::take(&mut self.write_buf);
+        owned.clear();
+        owned.extend_from_slice(buf);
+        WriteBorrowed {
+            fut: self.inner.write_owned(owned),
+            write_buf: &mut self.write_buf,
+        }
+    }
+    fn write_vectored(&'a mut self, bufs: &'a [IoSlice<'a>]) -> Self::WriteVectoredFuture {
+        DefaultWriteVectored::new(self, bufs)
+    }
+    fn flush(&'a mut self) -> Self::FlushFuture {
+        future::ready(Ok(()))
+    }
+}
+
+pin_project! {
+    /// Future for [`write`](AsyncWriteWith::write) on a [`Borrowed`].
+    pub struct WriteBorrowed<'a, T: OwnedAsyncWrite<Vec<u8>>> {
+        #[pin]
+        fut: <T as OwnedAsyncWriteWith<'a, Vec<u8>>>::WriteOwnedFuture,
+        write_buf: &'a mut Vec<u8>,
+    }
+}
+
+impl<T: OwnedAsyncWrite<Vec<u8>>> CompletionFuture for WriteBorrowed<'_, T> {
+    type Output = Result<usize>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let (res, owned) = ready!(this.fut.poll(cx));
+        **this.write_buf = owned;
+        Poll::Ready(res)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().fut.poll_cancel(cx)
+    }
+}
+impl<'a, T: OwnedAsyncWrite<Vec<u8>>> Future for WriteBorrowed<'a, T>
+where
+    <T as OwnedAsyncWriteWith<'a, Vec<u8>>>::WriteOwnedFuture:
+        Future<Output = (Result<usize>, Vec<u8>)>,
+{
+    type Output = Result<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::future::block_on;
+
+    use super::super::{test_utils::YieldingReader, AsyncWriteExt};
+
+    #[test]
+    fn owned() {
+        let mut reader = Owned::new(YieldingReader::new(vec![Ok("Hello"), Ok(" world")]));
+
+        let (res, buf) = block_on(reader.read_owned(Vec::with_capacity(3)));
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(buf, b"Hel");
+
+        let (res, buf) = block_on(reader.read_owned(vec![0; 10].into_boxed_slice()));
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!(&*buf, b"lo\0\0\0\0\0\0\0\0");
+
+        let mut writer = Owned::new(Vec::new());
+        let (res, buf) = block_on(writer.write_owned(&b"Hello"[..]));
+        assert_eq!(res.unwrap(), 5);
+        assert_eq!(buf, b"Hello");
+        assert_eq!(writer.into_inner(), b"Hello");
+    }
+
+    #[test]
+    fn borrowed() {
+        let mut reader = Borrowed::new(Owned::new(&b"Hello world"[..]));
+
+        let mut storage = [MaybeUninit::uninit(); 8];
+        let mut buf = ReadBuf::uninit(&mut storage);
+        block_on(reader.read(buf.as_mut())).unwrap();
+        assert_eq!(buf.filled(), b"Hello wo");
+
+        // The inner reader reads more than fits, and the rest is returned by the next read.
+        let mut storage = [MaybeUninit::uninit(); 2];
+        let mut buf = ReadBuf::uninit(&mut storage);
+        block_on(reader.read(buf.as_mut())).unwrap();
+        assert_eq!(buf.filled(), b"rl");
+        assert!(reader.get_ref().get_ref().is_empty());
+
+        let mut buf = ReadBuf::uninit(&mut storage);
+        block_on(reader.read(buf.as_mut())).unwrap();
+        assert_eq!(buf.filled(), b"d");
+
+        let mut buf = ReadBuf::uninit(&mut storage);
+        block_on(reader.read(buf.as_mut())).unwrap();
+        assert_eq!(buf.filled(), b"");
+
+        let mut writer = Borrowed::new(Owned::new(Vec::new()));
+        block_on(writer.write_all(b"Hello ")).unwrap();
+        block_on(writer.write_all(b"world")).unwrap();
+        block_on(writer.flush()).unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"Hello world");
+    }
+}
@@ -13,6 +13,13 @@
 //! - `alloc`: Enables features that require allocation, on by default.
 //! - `macro`: Enables the [`completion`], [`completion_async`], [`completion_async_move`] and
 //! [`completion_stream`] macros, on by default.
+//! - `io-uring`: Enables [`net`], and runs [`fs`] on `io_uring` when available. Implies `std`.
+//! - `bytes`: Implements the owned buffer traits in [`io`] for `Bytes` and `BytesMut`. Implies `std`.
+//!
+//! # Minimum Supported Rust Version
+//!
+//! This crate requires Rust 1.70 or newer, the first version with `std::sync::OnceLock`, which holds
+//! the global state of the blocking thread pool, the timer thread and the `io_uring` driver.
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(doc_cfg, feature(doc_cfg))]
 #![warn(
@@ -41,7 +48,9 @@ use core::pin::Pin;
 use core::task::{Context, Poll};
 
 #[doc(no_inline)]
-pub use completion_core::{CompletionFuture, CompletionStream};
+pub use completion_core::{
+    CompletionFuture, CompletionStream, FusedCompletionFuture, FusedCompletionStream,
+};
 use futures_core::Stream;
 use pin_project_lite::pin_project;
 
@@ -62,10 +71,46 @@ mod macros;
 #[cfg(feature = "macro")]
 pub use macros::*;
 
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod channel;
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod executor;
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod fs;
+
 #[cfg(feature = "std")]
 #[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod io;
 
+#[cfg(feature = "io-uring")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "io-uring")))]
+pub mod net;
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod sync;
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod time;
+
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod test;
+
+#[cfg(feature = "io-uring")]
+mod uring;
+
+#[cfg(feature = "std")]
+mod checked;
+#[cfg(feature = "std")]
+pub use checked::CheckedCompletion;
+
 pin_project! {
     /// Unsafely assert that the inner future or stream will complete.
     ///
@@ -277,11 +322,30 @@ impl<T: CompletionStream> CompletionStream for MustComplete<T> {
     }
 }
 
+/// Lock a mutex, ignoring poisoning.
+///
+/// Some of the crate's locks are held while running user code; for example, a task's future is
+/// polled and dropped while its mutex is locked. Panics from such code are either caught, or
+/// happen at a point where the protected data is consistent whether or not the code completed,
+/// so a poisoned lock can safely be used.
+#[cfg(feature = "std")]
+fn lock<T: ?Sized>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 #[cfg(test)]
 mod test_utils {
     use core::future::Future;
+    #[cfg(feature = "std")]
+    use core::marker::PhantomData;
     use core::pin::Pin;
+    #[cfg(feature = "std")]
+    use core::sync::atomic::{AtomicUsize, Ordering};
     use core::task::{Context, Poll, Waker};
+    #[cfg(feature = "std")]
+    use std::sync::Arc;
 
     use completion_core::CompletionFuture;
     use pin_project_lite::pin_project;
@@ -336,6 +400,99 @@ mod test_utils {
         }
     }
 
+    /// Counts of how a number of [`Forever`] futures have been polled.
+    #[cfg(feature = "std")]
+    #[derive(Debug, Default)]
+    pub(super) struct Polls {
+        polled: AtomicUsize,
+        cancel_polled: AtomicUsize,
+        cancelled: AtomicUsize,
+    }
+    #[cfg(feature = "std")]
+    impl Polls {
+        pub(super) fn new() -> Arc<Self> {
+            Arc::default()
+        }
+        /// The number of times the futures have been polled.
+        pub(super) fn polled(&self) -> usize {
+            self.polled.load(Ordering::SeqCst)
+        }
+        /// The number of times the futures have been polled to cancel them.
+        pub(super) fn cancel_polls(&self) -> usize {
+            self.cancel_polled.load(Ordering::SeqCst)
+        }
+        /// The number of futures that have finished cancelling.
+        pub(super) fn cancelled(&self) -> usize {
+            self.cancelled.load(Ordering::SeqCst)
+        }
+    }
+
+    /// A future that never completes by itself, and takes a few polls to cancel.
+    #[cfg(feature = "std")]
+    pub(super) struct Forever<T = ()> {
+        polls: Arc<Polls>,
+        yields: usize,
+        _output: PhantomData<fn() -> T>,
+    }
+    #[cfg(feature = "std")]
+    impl<T> Forever<T> {
+        pub(super) fn new(polls: &Arc<Polls>, yields: usize) -> Self {
+            Self {
+                polls: Arc::clone(polls),
+                yields,
+                _output: PhantomData,
+            }
+        }
+    }
+    #[cfg(feature = "std")]
+    impl<T> CompletionFuture for Forever<T> {
+        type Output = T;
+        unsafe fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.polls.polled.fetch_add(1, Ordering::SeqCst);
+            Poll::Pending
+        }
+        unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.polls.cancel_polled.fetch_add(1, Ordering::SeqCst);
+            if self.yields > 0 {
+                self.yields -= 1;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.polls.cancelled.fetch_add(1, Ordering::SeqCst);
+            Poll::Ready(())
+        }
+    }
+
+    /// Future that polls the inner future once, then cancels it.
+    #[cfg(feature = "std")]
+    pub(super) struct PollThenCancel<F> {
+        inner: F,
+        polled: bool,
+    }
+    #[cfg(feature = "std")]
+    impl<F> PollThenCancel<F> {
+        pub(super) fn new(inner: F) -> Self {
+            Self {
+                inner,
+                polled: false,
+            }
+        }
+    }
+    #[cfg(feature = "std")]
+    impl<F: CompletionFuture + Unpin> CompletionFuture for PollThenCancel<F> {
+        type Output = ();
+        unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if !self.polled {
+                self.polled = true;
+                assert!(Pin::new(&mut self.inner).poll(cx).is_pending());
+            }
+            Pin::new(&mut self.inner).poll_cancel(cx)
+        }
+        unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            unreachable!()
+        }
+    }
+
     pub(super) fn noop_waker() -> Waker {
         use core::ptr;
         use core::task::{RawWaker, RawWakerVTable};
@@ -3,7 +3,7 @@ use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
-use completion_core::CompletionFuture;
+use completion_core::{CompletionFuture, FusedCompletionFuture};
 use pin_project_lite::pin_project;
 
 #[cfg(test)]
@@ -189,7 +189,7 @@ thread_local! {
 
 /// Make a completion future async block.
 #[doc(hidden)]
-pub fn __completion_async<F: Future>(fut: F) -> impl CompletionFuture<Output = F::Output> {
+pub fn __completion_async<F: Future>(fut: F) -> impl FusedCompletionFuture<Output = F::Output> {
     pin_project! {
         #[doc(hidden)]
         struct Wrapper<F> {
@@ -197,6 +197,8 @@ pub fn __completion_async<F: Future>(fut: F) -> impl CompletionFuture<Output = F
             fut: F,
             // Whether we are currently `.await`ing on a completion future.
             awaiting_on_completion: bool,
+            // Whether the future has completed or finished cancelling.
+            terminated: bool,
         }
     }
     impl<F: Future> CompletionFuture for Wrapper<F> {
@@ -214,6 +216,8 @@ pub fn __completion_async<F: Future>(fut: F) -> impl CompletionFuture<Output = F
                     State::CompletionPollPending => true,
                     state => panic!("invalid state {:?}", state),
                 };
+            } else {
+                *this.terminated = true;
             }
             poll
         }
@@ -226,19 +230,25 @@ pub fn __completion_async<F: Future>(fut: F) -> impl CompletionFuture<Output = F
                 debug_assert!(poll.is_pending());
 
                 match STATE.with(Cell::get) {
-                    State::CancelReady => Poll::Ready(()),
-                    State::CancelPending => Poll::Pending,
+                    State::CancelReady => {}
+                    State::CancelPending => return Poll::Pending,
                     state => panic!("invalid state {:?}", state),
                 }
-            } else {
-                Poll::Ready(())
             }
+            *this.terminated = true;
+            Poll::Ready(())
+        }
+    }
+    impl<F: Future> FusedCompletionFuture for Wrapper<F> {
+        fn is_terminated(&self) -> bool {
+            self.terminated
         }
     }
 
     Wrapper {
         fut,
         awaiting_on_completion: false,
+        terminated: false,
     }
 }
 
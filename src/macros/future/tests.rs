@@ -35,7 +35,7 @@ use std::future::{self, Future};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use completion_core::CompletionFuture;
+use completion_core::{CompletionFuture, FusedCompletionFuture};
 use futures_lite::{future::yield_now, pin};
 
 use crate::test_utils;
@@ -205,9 +205,11 @@ fn cancel_completion_future() {
 
     assert_eq!(test_utils::poll_cancel_once(fut.as_mut()), false);
     assert_eq!(number.get(), 2);
+    assert!(!fut.is_terminated());
 
     assert_eq!(test_utils::poll_cancel_once(fut.as_mut()), true);
     assert_eq!(number.get(), 3);
+    assert!(fut.is_terminated());
 }
 
 #[test]
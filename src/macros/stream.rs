@@ -5,7 +5,8 @@ use core::pin::Pin;
 use core::ptr;
 use core::task::{Context, Poll};
 
-use completion_core::{CompletionFuture, CompletionStream};
+use completion_core::{CompletionFuture, CompletionStream, FusedCompletionStream};
+use futures_core::ready;
 use pin_project_lite::pin_project;
 
 #[doc(hidden)]
@@ -54,13 +55,14 @@ macro_rules! completion_stream {
 pub fn __completion_stream<T, F>(
     generator: F,
     item: PhantomData<T>,
-) -> impl CompletionStream<Item = T>
+) -> impl FusedCompletionStream<Item = T>
 where
     F: CompletionFuture<Output = ()>,
 {
     Wrapper {
         generator,
         _item: item,
+        terminated: false,
     }
 }
 
@@ -73,6 +75,8 @@ pin_project! {
         #[pin]
         generator: F,
         _item: PhantomData<T>,
+        // Whether the stream has been exhausted or finished cancelling.
+        terminated: bool,
     }
 }
 
@@ -101,13 +105,28 @@ where
 
         match (yielded, res) {
             (Some(yielded), Poll::Pending) => Poll::Ready(Some(yielded)),
-            (None, Poll::Ready(())) => Poll::Ready(None),
+            (None, Poll::Ready(())) => {
+                *this.terminated = true;
+                Poll::Ready(None)
+            }
             (None, Poll::Pending) => Poll::Pending,
             _ => unreachable!(),
         }
     }
     unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
-        self.project().generator.poll_cancel(cx)
+        let this = self.project();
+        ready!(this.generator.poll_cancel(cx));
+        *this.terminated = true;
+        Poll::Ready(())
+    }
+}
+
+impl<T, F> FusedCompletionStream for Wrapper<T, F>
+where
+    F: CompletionFuture<Output = ()>,
+{
+    fn is_terminated(&self) -> bool {
+        self.terminated
     }
 }
 
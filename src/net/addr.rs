@@ -0,0 +1,144 @@
+//! Conversions between socket addresses and their C representations.
+
+#![allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+
+use core::convert::TryFrom;
+use core::mem::{self, size_of, size_of_val};
+use core::ptr;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// A socket address in the form passed to the kernel.
+pub(super) struct RawAddr {
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+}
+
+impl RawAddr {
+    fn zeroed() -> Self {
+        Self {
+            // Safety: all-zero bytes are a valid `sockaddr_storage`.
+            storage: unsafe { mem::zeroed() },
+            len: 0,
+        }
+    }
+
+    /// Create space for the kernel to write an address into.
+    pub(super) fn empty() -> Self {
+        let mut raw = Self::zeroed();
+        raw.len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        raw
+    }
+
+    /// Get the storage as a specific address type.
+    fn as_ref<T>(&self) -> &T {
+        debug_assert!(size_of::<T>() <= size_of::<libc::sockaddr_storage>());
+        // Safety: `sockaddr_storage` is large and aligned enough to hold any address type, and
+        // they are all valid when zeroed.
+        unsafe { &*ptr::addr_of!(self.storage).cast::<T>() }
+    }
+
+    /// Get the storage as a specific address type.
+    fn as_mut<T>(&mut self) -> &mut T {
+        debug_assert!(size_of::<T>() <= size_of::<libc::sockaddr_storage>());
+        // Safety: `sockaddr_storage` is large and aligned enough to hold any address type, and
+        // they are all valid when zeroed.
+        unsafe { &mut *ptr::addr_of_mut!(self.storage).cast::<T>() }
+    }
+
+    /// Convert an internet address.
+    pub(super) fn from_inet(addr: SocketAddr) -> Self {
+        let mut raw = Self::zeroed();
+        match addr {
+            SocketAddr::V4(addr) => {
+                let sin = raw.as_mut::<libc::sockaddr_in>();
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+                raw.len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+            }
+            SocketAddr::V6(addr) => {
+                let sin6 = raw.as_mut::<libc::sockaddr_in6>();
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                raw.len = size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+            }
+        }
+        raw
+    }
+
+    /// Convert back to an internet address.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address is not an internet address.
+    pub(super) fn to_inet(&self) -> io::Result<SocketAddr> {
+        match self.domain() {
+            libc::AF_INET => {
+                let sin = self.as_ref::<libc::sockaddr_in>();
+                let ip = Ipv4Addr::from(sin.sin_addr.s_addr.to_ne_bytes());
+                Ok(SocketAddrV4::new(ip, u16::from_be(sin.sin_port)).into())
+            }
+            libc::AF_INET6 => {
+                let sin6 = self.as_ref::<libc::sockaddr_in6>();
+                let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+                let port = u16::from_be(sin6.sin6_port);
+                Ok(SocketAddrV6::new(ip, port, sin6.sin6_flowinfo, sin6.sin6_scope_id).into())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "address is not an internet address",
+            )),
+        }
+    }
+
+    /// Convert the path of a Unix domain socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path is too long.
+    pub(super) fn from_path(path: &Path) -> io::Result<Self> {
+        let mut raw = Self::zeroed();
+        let sun = raw.as_mut::<libc::sockaddr_un>();
+        sun.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+        let bytes = path.as_os_str().as_bytes();
+        // One byte is left for the null terminator.
+        if bytes.len() >= sun.sun_path.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "socket path is too long",
+            ));
+        }
+        for (dest, &byte) in sun.sun_path.iter_mut().zip(bytes) {
+            *dest = byte as libc::c_char;
+        }
+
+        // The path is the last field of the address.
+        let path_offset = size_of::<libc::sockaddr_un>() - size_of_val(&sun.sun_path);
+        raw.len = libc::socklen_t::try_from(path_offset + bytes.len() + 1).unwrap();
+        Ok(raw)
+    }
+
+    /// The address family, to create sockets with.
+    pub(super) fn domain(&self) -> libc::c_int {
+        libc::c_int::from(self.storage.ss_family)
+    }
+
+    pub(super) fn as_ptr(&self) -> *const libc::sockaddr {
+        ptr::addr_of!(self.storage).cast()
+    }
+
+    pub(super) fn as_mut_ptr(&mut self) -> *mut libc::sockaddr {
+        ptr::addr_of_mut!(self.storage).cast()
+    }
+
+    pub(super) fn len(&self) -> libc::socklen_t {
+        self.len
+    }
+}
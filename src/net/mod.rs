@@ -0,0 +1,296 @@
+//! Networking with TCP, UDP and Unix domain sockets.
+//!
+//! Operations are submitted to a shared `io_uring` instance, and reads and writes borrow the
+//! caller's buffers directly. Creating or converting a socket fails with an error if `io_uring` is
+//! not available on the system.
+//!
+//! Cancelling a read that has already received data fills the buffer with that data instead of
+//! discarding it, so no data is lost. Likewise, cancelling an [`Incoming`](tcp::Incoming) stream
+//! that has already accepted a connection keeps it for the stream's next item; a connection
+//! accepted by a cancelled [`accept`](tcp::TcpListener::accept) future is closed instead.
+
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Formatter};
+use core::pin::Pin;
+use core::ptr;
+use core::task::{Context, Poll};
+use std::io::{self, Result};
+use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+
+use completion_core::CompletionFuture;
+use completion_io::ReadBufMut;
+use futures_core::ready;
+use io_uring::{opcode, types};
+
+use crate::uring::{Driver, Op};
+
+mod addr;
+use addr::RawAddr;
+
+pub mod tcp;
+pub use tcp::{TcpListener, TcpStream};
+
+pub mod udp;
+pub use udp::UdpSocket;
+
+pub mod unix;
+pub use unix::{UnixListener, UnixStream};
+
+/// Convert the result of an operation that creates a file descriptor.
+fn owned_fd(res: Result<u32>) -> Result<OwnedFd> {
+    // Safety: the kernel just created the file descriptor, so nothing else owns it.
+    #[allow(clippy::cast_possible_wrap)]
+    res.map(|fd| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+/// Future for reading from a [`TcpStream`] or a [`UnixStream`], or for [`UdpSocket::recv`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct ReadSocket<'a> {
+    fd: RawFd,
+    driver: &'a Arc<Driver>,
+    buf: ReadBufMut<'a>,
+    op: Option<Op>,
+}
+
+impl<'a> ReadSocket<'a> {
+    fn new(fd: RawFd, driver: &'a Arc<Driver>, buf: ReadBufMut<'a>) -> Self {
+        Self {
+            fd,
+            driver,
+            buf,
+            op: None,
+        }
+    }
+
+    fn fill(&mut self, n: u32) {
+        let n = n as usize;
+        // Safety: the kernel initialized this many bytes.
+        unsafe { self.buf.assume_init(n) };
+        self.buf.add_filled(n);
+    }
+}
+
+impl CompletionFuture for ReadSocket<'_> {
+    type Output = Result<()>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let unfilled = this.buf.unfilled_mut();
+            let len = u32::try_from(unfilled.len()).unwrap_or(u32::MAX);
+            let entry =
+                opcode::Recv::new(types::Fd(this.fd), unfilled.as_mut_ptr().cast(), len).build();
+            // Safety: the socket and buffer are borrowed for the lifetime of the future, which
+            // will not be dropped while the operation runs.
+            match Op::submit(Arc::clone(this.driver), entry) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        this.fill(res?);
+        Poll::Ready(Ok(()))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if let Some(op) = &mut this.op {
+            let res = ready!(op.poll_cancel(cx));
+            this.op = None;
+            // Data that was received before the operation could be cancelled can't be put back,
+            // so keep it in the buffer.
+            if let Ok(n) = res {
+                this.fill(n);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for ReadSocket<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadSocket")
+            .field("fd", &self.fd)
+            .field("buf", &self.buf)
+            .field("running", &self.op.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future for writing to a [`TcpStream`] or a [`UnixStream`], or for [`UdpSocket::send`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct WriteSocket<'a> {
+    fd: RawFd,
+    driver: &'a Arc<Driver>,
+    buf: &'a [u8],
+    op: Option<Op>,
+}
+
+impl<'a> WriteSocket<'a> {
+    fn new(fd: RawFd, driver: &'a Arc<Driver>, buf: &'a [u8]) -> Self {
+        Self {
+            fd,
+            driver,
+            buf,
+            op: None,
+        }
+    }
+}
+
+impl CompletionFuture for WriteSocket<'_> {
+    type Output = Result<usize>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let len = u32::try_from(this.buf.len()).unwrap_or(u32::MAX);
+            let entry = opcode::Send::new(types::Fd(this.fd), this.buf.as_ptr(), len)
+                .flags(libc::MSG_NOSIGNAL)
+                .build();
+            // Safety: the socket and buffer are borrowed for the lifetime of the future, which
+            // will not be dropped while the operation runs.
+            match Op::submit(Arc::clone(this.driver), entry) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        Poll::Ready(res.map(|n| n as usize))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(op) = &mut self.op {
+            // Whatever was sent before the operation could be cancelled stays sent.
+            let _ = ready!(op.poll_cancel(cx));
+            self.op = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for WriteSocket<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteSocket")
+            .field("fd", &self.fd)
+            .field("buf", &self.buf)
+            .field("running", &self.op.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Accepting a connection on a listening socket, shared by the accept futures and streams.
+#[derive(Debug, Default)]
+struct AcceptOp {
+    op: Option<Op>,
+    /// A connection that was accepted while the operation was being cancelled.
+    accepted: Option<OwnedFd>,
+}
+
+impl AcceptOp {
+    /// # Safety
+    ///
+    /// The listener must remain open until the operation completes or is dropped.
+    unsafe fn poll(
+        &mut self,
+        fd: RawFd,
+        driver: &Arc<Driver>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<OwnedFd>> {
+        if let Some(accepted) = self.accepted.take() {
+            return Poll::Ready(Ok(accepted));
+        }
+        if self.op.is_none() {
+            let entry = opcode::Accept::new(types::Fd(fd), ptr::null_mut(), ptr::null_mut())
+                .flags(libc::SOCK_CLOEXEC)
+                .build();
+            match Op::submit(Arc::clone(driver), entry) {
+                Ok(op) => self.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(self.op.as_mut().unwrap().poll(cx));
+        self.op = None;
+        Poll::Ready(owned_fd(res))
+    }
+
+    fn poll_cancel(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(op) = &mut self.op {
+            let res = ready!(op.poll_cancel(cx));
+            self.op = None;
+            self.accepted = owned_fd(res).ok();
+        }
+        Poll::Ready(())
+    }
+}
+
+/// Connecting a new socket to an address, shared by the connect futures.
+#[derive(Default)]
+struct ConnectOp {
+    /// Declared first so that it is dropped, blocking until the operation has been cancelled,
+    /// before the socket and address it uses.
+    op: Option<Op>,
+    socket: Option<(OwnedFd, Arc<Driver>)>,
+    /// The address, which must outlive the operation.
+    addr: Option<Box<RawAddr>>,
+}
+
+impl ConnectOp {
+    fn start(&mut self, addr: RawAddr) -> Result<()> {
+        let driver = Driver::get()?;
+
+        // Safety: `socket` is always safe to call.
+        let fd = unsafe { libc::socket(addr.domain(), libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: the socket was just created.
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        let addr = &**self.addr.insert(Box::new(addr));
+
+        let entry = opcode::Connect::new(types::Fd(fd), addr.as_ptr(), addr.len()).build();
+        // Safety: the socket and the address live in `self`, and are dropped after the operation.
+        self.op = Some(unsafe { Op::submit(Arc::clone(&driver), entry) }?);
+        self.socket = Some((socket, driver));
+        Ok(())
+    }
+
+    /// Poll the connection, converting the address with `addr` when it is first polled.
+    fn poll(
+        &mut self,
+        addr: impl FnOnce() -> Result<RawAddr>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(OwnedFd, Arc<Driver>)>> {
+        if self.op.is_none() {
+            if let Err(e) = addr().and_then(|addr| self.start(addr)) {
+                return Poll::Ready(Err(e));
+            }
+        }
+
+        let res = ready!(self.op.as_mut().unwrap().poll(cx));
+        self.op = None;
+        let socket = self.socket.take().unwrap();
+        Poll::Ready(res.map(|_| socket))
+    }
+
+    fn poll_cancel(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(op) = &mut self.op {
+            // The socket is closed whether or not it connected.
+            let _ = ready!(op.poll_cancel(cx));
+            self.op = None;
+            self.socket = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for ConnectOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectOp")
+            .field("running", &self.op.is_some())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,479 @@
+//! TCP sockets.
+
+use core::fmt::{self, Debug, Formatter};
+use core::future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io::{IoSlice, Result};
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+
+use completion_core::{CompletionFuture, CompletionStream};
+use completion_io::{AsyncReadWith, AsyncWriteWith, DefaultWriteVectored, ReadBufMut};
+use futures_core::ready;
+
+use super::{AcceptOp, ConnectOp, RawAddr, ReadSocket, WriteSocket};
+use crate::uring::Driver;
+
+/// A TCP socket server, listening for connections.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{net::TcpListener, io::AsyncWriteExt, CompletionStreamExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let listener = TcpListener::bind("127.0.0.1:8080")?;
+/// let mut incoming = listener.incoming();
+/// while let Some(stream) = incoming.next().await {
+///     stream?.write_all(b"Hello world!").await?;
+/// }
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct TcpListener {
+    inner: net::TcpListener,
+    driver: Arc<Driver>,
+}
+
+impl TcpListener {
+    /// Create a listener bound to the given address.
+    ///
+    /// If the address resolves to multiple addresses, each one is tried in turn until one
+    /// succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the listener cannot be bound or `io_uring` is not available.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Self::from_std(net::TcpListener::bind(addr)?)
+    }
+
+    /// Convert a standard library listener to a `TcpListener`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available.
+    pub fn from_std(listener: net::TcpListener) -> Result<Self> {
+        Ok(Self {
+            inner: listener,
+            driver: Driver::get()?,
+        })
+    }
+
+    /// Convert this listener to a standard library listener.
+    #[must_use]
+    pub fn into_std(self) -> net::TcpListener {
+        self.inner
+    }
+
+    /// Get the local address that this listener is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Accept a new connection, resolving to the stream and the address of the peer.
+    ///
+    /// If the future is cancelled after a connection has been accepted, the connection is closed.
+    pub fn accept(&self) -> Accept<'_> {
+        Accept {
+            listener: self,
+            op: AcceptOp::default(),
+        }
+    }
+
+    /// Get a stream of the connections made to this listener.
+    ///
+    /// Unlike [`accept`](Self::accept), cancelling the stream keeps a connection that was accepted
+    /// while it was being cancelled, and yields it next.
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming {
+            listener: self,
+            op: AcceptOp::default(),
+        }
+    }
+
+    /// Set the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Get the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl IntoRawFd for TcpListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for TcpListener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpListener")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future for [`TcpListener::accept`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Accept<'a> {
+    listener: &'a TcpListener,
+    op: AcceptOp,
+}
+
+impl CompletionFuture for Accept<'_> {
+    type Output = Result<(TcpStream, SocketAddr)>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let listener = this.listener;
+        let fd = ready!(this.op.poll(listener.as_raw_fd(), &listener.driver, cx));
+        Poll::Ready(fd.and_then(|fd| {
+            let stream = TcpStream::from_fd(fd, Arc::clone(&listener.driver));
+            let addr = stream.peer_addr()?;
+            Ok((stream, addr))
+        }))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+/// Stream for [`TcpListener::incoming`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless you use them"]
+pub struct Incoming<'a> {
+    listener: &'a TcpListener,
+    op: AcceptOp,
+}
+
+impl CompletionStream for Incoming<'_> {
+    type Item = Result<TcpStream>;
+
+    unsafe fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let listener = this.listener;
+        let fd = ready!(this.op.poll(listener.as_raw_fd(), &listener.driver, cx));
+        Poll::Ready(Some(
+            fd.map(|fd| TcpStream::from_fd(fd, Arc::clone(&listener.driver))),
+        ))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+/// A TCP stream between a local and a remote socket.
+///
+/// Cancelling a read that has already received data fills the buffer with that data, so that it
+/// is not lost.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{net::TcpStream, io::AsyncWriteExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let mut stream = TcpStream::connect("127.0.0.1:8080".parse().unwrap()).await?;
+/// stream.write_all(b"Hello world!").await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct TcpStream {
+    inner: net::TcpStream,
+    driver: Arc<Driver>,
+}
+
+impl TcpStream {
+    /// Open a TCP connection to a remote host.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the connection cannot be made or `io_uring` is not available.
+    pub fn connect(addr: SocketAddr) -> Connect {
+        Connect {
+            addr,
+            op: ConnectOp::default(),
+        }
+    }
+
+    /// Convert a standard library stream to a `TcpStream`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available.
+    pub fn from_std(stream: net::TcpStream) -> Result<Self> {
+        Ok(Self {
+            inner: stream,
+            driver: Driver::get()?,
+        })
+    }
+
+    fn from_fd(fd: OwnedFd, driver: Arc<Driver>) -> Self {
+        Self {
+            inner: fd.into(),
+            driver,
+        }
+    }
+
+    /// Convert this stream to a standard library stream.
+    #[must_use]
+    pub fn into_std(self) -> net::TcpStream {
+        self.inner
+    }
+
+    /// Get the local address of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Get the address of the remote peer of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Shut down the read half, write half or both halves of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the stream is not connected.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Set the value of the `TCP_NODELAY` option, which disables Nagle's algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    /// Get the value of the `TCP_NODELAY` option.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    /// Set the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Get the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl IntoRawFd for TcpStream {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for TcpStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpStream")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> AsyncReadWith<'a> for TcpStream {
+    type ReadFuture = ReadSocket<'a>;
+
+    fn read(&'a mut self, buf: ReadBufMut<'a>) -> Self::ReadFuture {
+        ReadSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+}
+
+impl<'a> AsyncWriteWith<'a> for TcpStream {
+    type WriteFuture = WriteSocket<'a>;
+    type WriteVectoredFuture = DefaultWriteVectored<'a, Self>;
+    type FlushFuture = future::Ready<Result<()>>;
+
+    fn write(&'a mut self, buf: &'a [u8]) -> Self::WriteFuture {
+        WriteSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+    fn write_vectored(&'a mut self, bufs: &'a [IoSlice<'a>]) -> Self::WriteVectoredFuture {
+        DefaultWriteVectored::new(self, bufs)
+    }
+    fn flush(&'a mut self) -> Self::FlushFuture {
+        future::ready(Ok(()))
+    }
+}
+
+/// Future for [`TcpStream::connect`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Connect {
+    addr: SocketAddr,
+    op: ConnectOp,
+}
+
+impl CompletionFuture for Connect {
+    type Output = Result<TcpStream>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let addr = this.addr;
+        let res = ready!(this.op.poll(|| Ok(RawAddr::from_inet(addr)), cx));
+        Poll::Ready(res.map(|(fd, driver)| TcpStream::from_fd(fd, driver)))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::mem::MaybeUninit;
+
+    use completion_io::ReadBuf;
+
+    use crate::future::block_on;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::test_utils::{poll_cancel_once, poll_once};
+    use crate::{uring, CompletionStreamExt};
+
+    #[test]
+    fn loopback() {
+        if !uring::available() {
+            return;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = block_on(TcpStream::connect(addr)).unwrap();
+        let (mut server, peer) = block_on(listener.accept()).unwrap();
+        assert_eq!(peer, client.local_addr().unwrap());
+        assert_eq!(server.peer_addr().unwrap(), client.local_addr().unwrap());
+
+        block_on(client.write_all(b"ping")).unwrap();
+        let mut bytes = [0; 4];
+        block_on(server.read_exact(ReadBuf::new(&mut bytes).as_mut())).unwrap();
+        assert_eq!(&bytes, b"ping");
+
+        block_on(server.write_all(b"pong")).unwrap();
+        server.shutdown(Shutdown::Write).unwrap();
+        let mut received = Vec::new();
+        block_on(client.read_to_end(&mut received)).unwrap();
+        assert_eq!(received, b"pong");
+
+        // Connections can also be accepted from a stream.
+        let _client = block_on(TcpStream::connect(addr)).unwrap();
+        let mut incoming = listener.incoming();
+        assert!(block_on(incoming.next()).unwrap().is_ok());
+
+        // Nothing is listening on the listener's address once it has closed.
+        drop(incoming);
+        drop(listener);
+        assert!(block_on(TcpStream::connect(addr)).is_err());
+    }
+
+    #[test]
+    fn cancel() {
+        if !uring::available() {
+            return;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Cancelling an accept that is waiting for a connection.
+        let mut accept = listener.accept();
+        assert!(poll_once(&mut accept).is_none());
+        while !poll_cancel_once(&mut accept) {
+            std::thread::yield_now();
+        }
+        drop(accept);
+
+        let mut client = block_on(TcpStream::connect(addr)).unwrap();
+        let (mut server, _) = block_on(listener.accept()).unwrap();
+
+        // Cancelling a read that is waiting for data.
+        let mut bytes = [MaybeUninit::uninit(); 4];
+        let mut buf = ReadBuf::uninit(&mut bytes);
+        let mut read = server.read(buf.as_mut());
+        assert!(poll_once(&mut read).is_none());
+        while !poll_cancel_once(&mut read) {
+            std::thread::yield_now();
+        }
+        drop(read);
+        assert_eq!(buf.as_mut().filled(), b"");
+
+        // The stream is still usable.
+        block_on(client.write_all(b"data")).unwrap();
+        block_on(server.read_exact(buf.as_mut())).unwrap();
+        assert_eq!(buf.as_mut().filled(), b"data");
+    }
+}
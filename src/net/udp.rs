@@ -0,0 +1,572 @@
+//! UDP sockets.
+
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::pin::Pin;
+use core::ptr;
+use core::task::{Context, Poll};
+use std::io::Result;
+use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use std::sync::Arc;
+
+use completion_core::CompletionFuture;
+use completion_io::ReadBufMut;
+use futures_core::ready;
+use io_uring::{opcode, types};
+
+use super::{RawAddr, ReadSocket, WriteSocket};
+use crate::uring::{Driver, Op};
+
+/// A UDP socket.
+///
+/// Receiving futures borrow the caller's buffer until they complete or their cancellation
+/// completes, so a cancelled receive never writes into a buffer after the caller has got it back.
+/// A datagram that was received before a receive could be cancelled is kept in the buffer, but
+/// [`recv_from`](Self::recv_from) loses the address it was sent from.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{net::UdpSocket, io::ReadBuf, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let socket = UdpSocket::bind("127.0.0.1:8080")?;
+///
+/// let mut bytes = [0; 1024];
+/// let mut buf = ReadBuf::new(&mut bytes);
+/// let peer = socket.recv_from(buf.as_mut()).await?;
+/// socket.send_to(buf.as_mut().filled(), peer).await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct UdpSocket {
+    inner: net::UdpSocket,
+    driver: Arc<Driver>,
+}
+
+impl UdpSocket {
+    /// Create a socket bound to the given address.
+    ///
+    /// If the address resolves to multiple addresses, each one is tried in turn until one
+    /// succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the socket cannot be bound or `io_uring` is not available.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Self::from_std(net::UdpSocket::bind(addr)?)
+    }
+
+    /// Convert a standard library socket to a `UdpSocket`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available.
+    pub fn from_std(socket: net::UdpSocket) -> Result<Self> {
+        Ok(Self {
+            inner: socket,
+            driver: Driver::get()?,
+        })
+    }
+
+    /// Convert this socket to a standard library socket.
+    #[must_use]
+    pub fn into_std(self) -> net::UdpSocket {
+        self.inner
+    }
+
+    /// Get the local address that this socket is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Get the address of the remote peer this socket is connected to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the socket is not connected.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Connect this socket to a remote address, so that [`send`](Self::send) and
+    /// [`recv`](Self::recv) can be used and datagrams from other addresses are discarded.
+    ///
+    /// Connecting a UDP socket doesn't send anything, so this completes immediately.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address is invalid.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<()> {
+        self.inner.connect(addr)
+    }
+
+    /// Send a datagram to the given address, resolving to the number of bytes sent.
+    pub fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> SendTo<'a> {
+        SendTo {
+            op: None,
+            msg: None,
+            socket: self,
+            buf,
+            target,
+        }
+    }
+
+    /// Receive a datagram into the buffer, resolving to the address it was sent from.
+    ///
+    /// If the datagram is too long to fit in the buffer, the excess bytes are discarded.
+    pub fn recv_from<'a>(&'a self, buf: ReadBufMut<'a>) -> RecvFrom<'a> {
+        RecvFrom {
+            op: None,
+            msg: None,
+            socket: self,
+            buf,
+        }
+    }
+
+    /// Send a datagram to the connected peer, resolving to the number of bytes sent.
+    pub fn send<'a>(&'a self, buf: &'a [u8]) -> WriteSocket<'a> {
+        WriteSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+
+    /// Receive a datagram from the connected peer into the buffer.
+    ///
+    /// If the datagram is too long to fit in the buffer, the excess bytes are discarded.
+    pub fn recv<'a>(&'a self, buf: ReadBufMut<'a>) -> ReadSocket<'a> {
+        ReadSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+
+    /// Set whether this socket may send to broadcast addresses.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_broadcast(&self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    /// Get whether this socket may send to broadcast addresses.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    /// Set the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Get the time-to-live of packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    /// Join an IPv4 multicast group on the interface with the given address.
+    ///
+    /// [`Ipv4Addr::UNSPECIFIED`] lets the system choose the interface.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the group cannot be joined.
+    pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(&multiaddr, &interface)
+    }
+
+    /// Leave an IPv4 multicast group on the interface with the given address.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the group cannot be left.
+    pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(&multiaddr, &interface)
+    }
+
+    /// Join an IPv6 multicast group on the interface with the given index.
+    ///
+    /// An index of 0 lets the system choose the interface.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the group cannot be joined.
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, interface)
+    }
+
+    /// Leave an IPv6 multicast group on the interface with the given index.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the group cannot be left.
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, interface)
+    }
+
+    /// Set whether IPv4 multicast packets sent from this socket are looped back to the local
+    /// host.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_multicast_loop_v4(&self, multicast_loop: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(multicast_loop)
+    }
+
+    /// Get whether IPv4 multicast packets sent from this socket are looped back to the local
+    /// host.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    /// Set the time-to-live of IPv4 multicast packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    /// Get the time-to-live of IPv4 multicast packets sent from this socket.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    /// Set whether IPv6 multicast packets sent from this socket are looped back to the local
+    /// host.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be set.
+    pub fn set_multicast_loop_v6(&self, multicast_loop: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(multicast_loop)
+    }
+
+    /// Get whether IPv6 multicast packets sent from this socket are looped back to the local
+    /// host.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the option cannot be queried.
+    pub fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl IntoRawFd for UdpSocket {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for UdpSocket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A message header for `sendmsg` and `recvmsg`, boxed together with the address and the buffer
+/// description it points to so that they don't move while the kernel uses them.
+struct Msg {
+    header: libc::msghdr,
+    iov: libc::iovec,
+    addr: RawAddr,
+}
+
+// Safety: the pointers in the header only point into the message itself, and to a buffer that is
+// borrowed by the future owning the message.
+unsafe impl Send for Msg {}
+// Safety: as above.
+unsafe impl Sync for Msg {}
+
+impl Msg {
+    fn new(addr: RawAddr, buf: *mut u8, len: usize) -> Box<Self> {
+        let mut msg = Box::new(Self {
+            // Safety: all-zero bytes are a valid `msghdr`.
+            header: unsafe { mem::zeroed() },
+            iov: libc::iovec {
+                iov_base: buf.cast(),
+                iov_len: len,
+            },
+            addr,
+        });
+        msg.header.msg_name = msg.addr.as_mut_ptr().cast();
+        msg.header.msg_namelen = msg.addr.len();
+        msg.header.msg_iov = ptr::addr_of_mut!(msg.iov);
+        msg.header.msg_iovlen = 1;
+        msg
+    }
+}
+
+/// Future for [`UdpSocket::send_to`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct SendTo<'a> {
+    /// Declared first so that it is dropped, blocking until the operation has been cancelled,
+    /// before the message it uses.
+    op: Option<Op>,
+    msg: Option<Box<Msg>>,
+    socket: &'a UdpSocket,
+    buf: &'a [u8],
+    target: SocketAddr,
+}
+
+impl CompletionFuture for SendTo<'_> {
+    type Output = Result<usize>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let msg = this.msg.insert(Msg::new(
+                RawAddr::from_inet(this.target),
+                this.buf.as_ptr().cast_mut(),
+                this.buf.len(),
+            ));
+            let entry = opcode::SendMsg::new(
+                types::Fd(this.socket.as_raw_fd()),
+                ptr::addr_of!(msg.header),
+            )
+            .build();
+            // Safety: the message lives in the future and the buffer is borrowed for its
+            // lifetime, and the future will not be dropped while the operation runs.
+            match Op::submit(Arc::clone(&this.socket.driver), entry) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        this.msg = None;
+        Poll::Ready(res.map(|n| n as usize))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(op) = &mut self.op {
+            // A datagram that was sent before the operation could be cancelled stays sent.
+            let _ = ready!(op.poll_cancel(cx));
+            self.op = None;
+            self.msg = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for SendTo<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendTo")
+            .field("socket", &self.socket)
+            .field("buf", &self.buf)
+            .field("target", &self.target)
+            .field("running", &self.op.is_some())
+            .finish()
+    }
+}
+
+/// Future for [`UdpSocket::recv_from`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct RecvFrom<'a> {
+    /// Declared first so that it is dropped, blocking until the operation has been cancelled,
+    /// before the message it uses.
+    op: Option<Op>,
+    msg: Option<Box<Msg>>,
+    socket: &'a UdpSocket,
+    buf: ReadBufMut<'a>,
+}
+
+impl RecvFrom<'_> {
+    fn fill(&mut self, n: u32) {
+        let n = n as usize;
+        // Safety: the kernel initialized this many bytes.
+        unsafe { self.buf.assume_init(n) };
+        self.buf.add_filled(n);
+    }
+}
+
+impl CompletionFuture for RecvFrom<'_> {
+    type Output = Result<SocketAddr>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.op.is_none() {
+            let unfilled = this.buf.unfilled_mut();
+            let msg = this.msg.insert(Msg::new(
+                RawAddr::empty(),
+                unfilled.as_mut_ptr().cast(),
+                unfilled.len(),
+            ));
+            let entry = opcode::RecvMsg::new(
+                types::Fd(this.socket.as_raw_fd()),
+                ptr::addr_of_mut!(msg.header),
+            )
+            .build();
+            // Safety: the message lives in the future and the buffer is borrowed for its
+            // lifetime, and the future will not be dropped while the operation runs.
+            match Op::submit(Arc::clone(&this.socket.driver), entry) {
+                Ok(op) => this.op = Some(op),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        let res = ready!(this.op.as_mut().unwrap().poll(cx));
+        this.op = None;
+        let msg = this.msg.take().unwrap();
+        let n = res?;
+        let addr = msg.addr.to_inet()?;
+        this.fill(n);
+        Poll::Ready(Ok(addr))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if let Some(op) = &mut this.op {
+            let res = ready!(op.poll_cancel(cx));
+            this.op = None;
+            this.msg = None;
+            // A datagram that was received before the operation could be cancelled can't be put
+            // back, so keep it in the buffer.
+            if let Ok(n) = res {
+                this.fill(n);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Debug for RecvFrom<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecvFrom")
+            .field("socket", &self.socket)
+            .field("buf", &self.buf)
+            .field("running", &self.op.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use completion_io::ReadBuf;
+
+    use crate::future::block_on;
+    use crate::test_utils::{poll_cancel_once, poll_once};
+    use crate::uring;
+
+    #[test]
+    fn send_recv() {
+        if !uring::available() {
+            return;
+        }
+
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b_addr = b.local_addr().unwrap();
+
+        assert_eq!(block_on(a.send_to(b"ping", b_addr)).unwrap(), 4);
+        let mut bytes = [0; 16];
+        let mut buf = ReadBuf::new(&mut bytes);
+        assert_eq!(block_on(b.recv_from(buf.as_mut())).unwrap(), a_addr);
+        assert_eq!(buf.as_mut().filled(), b"ping");
+
+        // Excess bytes are discarded.
+        block_on(b.send_to(b"too long", a_addr)).unwrap();
+        let mut short = [0; 3];
+        let mut buf = ReadBuf::new(&mut short);
+        assert_eq!(block_on(a.recv_from(buf.as_mut())).unwrap(), b_addr);
+        assert_eq!(buf.as_mut().filled(), b"too");
+
+        a.connect(b_addr).unwrap();
+        b.connect(a_addr).unwrap();
+        assert_eq!(a.peer_addr().unwrap(), b_addr);
+        block_on(b.send(b"pong")).unwrap();
+        let mut buf = ReadBuf::new(&mut bytes);
+        block_on(a.recv(buf.as_mut())).unwrap();
+        assert_eq!(buf.as_mut().filled(), b"pong");
+    }
+
+    #[test]
+    fn cancel_recv_from() {
+        if !uring::available() {
+            return;
+        }
+
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b_addr = b.local_addr().unwrap();
+
+        let mut bytes = [0; 16];
+        let mut buf = ReadBuf::new(&mut bytes);
+        let mut recv = b.recv_from(buf.as_mut());
+        assert!(poll_once(&mut recv).is_none());
+        while !poll_cancel_once(&mut recv) {
+            std::thread::yield_now();
+        }
+        drop(recv);
+        assert_eq!(buf.as_mut().filled(), b"");
+
+        // The cancelled receive doesn't write into the buffer once it has been reused.
+        bytes = [0xAA; 16];
+        block_on(a.send_to(b"data", b_addr)).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(bytes, [0xAA; 16]);
+
+        // The datagram is still there to receive.
+        let mut buf = ReadBuf::new(&mut bytes);
+        block_on(b.recv_from(buf.as_mut())).unwrap();
+        assert_eq!(buf.as_mut().filled(), b"data");
+    }
+
+    #[test]
+    fn options() {
+        if !uring::available() {
+            return;
+        }
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_broadcast(true).unwrap();
+        assert!(socket.broadcast().unwrap());
+        socket.set_multicast_loop_v4(false).unwrap();
+        assert!(!socket.multicast_loop_v4().unwrap());
+        socket.set_multicast_ttl_v4(8).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 8);
+        assert!(socket.peer_addr().is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Unix domain sockets.
+
+use core::fmt::{self, Debug, Formatter};
+use core::future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io::{IoSlice, Result};
+use std::net::Shutdown;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{self, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use completion_core::{CompletionFuture, CompletionStream};
+use completion_io::{AsyncReadWith, AsyncWriteWith, DefaultWriteVectored, ReadBufMut};
+use futures_core::ready;
+
+use super::{AcceptOp, ConnectOp, RawAddr, ReadSocket, WriteSocket};
+use crate::uring::Driver;
+
+/// A Unix domain socket server, listening for connections.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{net::UnixListener, io::AsyncWriteExt, CompletionStreamExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let listener = UnixListener::bind("/tmp/socket")?;
+/// let mut incoming = listener.incoming();
+/// while let Some(stream) = incoming.next().await {
+///     stream?.write_all(b"Hello world!").await?;
+/// }
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct UnixListener {
+    inner: net::UnixListener,
+    driver: Arc<Driver>,
+}
+
+impl UnixListener {
+    /// Create a listener bound to the given path.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the listener cannot be bound or `io_uring` is not available.
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_std(net::UnixListener::bind(path)?)
+    }
+
+    /// Convert a standard library listener to a `UnixListener`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available.
+    pub fn from_std(listener: net::UnixListener) -> Result<Self> {
+        Ok(Self {
+            inner: listener,
+            driver: Driver::get()?,
+        })
+    }
+
+    /// Convert this listener to a standard library listener.
+    #[must_use]
+    pub fn into_std(self) -> net::UnixListener {
+        self.inner
+    }
+
+    /// Get the local address that this listener is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Accept a new connection, resolving to the stream and the address of the peer.
+    ///
+    /// If the future is cancelled after a connection has been accepted, the connection is closed.
+    pub fn accept(&self) -> Accept<'_> {
+        Accept {
+            listener: self,
+            op: AcceptOp::default(),
+        }
+    }
+
+    /// Get a stream of the connections made to this listener.
+    ///
+    /// Unlike [`accept`](Self::accept), cancelling the stream keeps a connection that was accepted
+    /// while it was being cancelled, and yields it next.
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming {
+            listener: self,
+            op: AcceptOp::default(),
+        }
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl IntoRawFd for UnixListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for UnixListener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnixListener")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future for [`UnixListener::accept`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Accept<'a> {
+    listener: &'a UnixListener,
+    op: AcceptOp,
+}
+
+impl CompletionFuture for Accept<'_> {
+    type Output = Result<(UnixStream, SocketAddr)>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let listener = this.listener;
+        let fd = ready!(this.op.poll(listener.as_raw_fd(), &listener.driver, cx));
+        Poll::Ready(fd.and_then(|fd| {
+            let stream = UnixStream::from_fd(fd, Arc::clone(&listener.driver));
+            let addr = stream.peer_addr()?;
+            Ok((stream, addr))
+        }))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+/// Stream for [`UnixListener::incoming`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless you use them"]
+pub struct Incoming<'a> {
+    listener: &'a UnixListener,
+    op: AcceptOp,
+}
+
+impl CompletionStream for Incoming<'_> {
+    type Item = Result<UnixStream>;
+
+    unsafe fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let listener = this.listener;
+        let fd = ready!(this.op.poll(listener.as_raw_fd(), &listener.driver, cx));
+        Poll::Ready(Some(
+            fd.map(|fd| UnixStream::from_fd(fd, Arc::clone(&listener.driver))),
+        ))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+/// A Unix domain socket stream.
+///
+/// Cancelling a read that has already received data fills the buffer with that data, so that it
+/// is not lost.
+///
+/// # Examples
+///
+/// ```no_run
+/// use completion::{net::UnixStream, io::AsyncWriteExt, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let mut stream = UnixStream::connect("/tmp/socket").await?;
+/// stream.write_all(b"Hello world!").await?;
+/// # completion::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+pub struct UnixStream {
+    inner: net::UnixStream,
+    driver: Arc<Driver>,
+}
+
+impl UnixStream {
+    /// Connect to the socket at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the connection cannot be made or `io_uring` is not available.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Connect {
+        Connect {
+            path: path.as_ref().to_owned(),
+            op: ConnectOp::default(),
+        }
+    }
+
+    /// Create an unnamed pair of connected sockets.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the sockets cannot be created or `io_uring` is not available.
+    pub fn pair() -> Result<(Self, Self)> {
+        let (a, b) = net::UnixStream::pair()?;
+        Ok((Self::from_std(a)?, Self::from_std(b)?))
+    }
+
+    /// Convert a standard library stream to a `UnixStream`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available.
+    pub fn from_std(stream: net::UnixStream) -> Result<Self> {
+        Ok(Self {
+            inner: stream,
+            driver: Driver::get()?,
+        })
+    }
+
+    fn from_fd(fd: OwnedFd, driver: Arc<Driver>) -> Self {
+        Self {
+            inner: fd.into(),
+            driver,
+        }
+    }
+
+    /// Convert this stream to a standard library stream.
+    #[must_use]
+    pub fn into_std(self) -> net::UnixStream {
+        self.inner
+    }
+
+    /// Get the local address of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Get the address of the remote peer of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the address cannot be queried.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Shut down the read half, write half or both halves of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the stream is not connected.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl IntoRawFd for UnixStream {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+impl Debug for UnixStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnixStream")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> AsyncReadWith<'a> for UnixStream {
+    type ReadFuture = ReadSocket<'a>;
+
+    fn read(&'a mut self, buf: ReadBufMut<'a>) -> Self::ReadFuture {
+        ReadSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+}
+
+impl<'a> AsyncWriteWith<'a> for UnixStream {
+    type WriteFuture = WriteSocket<'a>;
+    type WriteVectoredFuture = DefaultWriteVectored<'a, Self>;
+    type FlushFuture = future::Ready<Result<()>>;
+
+    fn write(&'a mut self, buf: &'a [u8]) -> Self::WriteFuture {
+        WriteSocket::new(self.inner.as_raw_fd(), &self.driver, buf)
+    }
+    fn write_vectored(&'a mut self, bufs: &'a [IoSlice<'a>]) -> Self::WriteVectoredFuture {
+        DefaultWriteVectored::new(self, bufs)
+    }
+    fn flush(&'a mut self) -> Self::FlushFuture {
+        future::ready(Ok(()))
+    }
+}
+
+/// Future for [`UnixStream::connect`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Connect {
+    path: PathBuf,
+    op: ConnectOp,
+}
+
+impl CompletionFuture for Connect {
+    type Output = Result<UnixStream>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let path = &this.path;
+        let res = ready!(this.op.poll(|| RawAddr::from_path(path), cx));
+        Poll::Ready(res.map(|(fd, driver)| UnixStream::from_fd(fd, driver)))
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.op.poll_cancel(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use completion_io::ReadBuf;
+
+    use crate::future::block_on;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::{uring, CompletionStreamExt};
+
+    #[test]
+    fn pair() {
+        if !uring::available() {
+            return;
+        }
+
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        block_on(a.write_all(b"ping")).unwrap();
+        let mut bytes = [0; 4];
+        block_on(b.read_exact(ReadBuf::new(&mut bytes).as_mut())).unwrap();
+        assert_eq!(&bytes, b"ping");
+
+        drop(a);
+        let mut rest = Vec::new();
+        block_on(b.read_to_end(&mut rest)).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn listener() {
+        if !uring::available() {
+            return;
+        }
+        let path = std::env::temp_dir().join(format!("completion-unix-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert_eq!(listener.local_addr().unwrap().as_pathname(), Some(&*path));
+
+        let mut client = block_on(UnixStream::connect(&path)).unwrap();
+        let mut incoming = listener.incoming();
+        let mut server = block_on(incoming.next()).unwrap().unwrap();
+
+        block_on(server.write_all(b"pong")).unwrap();
+        server.shutdown(Shutdown::Write).unwrap();
+        let mut received = Vec::new();
+        block_on(client.read_to_end(&mut received)).unwrap();
+        assert_eq!(received, b"pong");
+
+        drop(incoming);
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        assert!(block_on(UnixStream::connect(&path)).is_err());
+        assert!(block_on(UnixStream::connect("\0".repeat(1000))).is_err());
+    }
+}
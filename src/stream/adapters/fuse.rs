@@ -1,7 +1,7 @@
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
-use completion_core::CompletionStream;
+use completion_core::{CompletionStream, FusedCompletionStream};
 use futures_core::{ready, Stream};
 use pin_project_lite::pin_project;
 
@@ -60,6 +60,12 @@ impl<S: CompletionStream> CompletionStream for Fuse<S> {
     }
 }
 
+impl<S: CompletionStream> FusedCompletionStream for Fuse<S> {
+    fn is_terminated(&self) -> bool {
+        self.stream.is_none()
+    }
+}
+
 impl<S: CompletionStream + Stream<Item = <S as CompletionStream>::Item>> Stream for Fuse<S> {
     type Item = <S as CompletionStream>::Item;
 
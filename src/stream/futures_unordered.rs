@@ -0,0 +1,421 @@
+use std::any::Any;
+use std::fmt::{self, Debug, Formatter};
+use std::iter::FromIterator;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use completion_core::{CompletionFuture, CompletionStream};
+
+use crate::future::{block_on, ReadyQueue};
+
+/// A set of futures that are run concurrently, yielding their outputs in the order they complete.
+///
+/// Only the futures that have been woken are polled, so this scales to large numbers of futures.
+/// The stream yields [`None`] once the set is empty; more futures can be pushed after that.
+///
+/// Cancelling the stream through [`poll_cancel`](CompletionStream::poll_cancel) cancels every
+/// future in the set. If one of the futures panics, the others are cancelled and the panic is
+/// resumed once that has finished.
+///
+/// Since a stream may be dropped in between items, for example by
+/// [`take`](crate::CompletionStreamExt::take), dropping the set drops the futures in it that have
+/// never been polled, and cancels the ones that have started, blocking the current thread until
+/// their cancellation has finished. If cancelling one of them panics, the rest are still cancelled
+/// and the panic is resumed afterwards.
+///
+/// That blocking wait deadlocks if the futures can only be cancelled by the thread that drops the
+/// set, such as [`JoinHandle`](crate::executor::JoinHandle)s of tasks on a
+/// [`LocalExecutor`](crate::executor::LocalExecutor) run by that thread. Cancel such sets through
+/// [`poll_cancel`](CompletionStream::poll_cancel) before dropping them.
+///
+/// Requires the `std` feature, as [`catch_unwind`] is needed when polling the futures to ensure
+/// soundness.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{CompletionStreamExt, completion_async_move};
+/// use completion::stream::CompletionFuturesUnordered;
+///
+/// # completion::future::block_on(completion_async_move! {
+/// let mut futures = CompletionFuturesUnordered::new();
+/// for i in 0..5 {
+///     futures.push(completion_async_move!(i * 2));
+/// }
+///
+/// let mut outputs: Vec<_> = futures.collect().await;
+/// outputs.sort_unstable();
+/// assert_eq!(outputs, [0, 2, 4, 6, 8]);
+/// # });
+/// ```
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+#[must_use = "streams do nothing unless you use them"]
+pub struct CompletionFuturesUnordered<F: CompletionFuture> {
+    /// The futures in the set, indexed by their position in the ready queue.
+    slots: Vec<Option<Slot<F>>>,
+    /// Unused indices in `slots`.
+    free: Vec<usize>,
+    len: usize,
+    queue: ReadyQueue,
+    /// Whether all the futures are being cancelled.
+    cancelling: bool,
+    /// The payload of a panic that will be resumed once all the futures have been cancelled.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+struct Slot<F> {
+    future: Pin<Box<F>>,
+    waker: Waker,
+    /// Whether the future has been polled, and so must be completed before it is dropped.
+    started: bool,
+}
+
+impl<F: CompletionFuture> CompletionFuturesUnordered<F> {
+    /// Create an empty set of futures.
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            queue: ReadyQueue::default(),
+            cancelling: false,
+            panic: None,
+        }
+    }
+
+    /// Get the number of futures in the set.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get whether the set contains no futures.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a future to the set.
+    ///
+    /// The future will not be polled until the stream is next polled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream is being cancelled.
+    pub fn push(&mut self, future: F) {
+        assert!(
+            !self.cancelling,
+            "pushed a future to a cancelling `CompletionFuturesUnordered`"
+        );
+
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(None);
+            self.slots.len() - 1
+        });
+        self.slots[index] = Some(Slot {
+            future: Box::pin(future),
+            waker: self.queue.waker(index),
+            started: false,
+        });
+        self.len += 1;
+        self.queue.push(index);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.slots[index] = None;
+        self.free.push(index);
+        self.len -= 1;
+    }
+
+    /// Start cancelling every future in the set.
+    fn start_cancelling(&mut self) {
+        self.cancelling = true;
+        for index in 0..self.slots.len() {
+            match &self.slots[index] {
+                Some(slot) if slot.started => self.queue.push(index),
+                Some(_) => self.remove(index),
+                None => {}
+            }
+        }
+    }
+
+    /// Drive the cancellation of all the futures. Panics are stored, and resumed once every
+    /// future has been cancelled.
+    fn poll_cancelling(&mut self) -> Poll<()> {
+        for index in self.queue.take() {
+            if let Some(Some(slot)) = self.slots.get_mut(index) {
+                let mut cx = Context::from_waker(&slot.waker);
+                let future = slot.future.as_mut();
+                match catch_unwind(AssertUnwindSafe(|| unsafe { future.poll_cancel(&mut cx) })) {
+                    Ok(Poll::Pending) => continue,
+                    Ok(Poll::Ready(())) => {}
+                    Err(payload) => {
+                        if self.panic.is_none() {
+                            self.panic = Some(payload);
+                        }
+                    }
+                }
+                self.remove(index);
+            }
+        }
+
+        if self.len > 0 {
+            return Poll::Pending;
+        }
+        if let Some(payload) = self.panic.take() {
+            resume_unwind(payload);
+        }
+        Poll::Ready(())
+    }
+}
+
+impl<F: CompletionFuture> CompletionStream for CompletionFuturesUnordered<F> {
+    type Item = F::Output;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.queue.register(cx.waker());
+
+        if !this.cancelling {
+            let mut ready = this.queue.take().into_iter();
+            while let Some(index) = ready.next() {
+                let Some(Some(slot)) = this.slots.get_mut(index) else {
+                    continue;
+                };
+                slot.started = true;
+                let mut cx = Context::from_waker(&slot.waker);
+                let future = slot.future.as_mut();
+                let poll = catch_unwind(AssertUnwindSafe(|| future.poll(&mut cx)));
+                match poll {
+                    Ok(Poll::Pending) => {}
+                    Ok(Poll::Ready(output)) => {
+                        this.remove(index);
+                        // Keep the futures that weren't polled in the queue for next time.
+                        for index in ready {
+                            this.queue.push(index);
+                        }
+                        return Poll::Ready(Some(output));
+                    }
+                    Err(payload) => {
+                        this.remove(index);
+                        this.panic = Some(payload);
+                        this.start_cancelling();
+                        break;
+                    }
+                }
+            }
+
+            if !this.cancelling {
+                return if this.len == 0 {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
+            }
+        }
+
+        assert!(
+            this.panic.is_some(),
+            "Called `poll_next` after `poll_cancel` on `CompletionFuturesUnordered`"
+        );
+        match this.poll_cancelling() {
+            Poll::Ready(()) => unreachable!(),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        this.queue.register(cx.waker());
+
+        if !this.cancelling {
+            this.start_cancelling();
+        }
+        this.poll_cancelling()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<F: CompletionFuture> Drop for CompletionFuturesUnordered<F> {
+    fn drop(&mut self) {
+        /// Drives a started future through cancellation.
+        struct Cancel<'a, F>(Pin<&'a mut F>);
+        impl<F: CompletionFuture> CompletionFuture for Cancel<'_, F> {
+            type Output = ();
+            unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                self.0.as_mut().poll_cancel(cx)
+            }
+            unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                self.poll(cx)
+            }
+        }
+
+        let mut panic = None;
+        for slot in self.slots.drain(..).flatten() {
+            if slot.started {
+                let mut future = slot.future;
+                if let Err(payload) =
+                    catch_unwind(AssertUnwindSafe(|| block_on(Cancel(future.as_mut()))))
+                {
+                    panic.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = panic {
+            if !thread::panicking() {
+                resume_unwind(payload);
+            }
+        }
+    }
+}
+
+impl<F: CompletionFuture> Default for CompletionFuturesUnordered<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: CompletionFuture> Extend<F> for CompletionFuturesUnordered<F> {
+    fn extend<I: IntoIterator<Item = F>>(&mut self, iter: I) {
+        for future in iter {
+            self.push(future);
+        }
+    }
+}
+
+impl<F: CompletionFuture> FromIterator<F> for CompletionFuturesUnordered<F> {
+    fn from_iter<I: IntoIterator<Item = F>>(iter: I) -> Self {
+        let mut futures = Self::new();
+        futures.extend(iter);
+        futures
+    }
+}
+
+impl<F: CompletionFuture> Debug for CompletionFuturesUnordered<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionFuturesUnordered")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::ready;
+
+    use crate::future::{CompletionFutureExt, FutureExt};
+    use crate::stream::CompletionStreamExt;
+    use crate::test_utils::{poll_once, Forever, Polls, Yield};
+
+    #[test]
+    fn completion_order() {
+        let futures: CompletionFuturesUnordered<_> = [3, 0, 4, 1, 2]
+            .iter()
+            .map(|&i| Yield::new(i * 10, ready(Box::new(i))))
+            .collect();
+        assert_eq!(futures.size_hint(), (5, Some(5)));
+        assert_eq!(
+            block_on(futures.collect::<Vec<_>>()),
+            (0..5).map(Box::new).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn polls_only_ready() {
+        let polls = Polls::new();
+        let mut futures = CompletionFuturesUnordered::new();
+        for _ in 0..3 {
+            futures.push(Forever::new(&polls, 0).boxed_local());
+        }
+        futures.push(Yield::new(10, ready(Box::new(5))).boxed_local());
+
+        assert_eq!(block_on(futures.next()), Some(Box::new(5)));
+        assert_eq!(polls.polled(), 3);
+
+        block_on(Cancel(&mut futures));
+        assert_eq!(polls.cancelled(), 3);
+    }
+
+    /// Cancels a stream.
+    struct Cancel<'a, S>(&'a mut S);
+    impl<S: CompletionStream + Unpin> CompletionFuture for Cancel<'_, S> {
+        type Output = ();
+        unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            Pin::new(&mut *self.0).poll_cancel(cx)
+        }
+        unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn cancel() {
+        let polls = Polls::new();
+        let mut futures: CompletionFuturesUnordered<_> =
+            (0..5).map(|i| Forever::<()>::new(&polls, i)).collect();
+        assert!(poll_once(futures.next()).is_none());
+
+        // Never polled, so it is dropped without being cancelled.
+        futures.push(Forever::new(&polls, 0));
+
+        block_on(Cancel(&mut futures));
+        assert_eq!(polls.cancelled(), 5);
+        assert!(futures.is_empty());
+    }
+
+    #[test]
+    fn drop_cancels_started() {
+        let polls = Polls::new();
+        let mut futures = CompletionFuturesUnordered::new();
+        for i in 0..3 {
+            futures.push(Forever::new(&polls, i).boxed_local());
+        }
+        futures.push(Yield::once(ready(Box::new(1))).boxed_local());
+        assert_eq!(
+            block_on((&mut futures).take(1).collect::<Vec<_>>()),
+            [Box::new(1)]
+        );
+
+        // Never polled, so it is dropped without being cancelled.
+        futures.push(Forever::new(&polls, 0).boxed_local());
+        drop(futures);
+        assert_eq!(polls.cancelled(), 3);
+    }
+
+    #[test]
+    fn take_drops_started() {
+        let polls = Polls::new();
+        let mut futures = CompletionFuturesUnordered::new();
+        futures.push(Forever::new(&polls, 1).boxed_local());
+        futures.push(Yield::once(ready(Box::new(1))).boxed_local());
+        futures.push(ready(Box::new(2)).into_completion().boxed_local());
+
+        let outputs = block_on(futures.take(2).collect::<Vec<_>>());
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(polls.polled(), 1);
+        assert_eq!(polls.cancel_polls(), 2);
+        assert_eq!(polls.cancelled(), 1);
+    }
+
+    #[test]
+    fn panics() {
+        let polls = Polls::new();
+        let mut futures = CompletionFuturesUnordered::new();
+        for i in 0..3 {
+            futures.push(Forever::new(&polls, i).boxed_local());
+        }
+        futures
+            .push(Yield::once(async { std::panic::panic_any(5) }.into_completion()).boxed_local());
+
+        let payload = block_on(AssertUnwindSafe(futures.next()).catch_unwind()).unwrap_err();
+        assert_eq!(*payload.downcast::<i32>().unwrap(), 5);
+        assert_eq!(polls.cancelled(), 3);
+    }
+}
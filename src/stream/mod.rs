@@ -10,7 +10,7 @@ use core::task::{Context, Poll};
 
 use completion_core::CompletionFuture;
 #[doc(no_inline)]
-pub use completion_core::CompletionStream;
+pub use completion_core::{CompletionStream, FusedCompletionStream};
 use futures_core::Stream;
 
 use super::{Adapter, MustComplete};
@@ -27,6 +27,11 @@ pub use unfold::*;
 mod from_completion_stream;
 pub use from_completion_stream::FromCompletionStream;
 
+#[cfg(feature = "std")]
+mod futures_unordered;
+#[cfg(feature = "std")]
+pub use futures_unordered::CompletionFuturesUnordered;
+
 /// Extension trait for [`CompletionStream`].
 pub trait CompletionStreamExt: CompletionStream {
     /// A convenience for calling [`CompletionStream::poll_next`] on [`Unpin`] streams.
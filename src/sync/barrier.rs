@@ -0,0 +1,202 @@
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::Mutex;
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// A barrier that lets a number of tasks wait until all of them have reached a point.
+///
+/// Once the given number of tasks have called [`wait`](Self::wait), they are all released and
+/// the barrier can be used again. Cancelling a [`BarrierWait`] future before the barrier is
+/// released withdraws the task, so it no longer counts towards the total.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, sync::Barrier, completion_async};
+///
+/// let barrier = Barrier::new(3);
+///
+/// let results = future::block_on(future::join_all((0..3).map(|_| completion_async! {
+///     barrier.wait().await.is_leader()
+/// })));
+///
+/// // Exactly one task is the leader.
+/// assert_eq!(results.iter().filter(|&&leader| leader).count(), 1);
+/// ```
+#[derive(Debug)]
+pub struct Barrier {
+    state: Mutex<State>,
+    tasks: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Incremented every time the barrier releases its tasks.
+    generation: u64,
+    /// The tasks waiting in the current generation.
+    waiters: Vec<(usize, Waker)>,
+    next_id: usize,
+}
+
+impl Barrier {
+    /// Create a new barrier that releases tasks in groups of `tasks`.
+    ///
+    /// A barrier created with zero tasks behaves like one created with one task: every call to
+    /// `wait` completes immediately.
+    #[must_use]
+    pub fn new(tasks: usize) -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            tasks: tasks.max(1),
+        }
+    }
+
+    /// Wait for all the tasks to reach the barrier.
+    ///
+    /// The task whose arrival releases the barrier is the leader.
+    pub fn wait(&self) -> BarrierWait<'_> {
+        BarrierWait {
+            barrier: self,
+            waiting: None,
+        }
+    }
+}
+
+/// Future for [`Barrier::wait`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct BarrierWait<'a> {
+    barrier: &'a Barrier,
+    /// The ID of this task and the generation it is waiting in, if it has arrived.
+    waiting: Option<(usize, u64)>,
+}
+
+impl BarrierWait<'_> {
+    /// Withdraw from the barrier if it has not yet been released.
+    fn cancel(&mut self) {
+        if let Some((id, generation)) = self.waiting.take() {
+            let mut state = lock(&self.barrier.state);
+            if state.generation == generation {
+                state.waiters.retain(|&(waiter, _)| waiter != id);
+            }
+        }
+    }
+}
+
+impl CompletionFuture for BarrierWait<'_> {
+    type Output = BarrierWaitResult;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let barrier = self.barrier;
+        let mut state = lock(&barrier.state);
+
+        if let Some((id, generation)) = self.waiting {
+            if state.generation != generation {
+                self.waiting = None;
+                return Poll::Ready(BarrierWaitResult { is_leader: false });
+            }
+            let (_, waker) = state
+                .waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+            return Poll::Pending;
+        }
+
+        if state.waiters.len() + 1 == barrier.tasks {
+            state.generation += 1;
+            let waiters = mem::take(&mut state.waiters);
+            drop(state);
+
+            for (_, waker) in waiters {
+                waker.wake();
+            }
+            return Poll::Ready(BarrierWaitResult { is_leader: true });
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.push((id, cx.waker().clone()));
+        self.waiting = Some((id, state.generation));
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.cancel();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for BarrierWait<'_> {
+    fn drop(&mut self) {
+        // Withdraw from the barrier, so that this task no longer counts towards releasing it.
+        self.cancel();
+    }
+}
+
+impl Debug for BarrierWait<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BarrierWait")
+            .field("waiting", &self.waiting.is_some())
+            .finish()
+    }
+}
+
+/// The result of waiting on a [`Barrier`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Whether this task was the one that released the barrier.
+    ///
+    /// Exactly one task is the leader each time the barrier is released.
+    #[must_use]
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{poll_cancel_once, poll_once};
+
+    #[test]
+    fn reuse() {
+        let barrier = Barrier::new(2);
+
+        for _ in 0..2 {
+            let mut first = barrier.wait();
+            assert!(poll_once(&mut first).is_none());
+            assert!(poll_once(&mut first).is_none());
+
+            let leader = poll_once(barrier.wait()).unwrap();
+            assert!(leader.is_leader());
+            assert!(!poll_once(&mut first).unwrap().is_leader());
+        }
+
+        assert!(poll_once(Barrier::new(0).wait()).unwrap().is_leader());
+    }
+
+    #[test]
+    fn cancel() {
+        let barrier = Barrier::new(2);
+
+        let mut first = barrier.wait();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_cancel_once(&mut first));
+
+        // The cancelled task no longer counts towards the total.
+        let mut second = barrier.wait();
+        assert!(poll_once(&mut second).is_none());
+        assert!(poll_once(barrier.wait()).unwrap().is_leader());
+        assert!(!poll_once(&mut second).unwrap().is_leader());
+    }
+}
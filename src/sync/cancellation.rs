@@ -0,0 +1,410 @@
+use core::fmt::{self, Display, Formatter};
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::error::Error;
+use std::sync::{Arc, Mutex, Weak};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use crate::lock;
+
+/// A token that can be used to cooperatively cancel completion futures.
+///
+/// Cloning the token creates another handle to the same token, and cancelling any of the handles
+/// cancels them all. Child tokens created with [`child_token`](Self::child_token) are cancelled
+/// whenever their parent is, but can also be cancelled by themselves without affecting the parent.
+///
+/// Futures can be run until a token is cancelled with
+/// [`CompletionFutureExt::with_cancellation`](crate::CompletionFutureExt::with_cancellation).
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, CompletionFutureExt, completion_async};
+/// use completion::sync::{CancellationToken, Cancelled};
+///
+/// # future::block_on(completion_async! {
+/// let token = CancellationToken::new();
+/// let child = token.child_token();
+///
+/// token.cancel();
+/// assert!(child.is_cancelled());
+///
+/// let output = std::future::pending::<()>().with_cancellation(child).await;
+/// assert_eq!(output, Err(Cancelled));
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    node: Arc<Node>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    state: Mutex<NodeState>,
+    /// A strong reference keeps the ancestors alive, so that the token is still cancelled by them
+    /// after its parent token has been dropped.
+    parent: Option<Arc<Node>>,
+}
+
+#[derive(Debug, Default)]
+struct NodeState {
+    cancelled: bool,
+    /// The wakers of the futures waiting for this token to be cancelled, along with their IDs.
+    waiters: Vec<(usize, Waker)>,
+    next_id: usize,
+    children: Vec<Weak<Node>>,
+}
+
+impl Node {
+    fn cancel(&self) {
+        // Use a worklist instead of recursion so that deep trees of tokens can't overflow the
+        // stack.
+        let mut children = self.cancel_one();
+        while let Some(child) = children.pop() {
+            if let Some(child) = child.upgrade() {
+                children.extend(child.cancel_one());
+            }
+        }
+    }
+
+    /// Cancel this node without its children, returning the children.
+    fn cancel_one(&self) -> Vec<Weak<Node>> {
+        let (waiters, children) = {
+            let mut state = lock(&self.state);
+            if state.cancelled {
+                return Vec::new();
+            }
+            state.cancelled = true;
+            (
+                mem::take(&mut state.waiters),
+                mem::take(&mut state.children),
+            )
+        };
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+        children
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // Release the ancestors one at a time, as dropping a long chain of them recursively could
+        // overflow the stack.
+        let mut parent = self.parent.take();
+        while let Some(node) = parent {
+            parent = Arc::try_unwrap(node)
+                .ok()
+                .and_then(|mut node| node.parent.take());
+        }
+    }
+}
+
+impl CancellationToken {
+    /// Create a new token that has not been cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a child token, which will be cancelled when this token is cancelled.
+    ///
+    /// Cancelling the child token does not cancel this token.
+    #[must_use]
+    pub fn child_token(&self) -> Self {
+        let child = Self {
+            node: Arc::new(Node {
+                state: Mutex::default(),
+                parent: Some(Arc::clone(&self.node)),
+            }),
+        };
+
+        let mut state = lock(&self.node.state);
+        if state.cancelled {
+            lock(&child.node.state).cancelled = true;
+        } else {
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.node));
+        }
+
+        child
+    }
+
+    /// Cancel this token and all of its children, waking any futures waiting on them.
+    ///
+    /// Cancelling a token more than once has no effect.
+    pub fn cancel(&self) {
+        self.node.cancel();
+    }
+
+    /// Get whether this token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        lock(&self.node.state).cancelled
+    }
+
+    /// Wait for this token to be cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use completion::sync::CancellationToken;
+    ///
+    /// # completion::future::block_on(completion::completion_async! {
+    /// let token = CancellationToken::new();
+    /// token.clone().cancel();
+    /// token.cancelled().await;
+    /// # });
+    /// ```
+    pub fn cancelled(&self) -> WaitForCancellation {
+        WaitForCancellation {
+            waiter: Waiter::new(self.clone()),
+        }
+    }
+}
+
+/// A registration of a waker with a token.
+#[derive(Debug)]
+struct Waiter {
+    token: CancellationToken,
+    id: Option<usize>,
+}
+
+impl Waiter {
+    fn new(token: CancellationToken) -> Self {
+        Self { token, id: None }
+    }
+
+    /// Poll for the token to be cancelled, registering the waker if it hasn't been.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = lock(&self.token.node.state);
+        if state.cancelled {
+            return Poll::Ready(());
+        }
+
+        if let Some(id) = self.id {
+            let (_, waker) = state
+                .waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+        } else {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push((id, cx.waker().clone()));
+            self.id = Some(id);
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            lock(&self.token.node.state)
+                .waiters
+                .retain(|(waiter, _)| *waiter != id);
+        }
+    }
+}
+
+/// Future for [`CancellationToken::cancelled`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct WaitForCancellation {
+    waiter: Waiter,
+}
+
+impl Future for WaitForCancellation {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.waiter.poll(cx)
+    }
+}
+
+impl CompletionFuture for WaitForCancellation {
+    type Output = ();
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+pin_project! {
+    /// Future for
+    /// [`CompletionFutureExt::with_cancellation`](crate::CompletionFutureExt::with_cancellation).
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct WithCancellation<F> {
+        #[pin]
+        future: F,
+        waiter: Waiter,
+        cancelling: bool,
+    }
+}
+
+impl<F> WithCancellation<F> {
+    pub(crate) fn new(future: F, token: CancellationToken) -> Self {
+        Self {
+            future,
+            waiter: Waiter::new(token),
+            cancelling: false,
+        }
+    }
+}
+
+impl<F: CompletionFuture> CompletionFuture for WithCancellation<F> {
+    type Output = Result<F::Output, Cancelled>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if !*this.cancelling {
+            if this.waiter.poll(cx).is_pending() {
+                return this.future.poll(cx).map(Ok);
+            }
+            *this.cancelling = true;
+        }
+
+        ready!(this.future.poll_cancel(cx));
+        Poll::Ready(Err(Cancelled))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().future.poll_cancel(cx)
+    }
+}
+
+impl<F> Future for WithCancellation<F>
+where
+    F: CompletionFuture + Future<Output = <F as CompletionFuture>::Output>,
+{
+    type Output = Result<<F as CompletionFuture>::Output, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+/// The error returned when a future was cancelled by a [`CancellationToken`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("operation was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::pending;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::future::{block_on, CompletionFutureExt};
+    use crate::test_utils::{poll_once, Forever, Polls, Yield};
+
+    #[test]
+    fn children() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        let sibling = parent.child_token();
+
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(child.is_cancelled());
+        assert!(grandchild.is_cancelled());
+        assert!(!sibling.is_cancelled());
+
+        parent.clone().cancel();
+        assert!(parent.is_cancelled());
+        assert!(sibling.is_cancelled());
+        assert!(parent.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn dropped_parent() {
+        let root = CancellationToken::new();
+        let leaf = root.child_token().child_token();
+        root.cancel();
+        assert!(leaf.is_cancelled());
+    }
+
+    #[test]
+    fn deep() {
+        let root = CancellationToken::new();
+        let mut leaf = root.clone();
+        for _ in 0..100_000 {
+            leaf = leaf.child_token();
+        }
+        root.cancel();
+        assert!(leaf.is_cancelled());
+        drop(root);
+        drop(leaf);
+    }
+
+    #[test]
+    fn completes() {
+        let token = CancellationToken::new();
+        let output =
+            block_on(Yield::new(3, std::future::ready(5)).with_cancellation(token.clone()));
+        assert_eq!(output, Ok(5));
+
+        token.cancel();
+        assert_eq!(
+            poll_once(std::future::ready(5).with_cancellation(token)),
+            Some(Err(Cancelled))
+        );
+    }
+
+    #[test]
+    fn cancels_inner() {
+        let token = CancellationToken::new();
+        let polls = Polls::new();
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                token.cancel();
+            });
+            let future = Forever::<()>::new(&polls, 2);
+            let output = block_on(future.with_cancellation(token.child_token()));
+            assert_eq!(output, Err(Cancelled));
+        });
+        assert_eq!(polls.cancelled(), 1);
+    }
+
+    #[test]
+    fn wait_for_cancellation() {
+        let token = CancellationToken::new();
+        let mut waiting = Box::pin(token.cancelled());
+        assert_eq!(poll_once(waiting.as_mut()), None);
+        assert_eq!(lock(&token.node.state).waiters.len(), 1);
+        drop(waiting);
+        assert!(lock(&token.node.state).waiters.is_empty());
+
+        token.cancel();
+        assert_eq!(poll_once(token.cancelled()), Some(()));
+        assert_eq!(
+            poll_once(pending::<()>().with_cancellation(token)),
+            Some(Err(Cancelled))
+        );
+    }
+}
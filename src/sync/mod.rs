@@ -0,0 +1,19 @@
+//! Synchronization primitives for use in completion-based asynchronous code.
+
+mod barrier;
+pub use barrier::{Barrier, BarrierWait, BarrierWaitResult};
+
+mod cancellation;
+pub use cancellation::{CancellationToken, Cancelled, WaitForCancellation, WithCancellation};
+
+mod mutex;
+pub use mutex::{Lock, Mutex, MutexGuard};
+
+mod notify;
+pub use notify::{Notified, Notify};
+
+mod semaphore;
+pub use semaphore::{Acquire, AcquireOwned, OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+
+mod wait_group;
+pub use wait_group::{WaitGroup, WaitGroupWait};
@@ -0,0 +1,317 @@
+use core::cell::UnsafeCell;
+use core::fmt::{self, Debug, Formatter};
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::Mutex as StdMutex;
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// An asynchronous mutual exclusion lock.
+///
+/// Tasks waiting for the lock acquire it in the order they started waiting. When the lock is
+/// released it is handed directly to the next waiting task, and cancelling a [`lock`](Self::lock)
+/// future removes it from the queue, passing the lock on to the next task if it had already been
+/// handed the lock.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, sync::Mutex, completion_async};
+/// use futures_lite::future::yield_now;
+///
+/// let mutex = Mutex::new(0);
+///
+/// future::block_on(future::zip((
+///     completion_async! {
+///         let mut guard = mutex.lock().await;
+///         yield_now().await;
+///         *guard += 1;
+///     },
+///     completion_async! {
+///         *mutex.lock().await *= 10;
+///     },
+/// )));
+///
+/// assert_eq!(mutex.into_inner(), 10);
+/// ```
+pub struct Mutex<T: ?Sized> {
+    state: StdMutex<State>,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+#[derive(Debug, Default)]
+struct State {
+    locked: bool,
+    /// The tasks waiting for the lock, in the order they will acquire it.
+    waiters: VecDeque<(usize, Waker)>,
+    /// The waiter the lock has been handed to, which has not yet taken it.
+    handed_to: Option<usize>,
+    next_id: usize,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new unlocked mutex containing the value.
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            state: StdMutex::new(State::default()),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Take the value out of the mutex.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquire the lock, waiting until it is available.
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            id: None,
+        }
+    }
+
+    /// Attempt to acquire the lock without waiting.
+    ///
+    /// Returns [`None`] if the lock is held or other tasks are waiting for it.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = lock(&self.state);
+        if state.locked {
+            None
+        } else {
+            state.locked = true;
+            Some(MutexGuard { mutex: self })
+        }
+    }
+
+    /// Get a mutable reference to the value.
+    ///
+    /// As this takes the mutex mutably, no locking is necessary.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Release the lock, handing it to the next waiter if there is one.
+    fn unlock(&self) {
+        let mut state = lock(&self.state);
+        if let Some((id, waker)) = state.waiters.pop_front() {
+            state.handed_to = Some(id);
+            drop(state);
+            waker.wake();
+        } else {
+            state.locked = false;
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Some(guard) => debug.field("data", &&*guard),
+            None => debug.field("data", &format_args!("<locked>")),
+        };
+        debug.finish()
+    }
+}
+
+/// Future for [`Mutex::lock`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct Lock<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    /// The ID of this waiter, if it is in the queue.
+    id: Option<usize>,
+}
+
+impl<T: ?Sized> Lock<'_, T> {
+    /// Leave the queue, passing the lock on if it was handed to this waiter.
+    fn cancel(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut state = lock(&self.mutex.state);
+            if state.handed_to == Some(id) {
+                state.handed_to = None;
+                drop(state);
+                self.mutex.unlock();
+            } else {
+                state.waiters.retain(|&(waiter, _)| waiter != id);
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized> CompletionFuture for Lock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+        let mut state = lock(&mutex.state);
+
+        if let Some(id) = self.id {
+            if state.handed_to == Some(id) {
+                state.handed_to = None;
+                self.id = None;
+                return Poll::Ready(MutexGuard { mutex });
+            }
+            let (_, waker) = state
+                .waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+        } else if state.locked {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push_back((id, cx.waker().clone()));
+            self.id = Some(id);
+        } else {
+            state.locked = true;
+            return Poll::Ready(MutexGuard { mutex });
+        }
+
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.cancel();
+        Poll::Ready(())
+    }
+}
+
+impl<T: ?Sized> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        // A waiter dropped after being handed the lock must pass it on, or the mutex would stay
+        // locked forever.
+        self.cancel();
+    }
+}
+
+impl<T: ?Sized> Debug for Lock<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lock").field("id", &self.id).finish()
+    }
+}
+
+/// A guard that releases the lock of a [`Mutex`] when dropped.
+///
+/// The guard can be held across `.await` points, and can be sent to and released on other
+/// threads.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for MutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::future::block_on;
+    use crate::test_utils::{poll_cancel_once, poll_once, Yield};
+
+    #[test]
+    fn fifo() {
+        let mutex = Mutex::new(Vec::new());
+
+        let guard = mutex.try_lock().unwrap();
+        assert!(mutex.try_lock().is_none());
+
+        let mut first = mutex.lock();
+        let mut second = mutex.lock();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+        drop(guard);
+
+        // The lock was handed to the first waiter, so it can't be taken by anyone else.
+        assert!(mutex.try_lock().is_none());
+        assert!(poll_once(mutex.lock()).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        poll_once(&mut first).unwrap().push(1);
+        poll_once(&mut second).unwrap().push(2);
+        assert!(mutex.try_lock().is_some());
+        block_on(Yield::new(2, mutex.lock())).push(3);
+
+        drop((first, second));
+        assert_eq!(mutex.into_inner(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn cancel_waiting() {
+        let mutex = Mutex::new(());
+
+        let guard = mutex.try_lock().unwrap();
+        let mut first = mutex.lock();
+        let mut second = mutex.lock();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        assert!(poll_cancel_once(&mut first));
+        drop(guard);
+        assert!(poll_once(&mut second).is_some());
+    }
+
+    #[test]
+    fn cancel_handed_to() {
+        let mutex = Mutex::new(());
+
+        let guard = mutex.try_lock().unwrap();
+        let mut first = mutex.lock();
+        let mut second = mutex.lock();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+        drop(guard);
+
+        // The first waiter has been handed the lock, so cancelling it passes the lock on.
+        assert!(poll_cancel_once(&mut first));
+        let guard = poll_once(&mut second).unwrap();
+        drop(guard);
+        assert!(mutex.try_lock().is_some());
+    }
+}
@@ -0,0 +1,257 @@
+use core::fmt::{self, Debug, Formatter};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// Notifies tasks to wake up.
+///
+/// A task waits for a notification with [`notified`](Self::notified). [`notify_one`] wakes the
+/// task that has been waiting the longest, or, if no task is waiting, stores a single permit
+/// that the next call to `notified` will consume immediately. [`notify_waiters`] wakes every task
+/// currently waiting without storing a permit.
+///
+/// A [`Notified`] future starts waiting when it is first polled. If it is cancelled after
+/// receiving a notification from [`notify_one`], the notification is passed on to the next
+/// waiting task.
+///
+/// [`notify_one`]: Self::notify_one
+/// [`notify_waiters`]: Self::notify_waiters
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, sync::Notify, completion_async};
+///
+/// let notify = Notify::new();
+///
+/// future::block_on(future::zip((
+///     completion_async! {
+///         notify.notified().await;
+///         println!("Received notification");
+///     },
+///     completion_async! {
+///         notify.notify_one();
+///     },
+/// )));
+/// ```
+#[derive(Debug, Default)]
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Whether a call to `notify_one` happened with no waiters.
+    permit: bool,
+    /// The tasks waiting for a notification, in the order they will be notified.
+    waiters: VecDeque<(usize, Waker)>,
+    /// The waiters that have been notified but have not yet been polled, and whether the
+    /// notification came from `notify_one`.
+    notified: Vec<(usize, bool)>,
+    next_id: usize,
+}
+
+impl State {
+    /// Notify the first waiter, or store a permit if there are none.
+    fn notify_one(&mut self) -> Option<Waker> {
+        if let Some((id, waker)) = self.waiters.pop_front() {
+            self.notified.push((id, true));
+            Some(waker)
+        } else {
+            self.permit = true;
+            None
+        }
+    }
+}
+
+impl Notify {
+    /// Create a new `Notify` with no stored permit.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for a notification.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            id: None,
+        }
+    }
+
+    /// Notify the task that has been waiting the longest, or store a permit if no tasks are
+    /// waiting.
+    pub fn notify_one(&self) {
+        let waker = lock(&self.state).notify_one();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Notify all the tasks that are currently waiting.
+    pub fn notify_waiters(&self) {
+        let mut state = lock(&self.state);
+        let waiters = core::mem::take(&mut state.waiters);
+        state
+            .notified
+            .extend(waiters.iter().map(|&(id, _)| (id, false)));
+        drop(state);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+/// Future for [`Notify::notified`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    /// The ID of this waiter, if it is registered.
+    id: Option<usize>,
+}
+
+impl Notified<'_> {
+    /// Stop waiting, passing on a notification from `notify_one` if one was received.
+    fn cancel(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut state = lock(&self.notify.state);
+            let waker = if let Some(i) = state.notified.iter().position(|&(n, _)| n == id) {
+                let (_, one) = state.notified.swap_remove(i);
+                if one {
+                    state.notify_one()
+                } else {
+                    None
+                }
+            } else {
+                state.waiters.retain(|&(waiter, _)| waiter != id);
+                None
+            };
+            drop(state);
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl CompletionFuture for Notified<'_> {
+    type Output = ();
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.notify.state);
+
+        if let Some(id) = self.id {
+            if let Some(i) = state.notified.iter().position(|&(n, _)| n == id) {
+                state.notified.swap_remove(i);
+                self.id = None;
+                return Poll::Ready(());
+            }
+            let (_, waker) = state
+                .waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+        } else if state.permit {
+            state.permit = false;
+            return Poll::Ready(());
+        } else {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push_back((id, cx.waker().clone()));
+            self.id = Some(id);
+        }
+
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.cancel();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        // A notification from `notify_one` that this waiter received but never observed is passed
+        // on to the next waiter.
+        self.cancel();
+    }
+}
+
+impl Debug for Notified<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notified").field("id", &self.id).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{poll_cancel_once, poll_once};
+
+    #[test]
+    fn notify_one() {
+        let notify = Notify::new();
+
+        // The permit is stored, but only one.
+        notify.notify_one();
+        notify.notify_one();
+        assert_eq!(poll_once(notify.notified()), Some(()));
+        assert_eq!(poll_once(notify.notified()), None);
+
+        let mut first = notify.notified();
+        let mut second = notify.notified();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        notify.notify_one();
+        assert!(poll_once(&mut second).is_none());
+        assert_eq!(poll_once(&mut first), Some(()));
+        notify.notify_one();
+        assert_eq!(poll_once(&mut second), Some(()));
+    }
+
+    #[test]
+    fn notify_waiters() {
+        let notify = Notify::new();
+        let mut first = notify.notified();
+        let mut second = notify.notified();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        notify.notify_waiters();
+        assert_eq!(poll_once(&mut first), Some(()));
+        assert_eq!(poll_once(&mut second), Some(()));
+        // No permit is stored.
+        assert_eq!(poll_once(notify.notified()), None);
+    }
+
+    #[test]
+    fn cancel() {
+        let notify = Notify::new();
+        let mut first = notify.notified();
+        let mut second = notify.notified();
+        assert!(poll_once(&mut first).is_none());
+        assert!(poll_once(&mut second).is_none());
+
+        // Cancelling a notified waiter passes the notification on.
+        notify.notify_one();
+        assert!(poll_cancel_once(&mut first));
+        assert_eq!(poll_once(&mut second), Some(()));
+
+        // Cancelling a waiting waiter removes it from the queue.
+        let mut third = notify.notified();
+        assert!(poll_once(&mut third).is_none());
+        assert!(poll_cancel_once(&mut third));
+        notify.notify_one();
+        assert_eq!(poll_once(notify.notified()), Some(()));
+    }
+}
@@ -0,0 +1,443 @@
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// An asynchronous counting semaphore.
+///
+/// A semaphore holds a number of permits, which tasks can acquire and release. Tasks waiting for
+/// permits are served in the order they started waiting: released permits are reserved for the
+/// first waiting task until it has all the permits it asked for. Cancelling an
+/// [`acquire`](Self::acquire) future returns any permits it had reserved before the cancellation
+/// finishes.
+///
+/// # Examples
+///
+/// ```
+/// use completion::{future, sync::Semaphore, completion_async};
+///
+/// let semaphore = Semaphore::new(3);
+///
+/// # future::block_on(completion_async! {
+/// let two = semaphore.acquire(2).await;
+/// assert_eq!(semaphore.available_permits(), 1);
+/// assert!(semaphore.try_acquire(2).is_none());
+///
+/// drop(two);
+/// assert!(semaphore.try_acquire(2).is_some());
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    permits: usize,
+    /// The tasks waiting for permits, in the order they will receive them.
+    waiters: VecDeque<Waiting>,
+    /// The waiters that have been given all their permits, but have not yet taken them.
+    granted: Vec<usize>,
+    next_id: usize,
+}
+
+#[derive(Debug)]
+struct Waiting {
+    id: usize,
+    /// The number of permits this waiter still needs.
+    needed: usize,
+    /// The number of permits reserved for this waiter so far.
+    reserved: usize,
+    waker: Waker,
+}
+
+impl State {
+    /// Release permits, giving them to the waiters in order. Returns the wakers of the waiters that
+    /// received all their permits.
+    fn release(&mut self, mut permits: usize) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+
+        while permits > 0 {
+            let Some(waiter) = self.waiters.front_mut() else {
+                break;
+            };
+            let given = permits.min(waiter.needed);
+            waiter.needed -= given;
+            waiter.reserved += given;
+            permits -= given;
+
+            if waiter.needed == 0 {
+                let waiter = self.waiters.pop_front().unwrap();
+                self.granted.push(waiter.id);
+                wakers.push(waiter.waker);
+            }
+        }
+
+        self.permits += permits;
+        wakers
+    }
+}
+
+impl Semaphore {
+    /// Create a new semaphore with the given number of permits.
+    #[must_use]
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                permits,
+                waiters: VecDeque::new(),
+                granted: Vec::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Get the number of permits that are available to be acquired.
+    #[must_use]
+    pub fn available_permits(&self) -> usize {
+        lock(&self.state).permits
+    }
+
+    /// Add permits to the semaphore, giving them to waiting tasks first.
+    pub fn add_permits(&self, permits: usize) {
+        let wakers = lock(&self.state).release(permits);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Acquire a number of permits, waiting until they are available.
+    pub fn acquire(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            waiter: Waiter::new(permits),
+        }
+    }
+
+    /// Acquire a number of permits from a semaphore in an [`Arc`], waiting until they are
+    /// available.
+    ///
+    /// The returned permit holds a reference to the semaphore, so it is not tied to any lifetime.
+    pub fn acquire_owned(self: Arc<Self>, permits: usize) -> AcquireOwned {
+        AcquireOwned {
+            semaphore: Some(self),
+            waiter: Waiter::new(permits),
+        }
+    }
+
+    /// Attempt to acquire a number of permits without waiting.
+    ///
+    /// Returns [`None`] if there aren't enough permits available or other tasks are waiting for
+    /// permits.
+    pub fn try_acquire(&self, permits: usize) -> Option<SemaphorePermit<'_>> {
+        self.try_take(permits).then(|| SemaphorePermit {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    /// Attempt to acquire a number of permits from a semaphore in an [`Arc`] without waiting.
+    ///
+    /// Returns [`None`] if there aren't enough permits available or other tasks are waiting for
+    /// permits.
+    pub fn try_acquire_owned(self: Arc<Self>, permits: usize) -> Option<OwnedSemaphorePermit> {
+        self.try_take(permits).then(|| OwnedSemaphorePermit {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    fn try_take(&self, permits: usize) -> bool {
+        let mut state = lock(&self.state);
+        if permits == 0 || state.waiters.is_empty() && state.permits >= permits {
+            state.permits -= permits;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The state of a future waiting for permits.
+#[derive(Debug)]
+struct Waiter {
+    permits: usize,
+    /// The ID of this waiter, if it is waiting for permits.
+    id: Option<usize>,
+}
+
+impl Waiter {
+    fn new(permits: usize) -> Self {
+        Self { permits, id: None }
+    }
+
+    fn poll(&mut self, semaphore: &Semaphore, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = lock(&semaphore.state);
+
+        if let Some(id) = self.id {
+            if let Some(i) = state.granted.iter().position(|&granted| granted == id) {
+                state.granted.swap_remove(i);
+                self.id = None;
+                return Poll::Ready(());
+            }
+            let waiter = state.waiters.iter_mut().find(|w| w.id == id).unwrap();
+            waiter.waker.clone_from(cx.waker());
+            return Poll::Pending;
+        }
+
+        if self.permits == 0 || state.waiters.is_empty() && state.permits >= self.permits {
+            state.permits -= self.permits;
+            return Poll::Ready(());
+        }
+
+        // Reserve what is available now, and wait for the rest.
+        let reserved = if state.waiters.is_empty() {
+            mem::take(&mut state.permits)
+        } else {
+            0
+        };
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.push_back(Waiting {
+            id,
+            needed: self.permits - reserved,
+            reserved,
+            waker: cx.waker().clone(),
+        });
+        self.id = Some(id);
+
+        Poll::Pending
+    }
+
+    /// Stop waiting, returning any reserved permits to the semaphore.
+    fn cancel(&mut self, semaphore: &Semaphore) {
+        let Some(id) = self.id.take() else {
+            return;
+        };
+
+        let mut state = lock(&semaphore.state);
+        let reserved = if let Some(i) = state.granted.iter().position(|&granted| granted == id) {
+            state.granted.swap_remove(i);
+            self.permits
+        } else {
+            let i = state.waiters.iter().position(|w| w.id == id).unwrap();
+            state.waiters.remove(i).unwrap().reserved
+        };
+        let wakers = state.release(reserved);
+        drop(state);
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Future for [`Semaphore::acquire`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    waiter: Waiter,
+}
+
+impl<'a> CompletionFuture for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.waiter
+            .poll(this.semaphore, cx)
+            .map(|()| SemaphorePermit {
+                semaphore: this.semaphore,
+                permits: this.waiter.permits,
+            })
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        this.waiter.cancel(this.semaphore);
+        Poll::Ready(())
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        // Permits reserved for or granted to this waiter would otherwise be lost, so return them to
+        // the semaphore.
+        self.waiter.cancel(self.semaphore);
+    }
+}
+
+/// Future for [`Semaphore::acquire_owned`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct AcquireOwned {
+    /// The semaphore, until the permits have been acquired.
+    semaphore: Option<Arc<Semaphore>>,
+    waiter: Waiter,
+}
+
+impl CompletionFuture for AcquireOwned {
+    type Output = OwnedSemaphorePermit;
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let semaphore = this
+            .semaphore
+            .as_ref()
+            .expect("`AcquireOwned` polled after completion");
+        futures_core::ready!(this.waiter.poll(semaphore, cx));
+        Poll::Ready(OwnedSemaphorePermit {
+            semaphore: this.semaphore.take().unwrap(),
+            permits: this.waiter.permits,
+        })
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if let Some(semaphore) = &this.semaphore {
+            this.waiter.cancel(semaphore);
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Drop for AcquireOwned {
+    fn drop(&mut self) {
+        if let Some(semaphore) = &self.semaphore {
+            self.waiter.cancel(semaphore);
+        }
+    }
+}
+
+/// Permits acquired from a [`Semaphore`], which are released when this is dropped.
+#[derive(Debug)]
+#[must_use = "permits are released immediately if unused"]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Get the number of permits held.
+    #[must_use]
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forget the permits, so that they are not released back to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+/// Permits acquired from a [`Semaphore`] in an [`Arc`], which are released when this is dropped.
+#[derive(Debug)]
+#[must_use = "permits are released immediately if unused"]
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl OwnedSemaphorePermit {
+    /// Get the number of permits held.
+    #[must_use]
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Get the semaphore the permits were acquired from.
+    #[must_use]
+    pub fn semaphore(&self) -> &Arc<Semaphore> {
+        &self.semaphore
+    }
+
+    /// Forget the permits, so that they are not released back to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::future::block_on;
+    use crate::test_utils::{poll_cancel_once, poll_once};
+
+    #[test]
+    fn fifo() {
+        let semaphore = Semaphore::new(2);
+
+        let mut big = semaphore.acquire(3);
+        let mut small = semaphore.acquire(1);
+        assert!(poll_once(&mut big).is_none());
+        assert_eq!(semaphore.available_permits(), 0);
+
+        // Later acquires wait behind earlier ones, even if there would be enough permits.
+        semaphore.add_permits(1);
+        assert!(poll_once(&mut small).is_none());
+        assert!(semaphore.try_acquire(1).is_none());
+
+        let big = poll_once(&mut big).unwrap();
+        assert_eq!(big.permits(), 3);
+        assert!(poll_once(&mut small).is_none());
+        drop(big);
+
+        assert_eq!(poll_once(&mut small).unwrap().permits(), 1);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn cancel_returns_reserved() {
+        let semaphore = Semaphore::new(2);
+
+        let mut big = semaphore.acquire(3);
+        let mut small = semaphore.acquire(2);
+        assert!(poll_once(&mut big).is_none());
+        assert!(poll_once(&mut small).is_none());
+
+        // The two reserved permits are passed on to the next waiter.
+        assert!(poll_cancel_once(&mut big));
+        assert_eq!(poll_once(&mut small).unwrap().permits(), 2);
+        assert_eq!(semaphore.available_permits(), 2);
+
+        // Permits that were granted but not taken are returned too.
+        let mut big = semaphore.acquire(3);
+        assert!(poll_once(&mut big).is_none());
+        semaphore.add_permits(1);
+        assert!(poll_cancel_once(&mut big));
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn owned_across_threads() {
+        let semaphore = Arc::new(Semaphore::new(1));
+
+        let permit = Arc::clone(&semaphore).try_acquire_owned(1).unwrap();
+        let waiting = Arc::clone(&semaphore).acquire_owned(1);
+        let handle = thread::spawn(move || block_on(waiting).permits());
+        thread::spawn(move || drop(permit)).join().unwrap();
+        assert_eq!(handle.join().unwrap(), 1);
+
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
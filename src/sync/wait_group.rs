@@ -0,0 +1,215 @@
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Arc, Mutex};
+
+use completion_core::CompletionFuture;
+
+use crate::lock;
+
+/// Waits for a group of tasks to finish.
+///
+/// Each task is given a clone of the wait group, which it drops when it is done. Calling
+/// [`wait`](Self::wait) gives up that handle and waits for all the others to be dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use completion::{future, sync::WaitGroup};
+///
+/// let wait_group = WaitGroup::new();
+/// let finished = Arc::new(AtomicUsize::new(0));
+///
+/// for _ in 0..4 {
+///     let wait_group = wait_group.clone();
+///     let finished = Arc::clone(&finished);
+///     thread::spawn(move || {
+///         finished.fetch_add(1, Ordering::SeqCst);
+///         drop(wait_group);
+///     });
+/// }
+///
+/// future::block_on(wait_group.wait());
+/// assert_eq!(finished.load(Ordering::SeqCst), 4);
+/// ```
+pub struct WaitGroup {
+    inner: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The number of handles to the wait group alive.
+    count: usize,
+    /// The tasks waiting for the count to reach zero.
+    waiters: Vec<(usize, Waker)>,
+    next_id: usize,
+}
+
+impl WaitGroup {
+    /// Create a new wait group with a single handle.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(State {
+                count: 1,
+                ..State::default()
+            })),
+        }
+    }
+
+    /// Drop this handle and wait for all the other handles to be dropped.
+    pub fn wait(self) -> WaitGroupWait {
+        let inner = Arc::clone(&self.inner);
+        drop(self);
+        WaitGroupWait { inner, id: None }
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for WaitGroup {
+    fn clone(&self) -> Self {
+        lock(&self.inner).count += 1;
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl Drop for WaitGroup {
+    fn drop(&mut self) {
+        let mut state = lock(&self.inner);
+        state.count -= 1;
+        let waiters = if state.count == 0 {
+            mem::take(&mut state.waiters)
+        } else {
+            Vec::new()
+        };
+        drop(state);
+
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl Debug for WaitGroup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitGroup")
+            .field("count", &lock(&self.inner).count)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future for [`WaitGroup::wait`].
+#[must_use = "futures do nothing unless you use them"]
+pub struct WaitGroupWait {
+    inner: Arc<Mutex<State>>,
+    /// The ID of this waiter, if it is registered.
+    id: Option<usize>,
+}
+
+impl WaitGroupWait {
+    /// Stop waiting.
+    fn cancel(&mut self) {
+        if let Some(id) = self.id.take() {
+            lock(&self.inner)
+                .waiters
+                .retain(|&(waiter, _)| waiter != id);
+        }
+    }
+}
+
+impl CompletionFuture for WaitGroupWait {
+    type Output = ();
+
+    unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut state = lock(&this.inner);
+
+        if state.count == 0 {
+            // The waker was removed when the count reached zero.
+            this.id = None;
+            return Poll::Ready(());
+        }
+
+        if let Some(id) = this.id {
+            let (_, waker) = state
+                .waiters
+                .iter_mut()
+                .find(|(waiter, _)| *waiter == id)
+                .unwrap();
+            waker.clone_from(cx.waker());
+        } else {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiters.push((id, cx.waker().clone()));
+            this.id = Some(id);
+        }
+
+        Poll::Pending
+    }
+    unsafe fn poll_cancel(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        self.cancel();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for WaitGroupWait {
+    fn drop(&mut self) {
+        // Unregister the waker, so that the wait group does not wake a future that is gone.
+        self.cancel();
+    }
+}
+
+impl Debug for WaitGroupWait {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitGroupWait")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{poll_cancel_once, poll_once};
+
+    #[test]
+    fn wait() {
+        let wait_group = WaitGroup::new();
+        let first = wait_group.clone();
+        let second = wait_group.clone();
+
+        let mut wait = wait_group.wait();
+        assert!(poll_once(&mut wait).is_none());
+        drop(first);
+        assert!(poll_once(&mut wait).is_none());
+        drop(second);
+        assert_eq!(poll_once(&mut wait), Some(()));
+    }
+
+    #[test]
+    fn cancel() {
+        let wait_group = WaitGroup::new();
+        let other = wait_group.clone();
+
+        let mut wait = wait_group.clone().wait();
+        assert!(poll_once(&mut wait).is_none());
+        assert!(poll_cancel_once(&mut wait));
+        assert!(lock(&wait.inner).waiters.is_empty());
+        drop(other);
+
+        assert!(poll_once(wait_group.wait()).is_some());
+    }
+}
@@ -0,0 +1,207 @@
+//! Utilities for testing completion futures.
+//!
+//! Cancellation can happen at any point a future returns [`Poll::Pending`], and every one of those
+//! points is a different path through the code that needs to clean up correctly.
+//! [`check_cancellation`] runs a future once for each of those points, so that all of them can be
+//! tested systematically.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+use completion_core::CompletionFuture;
+
+use crate::future::{wake_pair, Parker};
+
+/// The outcome of a single run of a future by [`check_cancellation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome<T> {
+    /// The future completed with a value before it could be cancelled.
+    Completed(T),
+    /// The future was cancelled after being polled the given number of times, and its
+    /// cancellation finished.
+    Cancelled(usize),
+}
+
+/// Test cancelling a future at every point it can be cancelled at.
+///
+/// This first creates a future with `make_future` and runs it to completion, counting the number
+/// of times it is polled. It then creates a new future for every poll point, polls it up to that
+/// point and then cancels it, running [`poll_cancel`](CompletionFuture::poll_cancel) until its
+/// cancellation finishes. The outcome of each of those runs is passed to `check`, and the output
+/// of the first run is returned.
+///
+/// Futures that need to be woken are waited on, so each run behaves as it would under
+/// [`block_on`](crate::future::block_on).
+///
+/// Wrapping the futures used inside the tested future in
+/// [`CheckedCompletion`](crate::CheckedCompletion) will also check that they are driven correctly
+/// on every path.
+///
+/// # Panics
+///
+/// If a future panics while being tested, the panic is propagated. If its payload is a string, the
+/// panic message is prefixed with the point at which the future was cancelled.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// use completion::test::{check_cancellation, Outcome};
+/// use completion::completion_async;
+/// use futures_lite::future::yield_now;
+///
+/// let resumed = Cell::new(0);
+///
+/// let output = check_cancellation(
+///     || completion_async! {
+///         yield_now().await;
+///         resumed.set(resumed.get() + 1);
+///         yield_now().await;
+///         5
+///     },
+///     |outcome| match outcome {
+///         Outcome::Completed(output) => assert_eq!(output, 5),
+///         Outcome::Cancelled(polls) => assert!(polls < 3),
+///     },
+/// );
+/// assert_eq!(output, 5);
+///
+/// // Resumed once by the complete run, and once by the run cancelled after two polls.
+/// assert_eq!(resumed.get(), 2);
+/// ```
+pub fn check_cancellation<F, Fut, C>(mut make_future: F, mut check: C) -> Fut::Output
+where
+    F: FnMut() -> Fut,
+    Fut: CompletionFuture,
+    C: FnMut(Outcome<Fut::Output>),
+{
+    let (parker, waker) = wake_pair();
+    let mut cx = Context::from_waker(&waker);
+
+    let (outcome, polls) = run(make_future(), usize::MAX, &parker, &mut cx);
+    let output = match outcome {
+        Outcome::Completed(output) => output,
+        Outcome::Cancelled(_) => unreachable!(),
+    };
+
+    for cancel_after in 0..polls {
+        let future = make_future();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            run(future, cancel_after, &parker, &mut cx).0
+        }));
+        match result {
+            Ok(outcome) => check(outcome),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+                match message {
+                    Some(message) => panic!(
+                        "future panicked when cancelled after {} polls: {}",
+                        cancel_after, message
+                    ),
+                    None => resume_unwind(payload),
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Poll a future until it completes or it has been polled `max_polls` times, and then cancel it.
+///
+/// Returns the outcome and the number of times the future was polled.
+fn run<F: CompletionFuture>(
+    mut future: F,
+    max_polls: usize,
+    parker: &Parker,
+    cx: &mut Context<'_>,
+) -> (Outcome<F::Output>, usize) {
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+
+    let mut polls = 0;
+    while polls < max_polls {
+        polls += 1;
+        if let Poll::Ready(output) = unsafe { future.as_mut().poll(cx) } {
+            return (Outcome::Completed(output), polls);
+        }
+        parker.park();
+    }
+
+    while unsafe { future.as_mut().poll_cancel(cx) }.is_pending() {
+        parker.park();
+    }
+    (Outcome::Cancelled(polls), polls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::future::ready;
+
+    use crate::future::{self, CompletionFutureExt};
+    use crate::test_utils::Yield;
+    use crate::CheckedCompletion;
+
+    #[test]
+    fn every_point() {
+        let outcomes = RefCell::new(Vec::new());
+        let output = check_cancellation(
+            || Yield::new(2, ready(5)),
+            |outcome| outcomes.borrow_mut().push(outcome),
+        );
+        assert_eq!(output, 5);
+        assert_eq!(
+            outcomes.into_inner(),
+            [
+                Outcome::Cancelled(0),
+                Outcome::Cancelled(1),
+                Outcome::Cancelled(2)
+            ],
+        );
+    }
+
+    #[test]
+    #[should_panic = "future panicked when cancelled after 0 polls: oh no"]
+    fn panic_on_cancel() {
+        struct PanicOnCancel(bool);
+        impl CompletionFuture for PanicOnCancel {
+            type Output = ();
+            unsafe fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                if self.0 {
+                    return Poll::Ready(());
+                }
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+                panic!("oh no");
+            }
+        }
+
+        check_cancellation(|| PanicOnCancel(false), drop);
+    }
+
+    #[test]
+    fn adapters() {
+        let checked = |x| CheckedCompletion::new(Yield::new(2, ready(x)));
+
+        let runs = RefCell::new(0);
+        let output = check_cancellation(
+            || future::zip((checked(1).then(checked), checked(2))),
+            |outcome| {
+                assert_eq!(outcome, Outcome::Cancelled(*runs.borrow()));
+                *runs.borrow_mut() += 1;
+            },
+        );
+        assert_eq!(output, (1, 2));
+        assert_eq!(runs.into_inner(), 5);
+    }
+}
@@ -0,0 +1,201 @@
+//! The timer driver, which wakes timers once their deadlines are reached.
+
+use core::task::{Context, Poll, Waker};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::lock;
+
+/// About 30 years, used in place of deadlines that are too far in the future to represent.
+const FAR_FUTURE: Duration = Duration::new(946_080_000, 0);
+
+/// Add a duration to an instant.
+///
+/// If the result can't be represented, a deadline far in the future is used instead, so that very
+/// long durations such as [`Duration::MAX`] wait effectively forever instead of panicking.
+pub(super) fn deadline_after(instant: Instant, duration: Duration) -> Instant {
+    instant
+        .checked_add(duration)
+        .or_else(|| instant.checked_add(FAR_FUTURE))
+        .unwrap_or(instant)
+}
+
+/// A set of registered timers.
+#[derive(Debug, Default)]
+pub(super) struct Timers {
+    state: Mutex<State>,
+    /// Notified when the earliest deadline changes.
+    condvar: Condvar,
+    clock: Clock,
+}
+
+/// The source of the current time.
+#[derive(Debug, Default)]
+enum Clock {
+    #[default]
+    System,
+    /// A clock that only moves forward when it is advanced manually.
+    Mock(Mutex<Instant>),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The wakers of the timers, keyed by their deadline and ID.
+    entries: BTreeMap<(Instant, u64), Waker>,
+    next_id: u64,
+}
+
+impl Timers {
+    /// Get the timers driven by the global timer thread, starting it if it isn't running.
+    pub(super) fn system() -> Arc<Self> {
+        static SYSTEM: OnceLock<Arc<Timers>> = OnceLock::new();
+
+        Arc::clone(SYSTEM.get_or_init(|| {
+            let timers = Arc::new(Self::default());
+            let driver = Arc::clone(&timers);
+            thread::Builder::new()
+                .name("completion-timer".to_owned())
+                .spawn(move || driver.run())
+                .expect("failed to spawn timer thread");
+            timers
+        }))
+    }
+
+    /// Create timers driven by a mock clock starting at the given instant.
+    pub(super) fn mock(start: Instant) -> Self {
+        Self {
+            clock: Clock::Mock(Mutex::new(start)),
+            ..Self::default()
+        }
+    }
+
+    /// Get the current time.
+    pub(super) fn now(&self) -> Instant {
+        match &self.clock {
+            Clock::System => Instant::now(),
+            Clock::Mock(now) => *lock(now),
+        }
+    }
+
+    /// Advance the mock clock, waking the timers whose deadlines have been reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timers are not driven by a mock clock.
+    pub(super) fn advance(&self, duration: Duration) {
+        let now = match &self.clock {
+            Clock::System => panic!("cannot advance the system clock"),
+            Clock::Mock(now) => {
+                let mut now = lock(now);
+                *now = deadline_after(*now, duration);
+                *now
+            }
+        };
+
+        let wakers = Self::take_expired(&mut lock(&self.state), now);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Remove the timers whose deadlines have been reached, returning their wakers.
+    fn take_expired(state: &mut State, now: Instant) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        while let Some(entry) = state.entries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            wakers.push(entry.remove());
+        }
+        wakers
+    }
+
+    fn run(&self) {
+        let mut state = lock(&self.state);
+        loop {
+            let now = self.now();
+            let wakers = Self::take_expired(&mut state, now);
+
+            if !wakers.is_empty() {
+                drop(state);
+                for waker in wakers {
+                    waker.wake();
+                }
+                state = lock(&self.state);
+                continue;
+            }
+
+            state = if let Some(&(deadline, _)) = state.entries.keys().next() {
+                self.condvar
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            } else {
+                self.condvar
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner)
+            };
+        }
+    }
+}
+
+/// A timer registered with a set of [`Timers`].
+#[derive(Debug)]
+pub(super) struct Entry {
+    timers: Arc<Timers>,
+    key: Option<(Instant, u64)>,
+}
+
+impl Entry {
+    pub(super) fn new(timers: Arc<Timers>) -> Self {
+        Self { timers, key: None }
+    }
+
+    /// Get the current time according to the timers.
+    pub(super) fn now(&self) -> Instant {
+        self.timers.now()
+    }
+
+    /// Poll for the deadline to be reached, registering the waker if it hasn't been.
+    pub(super) fn poll(&mut self, deadline: Instant, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = lock(&self.timers.state);
+
+        let id = if let Some(key) = self.key.take() {
+            state.entries.remove(&key);
+            key.1
+        } else {
+            let id = state.next_id;
+            state.next_id += 1;
+            id
+        };
+
+        if self.timers.now() >= deadline {
+            return Poll::Ready(());
+        }
+
+        let key = (deadline, id);
+        let earliest = state
+            .entries
+            .keys()
+            .next()
+            .map_or(true, |&first| key < first);
+        state.entries.insert(key, cx.waker().clone());
+        self.key = Some(key);
+
+        if earliest {
+            self.timers.condvar.notify_one();
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            lock(&self.timers.state).entries.remove(&key);
+        }
+    }
+}
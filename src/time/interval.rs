@@ -0,0 +1,116 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use completion_core::CompletionStream;
+use futures_core::{ready, Stream};
+
+use super::driver::{deadline_after, Entry, Timers};
+use super::Sleep;
+
+/// Create a stream that yields at a fixed period, starting immediately.
+///
+/// Each item is the [`Instant`] at which that tick was scheduled. If the stream falls behind by a
+/// whole period, the missed ticks are skipped and later ticks are scheduled relative to the time
+/// the late tick was yielded.
+///
+/// # Panics
+///
+/// Panics if the period is zero.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use completion::{time, CompletionStreamExt};
+///
+/// # completion::future::block_on(completion::completion_async! {
+/// let mut interval = time::interval(Duration::from_millis(10));
+///
+/// let first = interval.next().await.unwrap();
+/// let second = interval.next().await.unwrap();
+/// assert!(second - first >= Duration::from_millis(10));
+/// # });
+/// ```
+pub fn interval(period: Duration) -> Interval {
+    let timers = Timers::system();
+    let start = timers.now();
+    Interval::new(Entry::new(timers), start, period)
+}
+
+/// Create a stream that yields at a fixed period, starting at the given instant.
+///
+/// See [`interval`] for more details.
+///
+/// # Panics
+///
+/// Panics if the period is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    Interval::new(Entry::new(Timers::system()), start, period)
+}
+
+/// Stream for [`interval`] and [`interval_at`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless you use them"]
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Interval {
+    pub(super) fn new(entry: Entry, start: Instant, period: Duration) -> Self {
+        assert!(
+            period > Duration::ZERO,
+            "`interval` period must be non-zero"
+        );
+        Self {
+            sleep: Sleep::new(entry, start),
+            period,
+        }
+    }
+
+    /// Get the period of the interval.
+    #[must_use]
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        ready!(Future::poll(Pin::new(&mut self.sleep), cx));
+
+        let tick = self.sleep.deadline();
+        let now = self.sleep.now();
+        let next = deadline_after(tick, self.period);
+        let next = if now >= next {
+            deadline_after(now, self.period)
+        } else {
+            next
+        };
+        self.sleep.reset(next);
+
+        Poll::Ready(Some(tick))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl CompletionStream for Interval {
+    type Item = Instant;
+
+    unsafe fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Stream::poll_next(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        Stream::size_hint(self)
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use completion_core::CompletionFuture;
+
+use super::driver::{deadline_after, Entry, Timers};
+use super::{Interval, Sleep, Timeout};
+
+/// A clock that only moves forward when it is advanced, for deterministic tests of code that
+/// uses timers.
+///
+/// Timers created through a mock clock are completely independent of the system clock: they only
+/// fire when [`advance`](Self::advance) moves the clock past their deadlines. Cloning the clock
+/// creates another handle to the same clock.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use completion::{future, time::MockClock, completion_async};
+///
+/// let clock = MockClock::new();
+/// let output = future::block_on(future::zip((
+///     clock.timeout(Duration::from_secs(10), std::future::pending::<()>()),
+///     completion_async! {
+///         clock.advance(Duration::from_secs(5));
+///         clock.advance(Duration::from_secs(5));
+///     },
+/// )));
+/// assert!(output.0.is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    timers: Arc<Timers>,
+}
+
+impl MockClock {
+    /// Create a new mock clock, starting at the current time.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// Create a new mock clock, starting at the given instant.
+    #[must_use]
+    pub fn starting_at(start: Instant) -> Self {
+        Self {
+            timers: Arc::new(Timers::mock(start)),
+        }
+    }
+
+    /// Get the current time according to this clock.
+    #[must_use]
+    pub fn now(&self) -> Instant {
+        self.timers.now()
+    }
+
+    /// Move the clock forward, waking every timer whose deadline has been reached.
+    pub fn advance(&self, duration: Duration) {
+        self.timers.advance(duration);
+    }
+
+    /// Wait until a duration has elapsed on this clock.
+    ///
+    /// See [`sleep`](super::sleep) for more details.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        self.sleep_until(deadline_after(self.now(), duration))
+    }
+
+    /// Wait until this clock reaches a deadline.
+    ///
+    /// See [`sleep_until`](super::sleep_until) for more details.
+    pub fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Sleep::new(self.entry(), deadline)
+    }
+
+    /// Create a stream that yields at a fixed period of this clock, starting immediately.
+    ///
+    /// See [`interval`](super::interval) for more details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period is zero.
+    pub fn interval(&self, period: Duration) -> Interval {
+        self.interval_at(self.now(), period)
+    }
+
+    /// Create a stream that yields at a fixed period of this clock, starting at the given
+    /// instant.
+    ///
+    /// See [`interval`](super::interval) for more details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period is zero.
+    pub fn interval_at(&self, start: Instant, period: Duration) -> Interval {
+        Interval::new(self.entry(), start, period)
+    }
+
+    /// Run a future until a duration has elapsed on this clock.
+    ///
+    /// See [`timeout`](super::timeout) for more details.
+    pub fn timeout<F: CompletionFuture>(&self, duration: Duration, future: F) -> Timeout<F> {
+        Timeout::new(future, self.sleep(duration))
+    }
+
+    /// Run a future until this clock reaches a deadline.
+    ///
+    /// See [`timeout`](super::timeout) for more details.
+    pub fn timeout_at<F: CompletionFuture>(&self, deadline: Instant, future: F) -> Timeout<F> {
+        Timeout::new(future, self.sleep_until(deadline))
+    }
+
+    fn entry(&self) -> Entry {
+        Entry::new(Arc::clone(&self.timers))
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::poll_once;
+    use crate::CompletionStreamExt;
+
+    #[test]
+    fn sleep() {
+        let clock = MockClock::new();
+        let start = clock.now();
+
+        let mut sleep = clock.sleep(Duration::from_secs(2));
+        assert_eq!(sleep.deadline(), start + Duration::from_secs(2));
+        assert_eq!(poll_once(&mut sleep), None);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(!sleep.is_elapsed());
+        assert_eq!(poll_once(&mut sleep), None);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(sleep.is_elapsed());
+        assert_eq!(poll_once(&mut sleep), Some(()));
+        assert_eq!(clock.now(), start + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn forever() {
+        let clock = MockClock::new();
+
+        let mut sleep = clock.sleep(Duration::MAX);
+        let mut interval = clock.interval(Duration::MAX);
+        assert_eq!(poll_once(interval.next()), Some(Some(clock.now())));
+
+        // A year.
+        clock.advance(Duration::new(31_536_000, 0));
+        assert_eq!(poll_once(&mut sleep), None);
+        assert_eq!(poll_once(interval.next()), None);
+        assert_eq!(
+            poll_once(&mut clock.timeout(Duration::MAX, std::future::ready(5))),
+            Some(Ok(5))
+        );
+
+        clock.advance(Duration::MAX);
+        assert_eq!(poll_once(&mut sleep), Some(()));
+    }
+
+    #[test]
+    fn interval() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let period = Duration::from_secs(1);
+        let mut interval = clock.interval(period);
+
+        assert_eq!(poll_once(interval.next()), Some(Some(start)));
+        assert_eq!(poll_once(interval.next()), None);
+
+        clock.advance(period);
+        assert_eq!(poll_once(interval.next()), Some(Some(start + period)));
+        assert_eq!(poll_once(interval.next()), None);
+
+        // Missed ticks are skipped.
+        clock.advance(period * 3 + period / 2);
+        assert_eq!(poll_once(interval.next()), Some(Some(start + period * 2)));
+        assert_eq!(poll_once(interval.next()), None);
+        clock.advance(period);
+        assert_eq!(
+            poll_once(interval.next()),
+            Some(Some(start + period * 5 + period / 2))
+        );
+    }
+}
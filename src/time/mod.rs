@@ -0,0 +1,19 @@
+//! Utilities for tracking time.
+//!
+//! Timers are driven by a background thread that is started the first time a timer is used. For
+//! deterministic tests, timers can instead be created from a [`MockClock`], which only moves
+//! forward when it is advanced.
+
+mod driver;
+
+mod mock;
+pub use mock::MockClock;
+
+mod sleep;
+pub use sleep::{sleep, sleep_until, Sleep};
+
+mod interval;
+pub use interval::{interval, interval_at, Interval};
+
+mod timeout;
+pub use timeout::{timeout, timeout_at, Elapsed, Timeout};
@@ -0,0 +1,106 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use completion_core::CompletionFuture;
+
+use super::driver::{deadline_after, Entry, Timers};
+
+/// Wait until a duration has elapsed.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// use completion::time;
+///
+/// # completion::future::block_on(completion::completion_async! {
+/// let start = Instant::now();
+/// time::sleep(Duration::from_millis(10)).await;
+/// assert!(start.elapsed() >= Duration::from_millis(10));
+/// # });
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    let timers = Timers::system();
+    let deadline = deadline_after(timers.now(), duration);
+    Sleep::new(Entry::new(timers), deadline)
+}
+
+/// Wait until a deadline is reached.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// use completion::time;
+///
+/// # completion::future::block_on(completion::completion_async! {
+/// let deadline = Instant::now() + Duration::from_millis(10);
+/// time::sleep_until(deadline).await;
+/// assert!(Instant::now() >= deadline);
+/// # });
+/// ```
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(Entry::new(Timers::system()), deadline)
+}
+
+/// Future for [`sleep`] and [`sleep_until`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you use them"]
+pub struct Sleep {
+    entry: Entry,
+    deadline: Instant,
+}
+
+impl Sleep {
+    pub(super) fn new(entry: Entry, deadline: Instant) -> Self {
+        Self { entry, deadline }
+    }
+
+    /// Get the deadline at which this future will complete.
+    #[must_use]
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Get whether the deadline has been reached.
+    #[must_use]
+    pub fn is_elapsed(&self) -> bool {
+        self.entry.now() >= self.deadline
+    }
+
+    /// Change the deadline at which this future will complete.
+    ///
+    /// This can be used to reuse the future after it has completed.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+    }
+
+    /// Get the current time according to the clock driving this timer.
+    pub(super) fn now(&self) -> Instant {
+        self.entry.now()
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let deadline = self.deadline;
+        self.entry.poll(deadline, cx)
+    }
+}
+
+impl CompletionFuture for Sleep {
+    type Output = ();
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Future::poll(self, cx)
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
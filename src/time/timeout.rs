@@ -0,0 +1,188 @@
+use core::fmt::{self, Display, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use completion_core::CompletionFuture;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use super::{sleep, sleep_until, Sleep};
+
+/// Run a future until a duration has elapsed.
+///
+/// If the future hasn't completed by the time the duration has elapsed, it is cancelled with
+/// [`poll_cancel`](CompletionFuture::poll_cancel), and [`Elapsed`] is returned once its
+/// cancellation has finished. This can also be called with
+/// [`CompletionFutureExt::timeout`](crate::CompletionFutureExt::timeout).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use completion::{time, completion_async};
+///
+/// # completion::future::block_on(completion_async! {
+/// let output = time::timeout(Duration::from_secs(5), completion_async!(5)).await;
+/// assert_eq!(output, Ok(5));
+///
+/// let output = time::timeout(Duration::from_millis(10), std::future::pending::<()>()).await;
+/// assert!(output.is_err());
+/// # });
+/// ```
+pub fn timeout<F: CompletionFuture>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout::new(future, sleep(duration))
+}
+
+/// Run a future until a deadline is reached.
+///
+/// See [`timeout`] for more details.
+pub fn timeout_at<F: CompletionFuture>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout::new(future, sleep_until(deadline))
+}
+
+pin_project! {
+    /// Future for [`timeout`], [`timeout_at`] and
+    /// [`CompletionFutureExt::timeout`](crate::CompletionFutureExt::timeout).
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you use them"]
+    pub struct Timeout<F> {
+        #[pin]
+        future: F,
+        sleep: Sleep,
+        cancelling: bool,
+    }
+}
+
+impl<F> Timeout<F> {
+    pub(crate) fn new(future: F, sleep: Sleep) -> Self {
+        Self {
+            future,
+            sleep,
+            cancelling: false,
+        }
+    }
+
+    /// Get the deadline after which the future will be cancelled.
+    #[must_use]
+    pub fn deadline(&self) -> Instant {
+        self.sleep.deadline()
+    }
+}
+
+impl<F: CompletionFuture> CompletionFuture for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    unsafe fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if !*this.cancelling {
+            if let Poll::Ready(output) = this.future.as_mut().poll(cx) {
+                return Poll::Ready(Ok(output));
+            }
+            ready!(Future::poll(Pin::new(this.sleep), cx));
+            *this.cancelling = true;
+        }
+
+        ready!(this.future.poll_cancel(cx));
+        Poll::Ready(Err(Elapsed))
+    }
+    unsafe fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.project().future.poll_cancel(cx)
+    }
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: CompletionFuture + Future<Output = <F as CompletionFuture>::Output>,
+{
+    type Output = Result<<F as CompletionFuture>::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { CompletionFuture::poll(self, cx) }
+    }
+}
+
+/// The error returned when a future passed its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::ready;
+
+    use crate::future::{block_on, zip, FutureExt};
+    use crate::test_utils::{poll_once, Forever, Polls, Yield};
+    use crate::time::MockClock;
+
+    #[test]
+    fn completes() {
+        let clock = MockClock::new();
+
+        let output = block_on(clock.timeout(Duration::from_secs(1), Yield::new(3, ready(5))));
+        assert_eq!(output, Ok(5));
+
+        // A future that is ready once the deadline has passed still succeeds.
+        let output = block_on(clock.timeout(Duration::ZERO, ready(5)));
+        assert_eq!(output, Ok(5));
+    }
+
+    #[test]
+    fn forever() {
+        use crate::CompletionFutureExt;
+
+        assert_eq!(poll_once(&mut sleep(Duration::MAX)), None);
+
+        let output = block_on(timeout(Duration::MAX, Yield::once(ready(5))));
+        assert_eq!(output, Ok(5));
+        let output = block_on(Yield::once(ready(5)).timeout(Duration::MAX));
+        assert_eq!(output, Ok(5));
+    }
+
+    #[test]
+    fn elapses() {
+        let clock = MockClock::new();
+        let polls = Polls::new();
+
+        let future = clock.timeout(Duration::from_secs(10), Forever::<()>::new(&polls, 1));
+        futures_lite::pin!(future);
+
+        assert_eq!(poll_once(future.as_mut()), None);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(poll_once(future.as_mut()), None);
+        assert_eq!(polls.cancel_polls(), 0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(poll_once(future.as_mut()), None);
+        assert_eq!(polls.cancel_polls(), 1);
+        assert_eq!(poll_once(future.as_mut()), Some(Err(Elapsed)));
+        assert_eq!(polls.cancel_polls(), 2);
+    }
+
+    #[test]
+    fn advance_wakes() {
+        let clock = MockClock::new();
+        let polls = Polls::new();
+
+        let future = Forever::<()>::new(&polls, 2);
+        let (output, ()) = block_on(zip((
+            clock.timeout(Duration::from_secs(10), future),
+            async { clock.advance(Duration::from_secs(10)) }.into_completion(),
+        )));
+        assert_eq!(output, Err(Elapsed));
+        assert_eq!(polls.cancel_polls(), 3);
+    }
+}
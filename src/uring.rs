@@ -0,0 +1,268 @@
+//! The `io_uring` driver, which submits operations to a shared ring and wakes them once they
+//! complete.
+
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::task::{Context, Poll, Waker};
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::process;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use io_uring::{opcode, squeue, IoUring};
+
+use crate::future::wake_pair;
+use crate::lock;
+
+/// The user data of cancellation requests, whose completions are ignored.
+const CANCEL: u64 = u64::MAX;
+
+/// A ring shared by all the operations in the process.
+pub(crate) struct Driver {
+    ring: IoUring,
+    /// Serializes pushing to the submission queue.
+    submit: Mutex<()>,
+    ops: Mutex<Ops>,
+}
+
+#[derive(Debug, Default)]
+struct Ops {
+    /// The state of every operation in flight, keyed by its user data.
+    states: HashMap<u64, State>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+enum State {
+    /// The operation is running, and the waker of the task waiting for it.
+    Running(Option<Waker>),
+    /// The operation completed with this result.
+    Complete(i32),
+}
+
+impl Driver {
+    /// Get the global driver, starting it if it isn't running.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `io_uring` is not available, for example because the kernel is too old or the
+    /// process is sandboxed.
+    pub(crate) fn get() -> io::Result<Arc<Self>> {
+        static DRIVER: OnceLock<Result<Arc<Driver>, i32>> = OnceLock::new();
+
+        let driver = DRIVER.get_or_init(|| {
+            let ring = IoUring::new(256).map_err(|e| e.raw_os_error().unwrap_or(libc::ENOSYS))?;
+            let driver = Arc::new(Self {
+                ring,
+                submit: Mutex::new(()),
+                ops: Mutex::new(Ops::default()),
+            });
+            let reaper = Arc::clone(&driver);
+            thread::Builder::new()
+                .name("completion-io-uring".to_owned())
+                .spawn(move || reaper.run())
+                .expect("failed to spawn io_uring thread");
+            Ok(driver)
+        });
+
+        match driver {
+            Ok(driver) => Ok(Arc::clone(driver)),
+            &Err(code) => Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+
+    /// Push an entry to the submission queue and submit it.
+    fn push(&self, entry: &squeue::Entry) -> io::Result<()> {
+        let _guard = lock(&self.submit);
+
+        // Safety: only this function accesses the submission queue, and it holds the lock.
+        let mut queue = unsafe { self.ring.submission_shared() };
+        // Safety: the caller guarantees that the entry's resources are valid.
+        while unsafe { queue.push(entry) }.is_err() {
+            // Make room by submitting what is already in the queue.
+            queue.sync();
+            self.ring.submit()?;
+            queue.sync();
+        }
+        drop(queue);
+
+        // The entry can't be taken back out of the queue, so it must be submitted.
+        loop {
+            match self.ring.submit() {
+                Ok(_) => return Ok(()),
+                Err(e) if is_transient(&e) => thread::yield_now(),
+                Err(e) => fatal("failed to submit to io_uring", &e),
+            }
+        }
+    }
+
+    fn run(&self) {
+        loop {
+            match self.ring.submit_and_wait(1) {
+                // If the completion queue is full, reaping it makes room.
+                Ok(_) => {}
+                Err(e) if is_transient(&e) => {}
+                Err(e) => fatal("failed to wait for io_uring completions", &e),
+            }
+
+            let mut wakers = Vec::new();
+            let mut ops = lock(&self.ops);
+            // Safety: only this thread accesses the completion queue.
+            for entry in unsafe { self.ring.completion_shared() } {
+                if entry.user_data() == CANCEL {
+                    continue;
+                }
+                if let Some(state) = ops.states.get_mut(&entry.user_data()) {
+                    if let State::Running(Some(waker)) =
+                        mem::replace(state, State::Complete(entry.result()))
+                    {
+                        wakers.push(waker);
+                    }
+                }
+            }
+            drop(ops);
+
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Whether submitting can succeed if it is tried again.
+fn is_transient(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Interrupted
+        || matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::EBUSY))
+}
+
+/// Abort the process after the ring has stopped working.
+///
+/// The kernel may still be using the resources of the operations in flight, and without the ring
+/// there is no way to know when it has finished. So they can neither be completed nor dropped,
+/// and waiting for them would hang forever. Panicking instead would kill the reaper thread or
+/// free the resources of a queued entry, so there is nothing better to do.
+fn fatal(msg: &str, e: &io::Error) -> ! {
+    eprintln!("{msg}: {e}");
+    process::abort()
+}
+
+impl Debug for Driver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Driver")
+            .field("ring", &self.ring.as_raw_fd())
+            .field("ops", &lock(&self.ops).states.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An operation submitted to the ring.
+///
+/// Dropping an operation before it completes cancels it and blocks until it has completed, so
+/// that the resources it uses are not freed while the kernel is using them.
+#[derive(Debug)]
+pub(crate) struct Op {
+    driver: Arc<Driver>,
+    id: u64,
+    /// Whether a cancellation request has been submitted.
+    cancelling: bool,
+    /// Whether the result of the operation has been taken.
+    complete: bool,
+}
+
+impl Op {
+    /// Submit an operation.
+    ///
+    /// # Safety
+    ///
+    /// All the resources referenced by the entry must remain valid until the operation completes,
+    /// that is until [`poll`](Self::poll) or [`poll_cancel`](Self::poll_cancel) returns
+    /// [`Poll::Ready`] or the `Op` is dropped.
+    pub(crate) unsafe fn submit(driver: Arc<Driver>, entry: squeue::Entry) -> io::Result<Self> {
+        let mut ops = lock(&driver.ops);
+        let id = ops.next_id;
+        ops.next_id += 1;
+        ops.states.insert(id, State::Running(None));
+        drop(ops);
+
+        if let Err(e) = driver.push(&entry.user_data(id)) {
+            lock(&driver.ops).states.remove(&id);
+            return Err(e);
+        }
+
+        Ok(Self {
+            driver,
+            id,
+            cancelling: false,
+            complete: false,
+        })
+    }
+
+    /// Poll for the result of the operation.
+    ///
+    /// Negative results are converted to errors.
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u32>> {
+        self.poll_raw(cx)
+            .map(|res| u32::try_from(res).map_err(|_| io::Error::from_raw_os_error(-res)))
+    }
+
+    /// Request cancellation of the operation and poll for it to complete.
+    ///
+    /// This resolves to the result of the operation, which may have completed successfully
+    /// before it could be cancelled.
+    pub(crate) fn poll_cancel(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u32>> {
+        if !self.cancelling && !self.complete {
+            let entry = opcode::AsyncCancel::new(self.id).build().user_data(CANCEL);
+            if self.driver.push(&entry).is_ok() {
+                self.cancelling = true;
+            } else {
+                // The operation might never complete unless it is cancelled, and its resources
+                // can't be freed until it does, so keep trying.
+                let res = self.poll(cx);
+                if res.is_pending() {
+                    cx.waker().wake_by_ref();
+                }
+                return res;
+            }
+        }
+        self.poll(cx)
+    }
+
+    fn poll_raw(&mut self, cx: &mut Context<'_>) -> Poll<i32> {
+        assert!(!self.complete, "`Op` polled after completion");
+
+        let mut ops = lock(&self.driver.ops);
+        let state = ops.states.get_mut(&self.id).unwrap();
+        match state {
+            State::Running(Some(waker)) => waker.clone_from(cx.waker()),
+            State::Running(waker) => *waker = Some(cx.waker().clone()),
+            &mut State::Complete(res) => {
+                ops.states.remove(&self.id);
+                self.complete = true;
+                return Poll::Ready(res);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Op {
+    fn drop(&mut self) {
+        if self.complete {
+            return;
+        }
+
+        let (parker, waker) = wake_pair();
+        let mut cx = Context::from_waker(&waker);
+        while self.poll_cancel(&mut cx).is_pending() {
+            parker.park();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn available() -> bool {
+    Driver::get().is_ok()
+}